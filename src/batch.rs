@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Context, Result};
+use zeroize::Zeroizing;
+
+use crate::utils::split_command_line;
+
+/// One change in a `sentinel batch` script
+pub enum BatchOp {
+    Add { name: String, value: Zeroizing<String> },
+    Expire { name: String, duration: String, sliding: bool },
+    Remove { name: String },
+}
+
+impl BatchOp {
+    /// The secret the operation changes
+    pub fn name(&self) -> &str {
+        match self {
+            BatchOp::Add { name, .. } | BatchOp::Expire { name, .. } | BatchOp::Remove { name } => name,
+        }
+    }
+}
+
+/// Parse a batch script: one operation per line, quoted like a shell
+/// command, with blank lines and `#` comments skipped.
+///
+/// ```text
+/// add db_password "hunter2 hunter2"
+/// expire db_password 30d --sliding
+/// remove old_token
+/// ```
+pub fn parse(script: &str) -> Result<Vec<BatchOp>> {
+    let mut ops = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        
+        let words = split_command_line(line).with_context(|| format!("Line {}", index + 1))?;
+        if words.is_empty() {
+            continue;
+        }
+        ops.push(parse_op(words).with_context(|| format!("Line {}", index + 1))?);
+    }
+    
+    Ok(ops)
+}
+
+fn parse_op(words: Vec<String>) -> Result<BatchOp> {
+    let words = Zeroizing::new(words);
+    let op = match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["add", name, value] => BatchOp::Add { name: name.to_string(), value: Zeroizing::new(value.to_string()) },
+        ["expire", name, duration] => BatchOp::Expire { name: name.to_string(), duration: duration.to_string(), sliding: false },
+        ["expire", name, duration, "--sliding"] => BatchOp::Expire { name: name.to_string(), duration: duration.to_string(), sliding: true },
+        ["remove", name] => BatchOp::Remove { name: name.to_string() },
+        [command, ..] if ["add", "expire", "remove"].contains(command) => {
+            return Err(anyhow!("Expected `add NAME VALUE`, `expire NAME DURATION [--sliding]` or `remove NAME`"));
+        }
+        [command, ..] => return Err(anyhow!("Unknown operation '{}'", command)),
+        [] => unreachable!("blank lines are skipped"),
+    };
+    
+    Ok(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse() {
+        let ops = parse("# rotate the database login\n\nadd db 'new value'\nexpire db 30d --sliding\n  remove old\r\n").unwrap();
+        assert_eq!(ops.len(), 3);
+        assert!(matches!(&ops[0], BatchOp::Add { name, value } if name == "db" && value.as_str() == "new value"));
+        assert!(matches!(&ops[1], BatchOp::Expire { duration, sliding: true, .. } if duration == "30d"));
+        assert!(matches!(&ops[2], BatchOp::Remove { name } if name == "old"));
+        assert_eq!(ops[2].name(), "old");
+        
+        let err = parse("add a b\nrename a b\n").err().unwrap();
+        assert_eq!(format!("{:#}", err), "Line 2: Unknown operation 'rename'");
+        assert!(parse("add only_a_name").is_err());
+        assert!(parse("expire db 1h --fixed").is_err());
+        assert!(parse("add db \"unterminated").is_err());
+    }
+}
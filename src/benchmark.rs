@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+use crate::crypto::{derive_key_with_params, generate_key, generate_salt, CryptoEngine, KdfParams};
+
+/// Memory costs tried, in KiB: from the 19 MiB OWASP minimum up to 256 MiB
+const MEMORY_COSTS: &[u32] = &[19456, 32768, 65536, 131072, 262144];
+const MAX_PASSES: u32 = 4;
+
+/// How long one key derivation took
+#[derive(Debug, Clone, Copy)]
+pub struct KdfTiming {
+    pub params: KdfParams,
+    pub elapsed: Duration,
+}
+
+/// Time Argon2id over a grid of memory and pass counts, on one lane like
+/// `KdfParams::RECOMMENDED`. Once a memory cost is over twice `target`, its
+/// higher pass counts and any larger memory costs are skipped.
+pub fn time_kdf_grid(target: Duration) -> Result<Vec<KdfTiming>> {
+    let salt = generate_salt();
+    let mut timings = Vec::new();
+    
+    for &m_cost in MEMORY_COSTS {
+        for t_cost in 1..=MAX_PASSES {
+            let timing = time_kdf(KdfParams { m_cost, t_cost, p_cost: 1 }, &salt)?;
+            timings.push(timing);
+            if timing.elapsed > target * 2 {
+                if t_cost == 1 {
+                    return Ok(timings);
+                }
+                break;
+            }
+        }
+    }
+    
+    Ok(timings)
+}
+
+pub fn time_kdf(params: KdfParams, salt: &[u8]) -> Result<KdfTiming> {
+    let start = Instant::now();
+    derive_key_with_params("benchmark", salt, &params)?;
+    Ok(KdfTiming { params, elapsed: start.elapsed() })
+}
+
+/// The strongest parameters that finish within `target`: most memory times
+/// passes, then most memory
+pub fn recommend(timings: &[KdfTiming], target: Duration) -> Option<KdfParams> {
+    timings
+        .iter()
+        .filter(|timing| timing.elapsed <= target)
+        .map(|timing| timing.params)
+        .max_by_key(|params| (u64::from(params.m_cost) * u64::from(params.t_cost), params.m_cost))
+}
+
+/// AES-256-GCM encryption and decryption speed over `size` bytes, in MiB/s
+pub fn cipher_throughput(size: usize) -> Result<(f64, f64)> {
+    let engine = CryptoEngine::new(&generate_key());
+    let data = vec![0x5au8; size];
+    let mib = size as f64 / (1024.0 * 1024.0);
+    
+    let start = Instant::now();
+    let encrypted = engine.encrypt_bytes(&data)?;
+    let encrypt = mib / start.elapsed().as_secs_f64();
+    
+    let start = Instant::now();
+    engine.decrypt_bytes(&encrypted)?;
+    let decrypt = mib / start.elapsed().as_secs_f64();
+    
+    Ok((encrypt, decrypt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_recommend() {
+        let timing = |m_cost, t_cost, ms| KdfTiming {
+            params: KdfParams { m_cost, t_cost, p_cost: 1 },
+            elapsed: Duration::from_millis(ms),
+        };
+        let timings = [
+            timing(32768, 1, 40),
+            timing(32768, 4, 160),
+            timing(65536, 2, 160),
+            timing(65536, 3, 240),
+            timing(131072, 3, 520),
+        ];
+        
+        let target = Duration::from_millis(500);
+        assert_eq!(recommend(&timings, target), Some(KdfParams { m_cost: 65536, t_cost: 3, p_cost: 1 }));
+        // Same total cost: more memory wins
+        let target = Duration::from_millis(200);
+        assert_eq!(recommend(&timings, target), Some(KdfParams { m_cost: 65536, t_cost: 2, p_cost: 1 }));
+        assert_eq!(recommend(&timings, Duration::from_millis(10)), None);
+    }
+}
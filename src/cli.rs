@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "sentinel")]
@@ -7,53 +8,549 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    
+    /// Keyfile combined with (or replacing) the master password
+    #[arg(long, global = true)]
+    pub keyfile: Option<PathBuf>,
+    
+    /// Read the master password from a file (SENTINELVAULT_PASSWORD also works)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub password_file: Option<PathBuf>,
+    
+    /// Don't re-hash a master password stored below the recommended Argon2 cost
+    #[arg(long, global = true)]
+    pub no_kdf_upgrade: bool,
+    
+    /// Open the vault even if the system clock is earlier than its last save
+    #[arg(long, global = true)]
+    pub allow_clock_skew: bool,
+    
+    /// Keep expired secrets when opening the vault so they can be inspected
+    #[arg(long, global = true)]
+    pub no_cleanup: bool,
+    
+    /// Show what prune, remove or change-password would do without writing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    
+    /// Log what the tool is doing to stderr: -v for info, -vv for debug. Secret
+    /// values and keys are never logged.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, overrides_with = "quiet")]
+    pub verbose: u8,
+    
+    /// Don't warn about secrets that are about to expire. Whichever of this
+    /// and -v comes last wins, so an alias can be overridden.
+    #[arg(long, global = true, overrides_with = "verbose")]
+    pub quiet: bool,
+    
+    /// Print how long authentication, loading the vault and the command itself take, to stderr
+    #[arg(long, global = true)]
+    pub profile: bool,
+    
+    /// Show timestamps in UTC instead of the local timezone
+    #[arg(long, global = true)]
+    pub utc: bool,
+    
+    /// Use the vault in this directory (overrides SENTINELVAULT_DIR and config.toml)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub vault_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initialize a new vault with master password
-    Init,
+    Init {
+        /// Encrypt the identity file under a separate PIN
+        #[arg(long)]
+        require_pin: bool,
+        /// Unlock with the keyfile alone, without a master password
+        #[arg(long)]
+        keyfile_only: bool,
+        /// Require a TOTP code from an authenticator app at unlock
+        #[arg(long = "enable-2fa")]
+        enable_2fa: bool,
+        /// Largest secret value to accept, in bytes (default 10000, max 1 MiB)
+        #[arg(long, value_name = "BYTES", conflicts_with = "from_backup")]
+        max_secret_size: Option<usize>,
+        /// Most secrets the vault may hold (default unlimited)
+        #[arg(long, value_name = "COUNT", conflicts_with = "from_backup")]
+        max_secrets: Option<usize>,
+        /// Create the vault from a backup file, unlocked by the backup's master password
+        #[arg(long, value_name = "PATH", conflicts_with = "keyfile_only")]
+        from_backup: Option<PathBuf>,
+        /// Mix this machine's id into the key so the vault won't open on another host
+        #[arg(long, conflicts_with = "from_backup")]
+        bind_machine: bool,
+        /// Require a FIDO2 security key (e.g. a YubiKey) with hmac-secret at every unlock
+        #[arg(long, conflicts_with = "from_backup")]
+        fido2: bool,
+        /// Vault file encoding: ron (readable) or binary (smaller and faster for large vaults)
+        #[arg(long, default_value = "ron")]
+        format: String,
+        /// Store environment variables matching these comma-separated globs as secrets (e.g. 'AWS_*,DB_PASSWORD')
+        #[arg(long, value_name = "PATTERNS", conflicts_with = "from_backup")]
+        import_env: Option<String>,
+        /// Raise the Argon2 cost of checking the master password, e.g. 'm=131072,t=4' (default m=65536,t=3,p=1)
+        #[arg(long, value_name = "COSTS", conflicts_with_all = ["from_backup", "keyfile_only"])]
+        hash_cost: Option<String>,
+        /// Argon2 cost of deriving the vault key, if it should differ from the hash's
+        #[arg(long, value_name = "COSTS", conflicts_with_all = ["from_backup", "keyfile_only"])]
+        key_cost: Option<String>,
+    },
     
     /// Add a new secret to the vault
     Add {
         /// Name of the secret
         name: String,
         /// Value of the secret (will be prompted if not provided)
-        #[arg(short, long)]
+        #[arg(long)]
         value: Option<String>,
+        /// Read the value from stdin, dropping one trailing newline. This is
+        /// the default when stdin isn't a terminal and --value isn't given.
+        #[arg(long, conflicts_with = "value")]
+        stdin: bool,
+        /// Require the master password again before the secret is revealed
+        #[arg(long)]
+        protected: bool,
+        /// Do nothing (and succeed) if the secret already exists
+        #[arg(long, conflicts_with = "replace")]
+        if_absent: bool,
+        /// Only overwrite an existing secret; fail if it doesn't exist
+        #[arg(long)]
+        replace: bool,
+        /// Decode the value from base64 or hex before storing it. The result must be UTF-8 text.
+        #[arg(long, value_name = "ENCODING")]
+        decode: Option<String>,
+        /// Label the secret, e.g. with its environment (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Note what the secret is for (stored unencrypted)
+        #[arg(long)]
+        description: Option<String>,
+    },
+    
+    /// Add a structured credential by filling in a built-in template
+    /// (database, aws, oauth). The fields are stored as one JSON secret.
+    AddTemplate {
+        /// Name of the secret
+        name: String,
+        /// Template to fill in
+        #[arg(long)]
+        template: String,
+        /// Require the master password again before the secret is revealed
+        #[arg(long)]
+        protected: bool,
+    },
+    
+    /// Give a secret a second name. Reading the alias returns the secret's
+    /// current value; `remove` the alias to drop it.
+    Alias {
+        /// New name
+        alias: String,
+        /// Existing secret it refers to
+        target: String,
+    },
+    
+    /// Give a secret a new name, keeping its history and expiry
+    Rename {
+        /// Current name
+        old: String,
+        /// Name to move it to
+        new: String,
     },
     
     /// Retrieve a secret from the vault
     Get {
         /// Name of the secret to retrieve
         name: String,
+        
+        /// Print one field of a JSON value (dots select nested fields)
+        #[arg(long)]
+        field: Option<String>,
+        
+        /// Erase the printed value from the terminal after SECONDS (default 30, or clear_seconds from config.toml)
+        #[arg(long, value_name = "SECONDS", num_args = 0..=1)]
+        clear: Option<Option<u64>>,
+        
+        /// Show a desktop notification that the secret was read (never its value)
+        #[arg(long)]
+        notify: bool,
+        
+        /// Print exactly the stored value, without a trailing newline
+        #[arg(short = 'n', long, conflicts_with = "clear")]
+        no_newline: bool,
+        
+        /// If the secret isn't in the vault, read the environment variable of
+        /// the same name (uppercased, other characters as `_`)
+        #[arg(long)]
+        env_fallback: bool,
+        
+        /// Print the value encoded as base64 or hex
+        #[arg(long, value_name = "ENCODING")]
+        encode: Option<String>,
+        
+        /// Fail, instead of printing "not found", if the secret is missing
+        /// (exit 2) or has expired (exit 3)
+        #[arg(long, conflicts_with = "env_fallback")]
+        require_active: bool,
+        
+        /// On a terminal, hide the window title and turn off bracketed paste
+        /// while the value is shown
+        #[arg(long)]
+        paranoid: bool,
+    },
+    
+    /// Copy a secret to the clipboard, then clear it again. Keeps running
+    /// in the foreground until it has.
+    Clip {
+        /// Name of the secret to copy
+        name: String,
+        /// How long to leave it there, and to wait before returning (e.g., "30s", "2m")
+        #[arg(long, default_value = "30s")]
+        clear_after: String,
+        /// Clipboard to use (arboard, wl-clipboard, xclip), instead of the one
+        /// detected for this session
+        #[arg(long, value_name = "BACKEND")]
+        clipboard_backend: Option<String>,
+    },
+    
+    /// Exit 0 if a secret exists and hasn't expired, 1 otherwise. Prints
+    /// nothing and never decrypts the value.
+    Has {
+        /// Name of the secret to check
+        name: String,
     },
     
     /// List all secret names (not values)
-    List,
+    List {
+        /// Only list secrets in this namespace, e.g. `prod` or `prod/db`
+        namespace: Option<String>,
+        /// Group names by namespace as a tree
+        #[arg(long, conflicts_with = "values")]
+        tree: bool,
+        /// Also decrypt and print every value
+        #[arg(long)]
+        values: bool,
+        /// Skip the confirmation and allow printing values to a non-terminal
+        #[arg(long = "i-understand-plaintext", requires = "values")]
+        i_understand_plaintext: bool,
+        /// Show only expired secrets that haven't been purged yet, and how long ago they expired
+        #[arg(long, conflicts_with_all = ["tree", "values"])]
+        expired: bool,
+        /// Show only secrets not read (or created, if never read) within this long, e.g. 90d
+        #[arg(long, value_name = "DURATION", conflicts_with_all = ["tree", "values", "expired"])]
+        older_than: Option<String>,
+        /// Output style: bullets, or table for aligned columns
+        #[arg(long, default_value = "bullets", value_parser = ["bullets", "table"], conflicts_with_all = ["tree", "values", "expired", "older_than"])]
+        format: String,
+        /// Only list secrets with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    
+    /// Find secrets by name, without decrypting anything
+    Search {
+        /// Text the name contains (case-insensitive), or a pattern with --glob
+        pattern: String,
+        /// Match the whole name against `*` and `?` wildcards instead
+        #[arg(long)]
+        glob: bool,
+    },
+    
+    /// Show the most recently read secrets
+    Last {
+        /// How many secrets to show
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    
+    /// Push back when a secret expires, keeping its lease
+    #[command(visible_alias = "extend")]
+    Renew {
+        /// Name of the secret
+        name: String,
+        /// How much longer it should last (e.g., "30m", "7d")
+        #[arg(long)]
+        by: String,
+    },
+    
+    /// Wait until a secret expires or is removed
+    Watch {
+        /// Name of the secret
+        name: String,
+        /// Shell command to run once the secret has expired or been removed
+        #[arg(long, value_name = "COMMAND")]
+        on_expire: Option<String>,
+        /// Give up after this many minutes
+        #[arg(long, value_name = "MINUTES")]
+        timeout: Option<u64>,
+    },
     
     /// Set expiration time for a secret
     Expire {
-        /// Name of the secret
+        /// Name of the secret (a pattern with --glob)
         name: String,
+        /// Treat the name as a glob pattern and set the same expiry on every match
+        #[arg(long, requires = "after", conflicts_with = "reads")]
+        glob: bool,
         /// Expiration duration (e.g., "10m", "1h", "1d")
+        #[arg(long, required_unless_present_any = ["reads", "clear"])]
+        after: Option<String>,
+        /// Remove the secret after this many successful reads
         #[arg(long)]
-        after: String,
+        reads: Option<u64>,
+        /// Extend the expiry by the same duration each time the secret is read
+        #[arg(long, requires = "after")]
+        sliding: bool,
+        /// Remove any expiry or read limit, making the secret permanent again
+        #[arg(long, conflicts_with_all = ["after", "reads", "sliding"])]
+        clear: bool,
     },
     
-    /// Remove a secret from the vault
+    /// Remove one or more secrets from the vault
     Remove {
-        /// Name of the secret to remove
-        name: String,
+        /// Names of the secrets to remove (patterns with --glob)
+        #[arg(required_unless_present_any = ["expired", "all", "older_than"])]
+        names: Vec<String>,
+        /// Treat names as glob patterns (`*` and `?` wildcards)
+        #[arg(long)]
+        glob: bool,
+        /// Remove every secret whose lease has expired, and nothing else
+        #[arg(long, conflicts_with_all = ["names", "glob"])]
+        expired: bool,
+        /// Remove every secret and lease, after re-entering the master password
+        #[arg(long, conflicts_with_all = ["names", "glob", "expired", "force"])]
+        all: bool,
+        /// Remove secrets not read (or created, if never read) within this long, e.g. 90d. Needs --force.
+        #[arg(long, value_name = "DURATION", conflicts_with_all = ["names", "glob", "expired", "all"])]
+        older_than: Option<String>,
+        /// With --all, `--keep-identity=false` also deletes the vault and identity for a full reset
+        #[arg(long, requires = "all", default_value_t = true, action = clap::ArgAction::Set, value_name = "BOOL")]
+        keep_identity: bool,
+        /// Skip the confirmation prompt, and allow a glob that matches every secret
+        #[arg(long, visible_alias = "yes")]
+        force: bool,
     },
     
     /// Create a backup of the vault
     Backup {
-        /// Output format: ron, json, qr
+        /// Output format: ron, json, yaml, qr
         #[arg(short, long, default_value = "ron")]
         format: String,
+        /// Write the backup to a file (0600) instead of stdout; QR backups to a .png path are rendered as an image
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Write a timestamped backup into this directory instead (created 0700)
+        #[arg(long, value_name = "DIR", conflicts_with = "output")]
+        dir: Option<PathBuf>,
+        /// With --dir, delete the oldest backups so only this many remain
+        #[arg(long, value_name = "COUNT", requires = "dir")]
+        keep: Option<usize>,
+        /// Back up only these secrets (comma-separated; patterns with --glob)
+        #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+        only: Vec<String>,
+        /// Treat --only names as glob patterns
+        #[arg(long, requires = "only")]
+        glob: bool,
+        /// Seal the backup under a separate passphrase (conventionally saved as .svb)
+        #[arg(long, conflicts_with = "format")]
+        encrypt: bool,
+    },
+    
+    /// Import secrets from a file, e.g. a spreadsheet saved as CSV
+    Import {
+        /// File to read; CSV needs a `name,value` header
+        path: PathBuf,
+        /// Input format: csv
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+    },
+    
+    /// Apply add, expire and remove operations from a script, all or none of them
+    Batch {
+        /// Script with one operation per line; read from stdin if omitted
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    
+    /// Export every unexpired secret in plaintext
+    Export {
+        /// Output format: csv, or systemd for one credential file per secret (needs --dir)
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+        /// Write to a file (0600) instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Directory for --format systemd, e.g. one named in LoadCredential=
+        #[arg(long, conflicts_with = "output")]
+        dir: Option<PathBuf>,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Required: acknowledge that every value is written in plaintext
+        #[arg(long = "i-understand-plaintext")]
+        i_understand_plaintext: bool,
+        /// Export only these secrets (comma-separated; patterns with --glob)
+        #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+        only: Vec<String>,
+        /// Treat --only names as glob patterns
+        #[arg(long, requires = "only")]
+        glob: bool,
+    },
+    
+    /// Replace the vault's contents with a backup of it, or create the vault
+    /// from the backup if there isn't one yet
+    Restore {
+        /// Backup file written by `sentinel backup`
+        path: PathBuf,
+        /// Backup format (ron, json, yaml), if the extension doesn't say
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Replace the current secrets without asking
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Merge the secrets from a backup of another vault into this one
+    Merge {
+        /// Backup file written by `sentinel backup`
+        path: PathBuf,
+        /// Which copy wins when both vaults have a secret: newest, keep-mine, keep-theirs
+        #[arg(long, default_value = "newest")]
+        strategy: String,
     },
     
     /// Show vault statistics
-    Stats,
-}
\ No newline at end of file
+    Stats {
+        /// Include oldest/newest, most accessed and average lease length
+        #[arg(long)]
+        detailed: bool,
+        /// Keep re-reading the vault and redrawing the stats until interrupted
+        #[arg(long)]
+        watch: bool,
+        /// Time between refreshes with --watch, e.g. 5s or 1m
+        #[arg(long, default_value = "5s", requires = "watch")]
+        interval: String,
+    },
+    
+    /// Remove expired secrets and orphaned leases
+    Prune,
+    
+    /// Require the master password again before revealing a secret
+    Protect {
+        /// Name of the secret
+        name: String,
+    },
+    
+    /// Remove the extra password check from a protected secret
+    Unprotect {
+        /// Name of the secret
+        name: String,
+    },
+    
+    /// Make the vault read-only: add, remove and expire are refused
+    LockVault,
+    
+    /// Allow changes to a read-only vault again
+    UnlockVault,
+    
+    /// Show or set the pattern new secret names must match
+    NamePolicy {
+        /// Regex that whole names must match, e.g. '[a-z0-9_.-]+'
+        pattern: Option<String>,
+        /// Remove the policy
+        #[arg(long, conflicts_with = "pattern")]
+        clear: bool,
+    },
+    
+    /// Show or set the most secrets the vault may hold
+    MaxSecrets {
+        /// New limit
+        limit: Option<usize>,
+        /// Remove the limit
+        #[arg(long, conflicts_with = "limit")]
+        clear: bool,
+    },
+    
+    /// Report secrets with reused or weak values (names only)
+    AuditValues,
+    
+    /// Print a digest of secret names and update times, to compare vaults across machines
+    Fingerprint,
+    
+    /// Change the master password
+    ChangePassword,
+    
+    /// Back up or restore the identity file on its own
+    Identity {
+        #[command(subcommand)]
+        action: IdentityCommand,
+    },
+    
+    /// Unlock once and run commands interactively until `exit`
+    Shell {
+        /// Minutes of inactivity before the key is wiped and the password is asked again (default 15, or shell_timeout_minutes from config.toml)
+        #[arg(long, value_name = "MINUTES")]
+        timeout: Option<u64>,
+    },
+    
+    /// Disable TOTP two-factor authentication
+    #[command(name = "disable-2fa")]
+    Disable2fa,
+    
+    /// Check the vault setup for common problems (no password needed)
+    Doctor,
+    
+    /// Report whether the vault exists and is readable, for monitoring (no password needed)
+    Status,
+    
+    /// Look for stored secret values written in plaintext under a directory
+    Scan {
+        /// File or directory to search
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    
+    /// Print the machine id that machine-bound vaults are tied to
+    MachineId,
+    
+    /// Time key derivation and encryption on this machine
+    #[command(hide = true)]
+    Benchmark {
+        /// Unlock time to recommend Argon2 parameters for
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        target_ms: u64,
+    },
+    
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+#[derive(Subcommand)]
+pub enum IdentityCommand {
+    /// Copy the identity file (what unlocks the vault, not the secrets) to a file
+    Export {
+        /// File to write (0600)
+        #[arg(long)]
+        out: PathBuf,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Install an exported identity after checking it unlocks this vault
+    Import {
+        /// File written by `identity export`
+        #[arg(long = "in", value_name = "PATH")]
+        input: PathBuf,
+        /// Replace the current identity
+        #[arg(long)]
+        force: bool,
+    },
+}
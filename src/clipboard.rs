@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Whether this build has the built-in clipboard (the `clipboard` feature)
+pub const BUILTIN: bool = cfg!(feature = "clipboard");
+
+/// How `clip` reaches the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The built-in clipboard, for X11, Wayland, macOS and Windows
+    Arboard,
+    /// `wl-copy` and `wl-paste` from wl-clipboard, for Wayland
+    WlClipboard,
+    /// `xclip`, for X11
+    Xclip,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "arboard" => Ok(Backend::Arboard),
+            "wl-clipboard" => Ok(Backend::WlClipboard),
+            "xclip" => Ok(Backend::Xclip),
+            _ => Err(anyhow!("Unknown clipboard backend: {}. Use arboard, wl-clipboard or xclip", s)),
+        }
+    }
+}
+
+impl Backend {
+    /// Pick the backend for the current session: wl-clipboard under Wayland,
+    /// the built-in clipboard under X11 and elsewhere, with xclip standing in
+    /// for it in builds without the `clipboard` feature
+    pub fn detect() -> Result<Self> {
+        if cfg!(any(windows, target_os = "macos")) {
+            return if BUILTIN {
+                Ok(Backend::Arboard)
+            } else {
+                Err(anyhow!("Copying to the clipboard needs a build with the `clipboard` feature"))
+            };
+        }
+        
+        let is_set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+        choose(is_set, on_path)
+    }
+}
+
+/// The backend for a desktop session with these variables set and these
+/// programs installed
+fn choose(is_set: impl Fn(&str) -> bool, installed: impl Fn(&str) -> bool) -> Result<Backend> {
+    if is_set("WAYLAND_DISPLAY") {
+        if installed("wl-copy") {
+            return Ok(Backend::WlClipboard);
+        }
+        if BUILTIN {
+            return Ok(Backend::Arboard);
+        }
+        return Err(anyhow!("No clipboard available: install wl-clipboard, or use a build with the `clipboard` feature"));
+    }
+    if is_set("DISPLAY") {
+        if BUILTIN {
+            return Ok(Backend::Arboard);
+        }
+        if installed("xclip") {
+            return Ok(Backend::Xclip);
+        }
+        return Err(anyhow!("No clipboard available: install xclip, or use a build with the `clipboard` feature"));
+    }
+    
+    Err(anyhow!("No clipboard available: no graphical session (neither WAYLAND_DISPLAY nor DISPLAY is set)"))
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Whether the clipboard still holds what `clip` put there. Anything else
+/// was copied since, and isn't ours to clear.
+pub fn still_ours(current: Option<&str>, copied: &str) -> bool {
+    current == Some(copied)
+}
+
+/// Put `value` on the clipboard, wait `clear_after`, then clear it unless
+/// something else was copied meanwhile. Returns whether it was cleared.
+/// Blocks for the whole wait: the built-in clipboard stays open until then,
+/// since on X11 its contents go away with the process that set them.
+pub fn copy_then_clear(backend: Backend, value: &str, clear_after: Duration) -> Result<bool> {
+    let tool = match backend {
+        Backend::Arboard => return builtin_copy_then_clear(value, clear_after),
+        Backend::WlClipboard => &WL_CLIPBOARD,
+        Backend::Xclip => &XCLIP,
+    };
+    
+    tool.run(tool.copy, value).context("Failed to copy to the clipboard")?;
+    std::thread::sleep(clear_after);
+    let current = tool.paste().ok();
+    if !still_ours(current.as_ref().map(|text| text.as_str()), value) {
+        return Ok(false);
+    }
+    tool.run(tool.clear, "").context("Failed to clear the clipboard")?;
+    Ok(true)
+}
+
+/// A clipboard reached through command-line programs
+struct Tool {
+    /// What to install when the programs are missing
+    package: &'static str,
+    /// Copies its stdin
+    copy: &'static [&'static str],
+    /// Prints the clipboard
+    paste: &'static [&'static str],
+    /// Empties the clipboard, given empty stdin
+    clear: &'static [&'static str],
+}
+
+const WL_CLIPBOARD: Tool = Tool {
+    package: "wl-clipboard",
+    copy: &["wl-copy"],
+    paste: &["wl-paste", "--no-newline"],
+    clear: &["wl-copy", "--clear"],
+};
+
+const XCLIP: Tool = Tool {
+    package: "xclip",
+    copy: &["xclip", "-selection", "clipboard"],
+    paste: &["xclip", "-selection", "clipboard", "-o"],
+    clear: &["xclip", "-selection", "clipboard"],
+};
+
+impl Tool {
+    fn spawn(&self, args: &[&str], stdin: Stdio, stdout: Stdio) -> Result<std::process::Child> {
+        Command::new(args[0])
+            .args(&args[1..])
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound => anyhow!("No clipboard available: {} is not installed (install {})", args[0], self.package),
+                _ => anyhow!("Failed to run {}: {}", args[0], e),
+            })
+    }
+    
+    /// Run `args` with `input` on stdin, so the value never shows up in the
+    /// process list
+    fn run(&self, args: &[&str], input: &str) -> Result<()> {
+        let mut child = self.spawn(args, Stdio::piped(), Stdio::null())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())?;
+        }
+        
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("{} failed with {}", args[0], status));
+        }
+        Ok(())
+    }
+    
+    fn paste(&self) -> Result<Zeroizing<String>> {
+        let mut child = self.spawn(self.paste, Stdio::null(), Stdio::piped())?;
+        let mut text = Zeroizing::new(String::new());
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout.read_to_string(&mut text)?;
+        }
+        
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("{} failed with {}", self.paste[0], status));
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn builtin_copy_then_clear(value: &str, clear_after: Duration) -> Result<bool> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| anyhow!("No clipboard available: {}", e))?;
+    clipboard
+        .set_text(value)
+        .map_err(|e| anyhow!("Failed to copy to the clipboard: {}", e))?;
+    
+    std::thread::sleep(clear_after);
+    let current = clipboard.get_text().ok().map(Zeroizing::new);
+    if !still_ours(current.as_ref().map(|text| text.as_str()), value) {
+        return Ok(false);
+    }
+    clipboard.clear().map_err(|e| anyhow!("Failed to clear the clipboard: {}", e))?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn builtin_copy_then_clear(_value: &str, _clear_after: Duration) -> Result<bool> {
+    Err(anyhow!("The arboard clipboard backend needs a build with the `clipboard` feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_still_ours() {
+        assert!(still_ours(Some("sk-123"), "sk-123"));
+        assert!(!still_ours(Some("copied since"), "sk-123"));
+        assert!(!still_ours(Some(""), "sk-123"));
+        assert!(!still_ours(None, "sk-123"));
+    }
+    
+    #[test]
+    fn test_choose_backend() {
+        let wayland = |name: &str| name == "WAYLAND_DISPLAY";
+        let x11 = |name: &str| name == "DISPLAY";
+        let everything = |_: &str| true;
+        let nothing = |_: &str| false;
+        let builtin_or = |backend| if BUILTIN { Backend::Arboard } else { backend };
+        
+        assert_eq!(choose(wayland, everything).unwrap(), Backend::WlClipboard);
+        assert_eq!(choose(everything, everything).unwrap(), Backend::WlClipboard);
+        assert_eq!(choose(x11, everything).unwrap(), builtin_or(Backend::Xclip));
+        assert_eq!(choose(wayland, nothing).is_ok(), BUILTIN);
+        assert_eq!(choose(x11, nothing).is_ok(), BUILTIN);
+        
+        let err = choose(nothing, everything).unwrap_err();
+        assert!(err.to_string().contains("no graphical session"));
+        
+        assert_eq!("xclip".parse::<Backend>().unwrap(), Backend::Xclip);
+        assert_eq!("wl-clipboard".parse::<Backend>().unwrap(), Backend::WlClipboard);
+        assert!("pbcopy".parse::<Backend>().is_err());
+    }
+    
+    #[cfg(not(feature = "clipboard"))]
+    #[test]
+    fn test_copy_needs_feature() {
+        assert!(copy_then_clear(Backend::Arboard, "sk-123", Duration::ZERO).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::utils::expand_home;
+
+/// Overrides the vault directory; `--vault-dir` sets it for the process
+pub const VAULT_DIR_ENV: &str = "SENTINELVAULT_DIR";
+
+/// Overrides `clear_seconds` from the config file
+pub const CLEAR_SECONDS_ENV: &str = "SENTINELVAULT_CLEAR_SECONDS";
+
+/// Overrides `shell_timeout_minutes` from the config file
+pub const SHELL_TIMEOUT_ENV: &str = "SENTINELVAULT_SHELL_TIMEOUT";
+
+const DEFAULT_CLEAR_SECONDS: u64 = 30;
+const DEFAULT_SHELL_TIMEOUT_MINUTES: u64 = 15;
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Defaults read from `~/.sentinelvault/config.toml`. Explicit flags take
+/// precedence, then environment variables, then the file, then the built-in
+/// defaults.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where the vault and identity live. Relative paths, and `~`, are taken
+    /// from the home directory.
+    pub vault_dir: Option<PathBuf>,
+    /// Delay for `get --clear` when no value is given
+    pub clear_seconds: Option<u64>,
+    /// Idle minutes before `shell` locks again
+    pub shell_timeout_minutes: Option<u64>,
+    /// strftime format for timestamps in `list` and `stats`
+    pub time_format: Option<String>,
+    /// Show timestamps in UTC instead of the local timezone; `--utc` sets it
+    /// for one command
+    pub utc: Option<bool>,
+}
+
+/// The config file's location. It always lives in the default vault
+/// directory, since it may itself move the vault elsewhere.
+pub fn config_path() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir()
+        .ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    
+    Ok(home_dir.join(".sentinelvault").join("config.toml"))
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents).map_err(|e| anyhow!("Invalid config: {}", e))?;
+        if let Some(format) = &config.time_format {
+            // chrono panics when displaying a bad format, so catch it up front
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(anyhow!("Invalid config: time_format '{}' is not a valid strftime format", format));
+            }
+        }
+        Ok(config)
+    }
+    
+    /// Read the config file, if there is one, without applying environment
+    /// overrides
+    pub fn load_file() -> Result<Self> {
+        let path = config_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        
+        log::debug!("Reading config from {}", path.display());
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+    
+    /// The config file with environment variables applied over it
+    pub fn load() -> Result<Self> {
+        Self::load_file()?.with_env()
+    }
+    
+    fn with_env(mut self) -> Result<Self> {
+        if let Some(dir) = env_value(VAULT_DIR_ENV) {
+            self.vault_dir = Some(absolute_dir(PathBuf::from(dir))?);
+        }
+        if let Some(seconds) = env_number(CLEAR_SECONDS_ENV)? {
+            self.clear_seconds = Some(seconds);
+        }
+        if let Some(minutes) = env_number(SHELL_TIMEOUT_ENV)? {
+            self.shell_timeout_minutes = Some(minutes);
+        }
+        Ok(self)
+    }
+    
+    pub fn clear_seconds(&self) -> u64 {
+        self.clear_seconds.unwrap_or(DEFAULT_CLEAR_SECONDS)
+    }
+    
+    pub fn shell_timeout_minutes(&self) -> u64 {
+        self.shell_timeout_minutes.unwrap_or(DEFAULT_SHELL_TIMEOUT_MINUTES)
+    }
+    
+    /// Render a stored (UTC) timestamp for display, in the local timezone
+    /// unless `utc` is set
+    pub fn format_time(&self, at: DateTime<Utc>) -> String {
+        let format = self.time_format.as_deref().unwrap_or(DEFAULT_TIME_FORMAT);
+        if self.utc.unwrap_or(false) {
+            at.format(format).to_string()
+        } else {
+            at.with_timezone(&Local).format(format).to_string()
+        }
+    }
+}
+
+/// Resolve a directory from the environment or `--vault-dir` the way a shell
+/// user expects: `~` is the home directory, and relative paths start from
+/// the current directory rather than the home directory
+fn absolute_dir(dir: PathBuf) -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    let dir = expand_home(&dir, &home);
+    if dir.is_absolute() {
+        return Ok(dir);
+    }
+    Ok(std::env::current_dir()?.join(dir))
+}
+
+fn env_value(name: &str) -> Option<OsString> {
+    std::env::var_os(name).filter(|value| !value.is_empty())
+}
+
+fn env_number(name: &str) -> Result<Option<u64>> {
+    env_value(name)
+        .map(|value| {
+            value
+                .to_str()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| anyhow!("{} must be a whole number", name))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("vault_dir = \"/srv/vault\"\nclear_seconds = 10\n").unwrap();
+        assert_eq!(config.vault_dir, Some(PathBuf::from("/srv/vault")));
+        assert_eq!(config.clear_seconds(), 10);
+        assert_eq!(config.shell_timeout_minutes(), DEFAULT_SHELL_TIMEOUT_MINUTES);
+        
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("clear_seconds = \"soon\"").is_err());
+        // Typos are reported rather than silently ignored
+        assert!(Config::parse("clear_secs = 10").is_err());
+    }
+    
+    #[test]
+    fn test_format_time() {
+        let at = DateTime::parse_from_rfc3339("2024-03-09T14:05:00Z").unwrap().with_timezone(&Utc);
+        
+        let mut config = Config { utc: Some(true), ..Config::default() };
+        assert_eq!(config.format_time(at), "2024-03-09 14:05:00");
+        config.time_format = Some("%d/%m %H:%M %Z".to_string());
+        assert_eq!(config.format_time(at), "09/03 14:05 UTC");
+        
+        config.utc = None;
+        let local = at.with_timezone(&Local).format("%d/%m %H:%M").to_string();
+        assert!(config.format_time(at).starts_with(&local));
+        
+        assert!(Config::parse("time_format = \"%Y-%m-%d\"").is_ok());
+        assert!(Config::parse("time_format = \"%Q\"").is_err());
+    }
+}
@@ -3,19 +3,75 @@ use aes_gcm::{
   Aes256Gcm, Key, Nonce,
 };
 use anyhow::{anyhow, Result};
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version, password_hash::SaltString};
 use base64::{Engine as _, engine::general_purpose};
+use hkdf::Hkdf;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use sha2::Sha256;
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::error::VaultError;
+use crate::migrations::predates_layout;
+
+/// Layout of `EncryptedData` written by this version
+pub const ENCRYPTION_VERSION: u8 = 1;
+/// `EncryptedData::alg` for AES-256-GCM with a 96-bit nonce
+pub const ALG_AES_256_GCM: u8 = 1;
+
+const AES_GCM_NONCE_LEN: usize = 12;
+
+fn default_encryption_version() -> u8 {
+  ENCRYPTION_VERSION
+}
+
+fn default_alg() -> u8 {
+  ALG_AES_256_GCM
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct EncryptedData {
   pub ciphertext: Vec<u8>,
   pub nonce: Vec<u8>,
+  /// Which layout the fields follow, so it can change without guessing
+  pub version: u8,
+  /// Cipher the ciphertext was produced with
+  pub alg: u8,
 }
 
-#[derive(Debug, Zeroize, ZeroizeOnDrop)]
+impl<'de> Deserialize<'de> for EncryptedData {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+      #[derive(Deserialize)]
+      #[serde(rename = "EncryptedData")]
+      struct Tagged {
+          ciphertext: Vec<u8>,
+          nonce: Vec<u8>,
+          #[serde(default = "default_encryption_version")]
+          version: u8,
+          #[serde(default = "default_alg")]
+          alg: u8,
+      }
+      
+      #[derive(Deserialize)]
+      #[serde(rename = "EncryptedData")]
+      struct Untagged {
+          ciphertext: Vec<u8>,
+          nonce: Vec<u8>,
+      }
+      
+      // Binary vaults from before layout 2 have no tags to read
+      if predates_layout(&deserializer, 2) {
+          let Untagged { ciphertext, nonce } = Untagged::deserialize(deserializer)?;
+          return Ok(EncryptedData { ciphertext, nonce, version: ENCRYPTION_VERSION, alg: ALG_AES_256_GCM });
+      }
+      
+      let Tagged { ciphertext, nonce, version, alg } = Tagged::deserialize(deserializer)?;
+      Ok(EncryptedData { ciphertext, nonce, version, alg })
+  }
+}
+
+#[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
 pub struct SecretKey([u8; 32]);
 
 impl SecretKey {
@@ -28,6 +84,33 @@ impl SecretKey {
   }
 }
 
+/// Decrypted secret value that is wiped from memory when dropped.
+/// `Debug` is redacted so it cannot leak through logging.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct SecretString(String);
+
+impl SecretString {
+  pub fn new(value: String) -> Self {
+      Self(value)
+  }
+  
+  pub fn expose(&self) -> &str {
+      &self.0
+  }
+}
+
+impl From<String> for SecretString {
+  fn from(value: String) -> Self {
+      Self(value)
+  }
+}
+
+impl fmt::Debug for SecretString {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("SecretString(***)")
+  }
+}
+
 pub struct CryptoEngine {
   cipher: Aes256Gcm,
 }
@@ -41,30 +124,259 @@ impl CryptoEngine {
   }
   
   pub fn encrypt(&self, plaintext: &str) -> Result<EncryptedData> {
+      self.encrypt_bytes(plaintext.as_bytes())
+  }
+  
+  pub fn decrypt(&self, encrypted: &EncryptedData) -> Result<String> {
+      let plaintext = self.decrypt_bytes(encrypted)?;
+      
+      String::from_utf8(plaintext)
+          .map_err(|e| VaultError::Corrupted(format!("invalid UTF-8 in decrypted data: {}", e)).into())
+  }
+  
+  /// Decrypt into a container that zeroizes the plaintext on drop
+  pub fn decrypt_secret(&self, encrypted: &EncryptedData) -> Result<SecretString> {
+      self.decrypt(encrypted).map(SecretString::new)
+  }
+  
+  pub fn encrypt_bytes(&self, plaintext: &[u8]) -> Result<EncryptedData> {
       let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
       let ciphertext = self.cipher
-          .encrypt(&nonce, plaintext.as_bytes())
+          .encrypt(&nonce, plaintext)
           .map_err(|e| anyhow!("Encryption failed: {}", e))?;
       
       Ok(EncryptedData {
           ciphertext,
           nonce: nonce.to_vec(),
+          version: ENCRYPTION_VERSION,
+          alg: ALG_AES_256_GCM,
       })
   }
   
-  pub fn decrypt(&self, encrypted: &EncryptedData) -> Result<String> {
+  pub fn decrypt_bytes(&self, encrypted: &EncryptedData) -> Result<Vec<u8>> {
+      match (encrypted.version, encrypted.alg) {
+          (ENCRYPTION_VERSION, ALG_AES_256_GCM) => {}
+          (version, alg) => {
+              return Err(VaultError::Corrupted(format!(
+                  "unsupported encryption version {} / algorithm {}", version, alg
+              )).into());
+          }
+      }
+      if encrypted.nonce.len() != AES_GCM_NONCE_LEN {
+          return Err(VaultError::Corrupted(format!(
+              "nonce is {} bytes, expected {}", encrypted.nonce.len(), AES_GCM_NONCE_LEN
+          )).into());
+      }
+      
       let nonce = Nonce::from_slice(&encrypted.nonce);
-      let plaintext = self.cipher
+      self.cipher
           .decrypt(nonce, encrypted.ciphertext.as_ref())
-          .map_err(|e| anyhow!("Decryption failed: {}", e))?;
+          .map_err(|e| VaultError::Corrupted(format!("decryption failed: {}", e)).into())
+  }
+}
+
+/// Generate a random data-encryption key
+pub fn generate_key() -> SecretKey {
+  let mut key = [0u8; 32];
+  OsRng.fill_bytes(&mut key);
+  SecretKey::new(key)
+}
+
+/// Encrypt a data key under a key-encryption key
+pub fn wrap_key(kek: &SecretKey, key: &SecretKey) -> Result<EncryptedData> {
+  CryptoEngine::new(kek).encrypt_bytes(key.as_bytes())
+}
+
+/// Recover a data key previously wrapped with `wrap_key`
+pub fn unwrap_key(kek: &SecretKey, wrapped: &EncryptedData) -> Result<SecretKey> {
+  let bytes = Zeroizing::new(
+      CryptoEngine::new(kek)
+          .decrypt_bytes(wrapped)
+          .map_err(|_| anyhow!("Failed to unwrap vault data key"))?,
+  );
+  
+  let key: [u8; 32] = bytes
+      .as_slice()
+      .try_into()
+      .map_err(|_| VaultError::Corrupted("wrapped vault data key has invalid length".to_string()))?;
+  
+  Ok(SecretKey::new(key))
+}
+
+/// Argon2id cost of a password hash, reused when deriving the vault key from
+/// the same password
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+  /// Memory in KiB
+  pub m_cost: u32,
+  pub t_cost: u32,
+  pub p_cost: u32,
+}
+
+impl KdfParams {
+  /// Given to new and changed passwords, and upgraded to on login: the
+  /// RFC 9106 64 MiB recommendation, on a single lane
+  pub const RECOMMENDED: Self = Self { m_cost: 65536, t_cost: 3, p_cost: 1 };
+  
+  /// The parameters recorded in a PHC hash string. Hashes written before
+  /// parameters were configurable carry argon2's defaults, which is also what
+  /// their keys were derived with.
+  pub fn from_hash(hash_str: &str) -> Result<Self> {
+      let parsed_hash = PasswordHash::new(hash_str)
+          .map_err(|e| anyhow!("Failed to parse password hash: {}", e))?;
+      let params = Params::try_from(&parsed_hash)
+          .map_err(|e| anyhow!("Unsupported password hash parameters: {}", e))?;
       
-      String::from_utf8(plaintext)
-          .map_err(|e| anyhow!("Invalid UTF-8 in decrypted data: {}", e))
+      Ok(Self { m_cost: params.m_cost(), t_cost: params.t_cost(), p_cost: params.p_cost() })
+  }
+  
+  /// Whether any cost is lower than in `other`
+  pub fn is_weaker_than(&self, other: &Self) -> bool {
+      self.m_cost < other.m_cost || self.t_cost < other.t_cost || self.p_cost < other.p_cost
+  }
+  
+  fn argon2(&self) -> Result<Argon2<'static>> {
+      let params = Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+          .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?;
+      Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+  }
+}
+
+impl std::str::FromStr for KdfParams {
+  type Err = anyhow::Error;
+  
+  /// `m=131072,t=4,p=1`, with any cost left out taken from `RECOMMENDED`
+  fn from_str(s: &str) -> Result<Self> {
+      let mut params = Self::RECOMMENDED;
+      for part in s.split(',').map(str::trim) {
+          let (key, value) = part.split_once('=')
+              .ok_or_else(|| anyhow!("Invalid Argon2 cost '{}'. Use m=<KiB>,t=<passes>,p=<lanes>", part))?;
+          let value: u32 = value.parse().map_err(|_| anyhow!("Invalid Argon2 cost '{}'", part))?;
+          match key {
+              "m" => params.m_cost = value,
+              "t" => params.t_cost = value,
+              "p" => params.p_cost = value,
+              _ => return Err(anyhow!("Unknown Argon2 cost '{}'. Use m, t or p", key)),
+          }
+      }
+      
+      params.argon2()?;
+      Ok(params)
+  }
+}
+
+/// A password hashing scheme: it checks the master password and derives the
+/// vault key from it. Identities record which one they use in [`KdfAlgorithm`].
+pub trait Kdf: fmt::Display {
+  /// Hash `password` for verification, under its own random salt
+  fn hash(&self, password: &str) -> Result<String>;
+  
+  /// Check `password` against a string returned by [`Kdf::hash`]
+  fn verify(&self, password: &str, hash: &str) -> Result<bool>;
+  
+  /// Derive a 32-byte key from `password` and a [`SALT_LEN`]-byte salt
+  fn derive_key(&self, password: &str, salt: &[u8]) -> Result<SecretKey>;
+}
+
+/// Argon2id at fixed costs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Kdf {
+  pub params: KdfParams,
+}
+
+impl Default for Argon2Kdf {
+  fn default() -> Self {
+      Self { params: KdfParams::RECOMMENDED }
+  }
+}
+
+impl fmt::Display for Argon2Kdf {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "Argon2id (m={} KiB, t={}, p={})", self.params.m_cost, self.params.t_cost, self.params.p_cost)
+  }
+}
+
+impl Kdf for Argon2Kdf {
+  fn hash(&self, password: &str) -> Result<String> {
+      let salt = SaltString::generate(&mut OsRng);
+      let password_hash = self.params.argon2()?
+          .hash_password(password.as_bytes(), &salt)
+          .map_err(|e| anyhow!("Failed to hash password: {}", e))?;
+      
+      Ok(password_hash.to_string())
+  }
+  
+  fn verify(&self, password: &str, hash: &str) -> Result<bool> {
+      let parsed_hash = PasswordHash::new(hash)
+          .map_err(|e| anyhow!("Failed to parse password hash: {}", e))?;
+      
+      // The costs to verify at are read from the hash itself
+      Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok())
+  }
+  
+  fn derive_key(&self, password: &str, salt: &[u8]) -> Result<SecretKey> {
+      derive_key(&self.params.argon2()?, password, salt)
+  }
+}
+
+/// Which [`Kdf`] an identity's password hash and vault key come from.
+/// Identities written before this was recorded are Argon2id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KdfAlgorithm {
+  #[default]
+  Argon2id,
+}
+
+impl KdfAlgorithm {
+  /// The scheme at the costs recorded in `hash`, for unlocking
+  pub fn for_hash(self, hash: &str) -> Result<Box<dyn Kdf>> {
+      match self {
+          KdfAlgorithm::Argon2id => Ok(Box::new(Argon2Kdf { params: KdfParams::from_hash(hash)? })),
+      }
+  }
+  
+  /// The scheme at its recommended costs, for new and changed passwords
+  pub fn recommended(self) -> Box<dyn Kdf> {
+      match self {
+          KdfAlgorithm::Argon2id => Box::new(Argon2Kdf::default()),
+      }
+  }
+  
+  /// The scheme for re-hashing a password currently hashed as `hash`: at
+  /// the same costs if they were raised above the recommended ones
+  pub fn for_rehash(self, hash: &str) -> Box<dyn Kdf> {
+      match self {
+          KdfAlgorithm::Argon2id => match KdfParams::from_hash(hash) {
+              Ok(params) if !params.is_weaker_than(&KdfParams::RECOMMENDED) => Box::new(Argon2Kdf { params }),
+              _ => self.recommended(),
+          },
+      }
+  }
+}
+
+/// Length of every key-derivation salt: the identity's, the PIN's and an
+/// encrypted backup's. Anything else is rejected rather than silently used.
+pub const SALT_LEN: usize = 32;
+
+/// Fail unless `salt` is exactly [`SALT_LEN`] bytes
+pub fn check_salt(salt: &[u8]) -> Result<()> {
+  if salt.len() != SALT_LEN {
+      return Err(VaultError::Corrupted(format!("key derivation salt is {} bytes, expected {}", salt.len(), SALT_LEN)).into());
   }
+  Ok(())
 }
 
 pub fn derive_key_from_password(password: &str, salt: &[u8]) -> Result<SecretKey> {
-  let argon2 = Argon2::default();
+  derive_key(&Argon2::default(), password, salt)
+}
+
+/// Derive a key at an explicit cost, e.g. the one of the identity's password hash
+pub fn derive_key_with_params(password: &str, salt: &[u8], params: &KdfParams) -> Result<SecretKey> {
+  Argon2Kdf { params: *params }.derive_key(password, salt)
+}
+
+fn derive_key(argon2: &Argon2, password: &str, salt: &[u8]) -> Result<SecretKey> {
+  check_salt(salt)?;
   let salt = SaltString::encode_b64(salt)
       .map_err(|e| anyhow!("Failed to encode salt: {}", e))?;
   
@@ -85,27 +397,77 @@ pub fn derive_key_from_password(password: &str, salt: &[u8]) -> Result<SecretKey
   Ok(SecretKey::new(key))
 }
 
-pub fn verify_password(password: &str, hash_str: &str) -> Result<bool> {
-  let parsed_hash = PasswordHash::new(hash_str)
-      .map_err(|e| anyhow!("Failed to parse password hash: {}", e))?;
+/// Derive the vault key from a keyfile, mixed with the password-derived key when present
+pub fn derive_key_from_keyfile(password_key: Option<&SecretKey>, keyfile: &[u8], salt: &[u8]) -> Result<SecretKey> {
+  let mut ikm = Zeroizing::new(Vec::with_capacity(32 + keyfile.len()));
+  if let Some(password_key) = password_key {
+      ikm.extend_from_slice(password_key.as_bytes());
+  }
+  ikm.extend_from_slice(keyfile);
   
-  let argon2 = Argon2::default();
-  Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
+  let mut key = [0u8; 32];
+  Hkdf::<Sha256>::new(Some(salt), &ikm)
+      .expand(b"sentinelvault keyfile key", &mut key)
+      .map_err(|e| anyhow!("Failed to derive key from keyfile: {}", e))?;
+  
+  Ok(SecretKey::new(key))
 }
 
-pub fn hash_password(password: &str) -> Result<String> {
-  let salt = SaltString::generate(&mut OsRng);
-  let argon2 = Argon2::default();
+/// Mix a machine identifier into an unlock key, so the same credentials
+/// derive a different key on any other host
+pub fn bind_key_to_machine(key: &SecretKey, machine_id: &str, salt: &[u8]) -> Result<SecretKey> {
+  let mut ikm = Zeroizing::new(Vec::with_capacity(32 + machine_id.len()));
+  ikm.extend_from_slice(key.as_bytes());
+  ikm.extend_from_slice(machine_id.as_bytes());
   
-  let password_hash = argon2
-      .hash_password(password.as_bytes(), &salt)
-      .map_err(|e| anyhow!("Failed to hash password: {}", e))?;
+  let mut bound = [0u8; 32];
+  Hkdf::<Sha256>::new(Some(salt), &ikm)
+      .expand(b"sentinelvault machine binding", &mut bound)
+      .map_err(|e| anyhow!("Failed to bind key to machine: {}", e))?;
+  
+  Ok(SecretKey::new(bound))
+}
+
+/// Mix a security key's hmac-secret output into an unlock key, so the vault
+/// can't be opened without the hardware
+pub fn bind_key_to_hardware(key: &SecretKey, hardware_secret: &[u8], salt: &[u8]) -> Result<SecretKey> {
+  let mut ikm = Zeroizing::new(Vec::with_capacity(32 + hardware_secret.len()));
+  ikm.extend_from_slice(key.as_bytes());
+  ikm.extend_from_slice(hardware_secret);
+  
+  let mut bound = [0u8; 32];
+  Hkdf::<Sha256>::new(Some(salt), &ikm)
+      .expand(b"sentinelvault fido2 binding", &mut bound)
+      .map_err(|e| anyhow!("Failed to bind key to security key: {}", e))?;
+  
+  Ok(SecretKey::new(bound))
+}
+
+/// Fingerprint used to recognise the right keyfile without storing anything key-equivalent
+pub fn keyfile_fingerprint(keyfile: &[u8], salt: &[u8]) -> Result<String> {
+  let mut check = [0u8; 32];
+  Hkdf::<Sha256>::new(Some(salt), keyfile)
+      .expand(b"sentinelvault keyfile check", &mut check)
+      .map_err(|e| anyhow!("Failed to fingerprint keyfile: {}", e))?;
   
-  Ok(password_hash.to_string())
+  Ok(encode_base64(&check))
+}
+
+pub fn verify_password(password: &str, hash_str: &str) -> Result<bool> {
+  Argon2Kdf::default().verify(password, hash_str)
+}
+
+/// Hash a master password at [`KdfParams::RECOMMENDED`], for verification.
+///
+/// The hash gets its own random salt, embedded in the returned PHC string, and
+/// never the identity's key-derivation salt: hashing with the same salt and
+/// cost would make the stored hash equal to the vault key.
+pub fn hash_password(password: &str) -> Result<String> {
+  Argon2Kdf::default().hash(password)
 }
 
-pub fn generate_salt() -> [u8; 32] {
-  let mut salt = [0u8; 32];
+pub fn generate_salt() -> [u8; SALT_LEN] {
+  let mut salt = [0u8; SALT_LEN];
   OsRng.fill_bytes(&mut salt);
   salt
 }
@@ -120,6 +482,56 @@ pub fn decode_base64(data: &str) -> Result<Vec<u8>> {
       .map_err(|e| anyhow!("Base64 decode error: {}", e))
 }
 
+pub fn encode_hex(data: &[u8]) -> String {
+  data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn decode_hex(data: &str) -> Result<Vec<u8>> {
+  if !data.len().is_multiple_of(2) || !data.is_ascii() {
+      return Err(anyhow!("Hex decode error: expected an even number of hex digits"));
+  }
+  (0..data.len())
+      .step_by(2)
+      .map(|i| u8::from_str_radix(&data[i..i + 2], 16).map_err(|e| anyhow!("Hex decode error: {}", e)))
+      .collect()
+}
+
+/// Text encoding applied to a value on its way in or out, for `get --encode`
+/// and `add --decode`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueEncoding {
+  Base64,
+  Hex,
+}
+
+impl ValueEncoding {
+  pub fn encode(self, data: &[u8]) -> String {
+      match self {
+          ValueEncoding::Base64 => encode_base64(data),
+          ValueEncoding::Hex => encode_hex(data),
+      }
+  }
+  
+  pub fn decode(self, data: &str) -> Result<Vec<u8>> {
+      match self {
+          ValueEncoding::Base64 => decode_base64(data),
+          ValueEncoding::Hex => decode_hex(data),
+      }
+  }
+}
+
+impl std::str::FromStr for ValueEncoding {
+  type Err = anyhow::Error;
+  
+  fn from_str(s: &str) -> Result<Self> {
+      match s {
+          "base64" => Ok(ValueEncoding::Base64),
+          "hex" => Ok(ValueEncoding::Hex),
+          _ => Err(anyhow!("Unsupported encoding: {}. Use base64 or hex", s)),
+      }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -136,6 +548,79 @@ mod tests {
       assert_eq!(plaintext, decrypted);
   }
   
+  #[test]
+  fn test_encrypted_data_tags() {
+      let engine = CryptoEngine::new(&SecretKey::new([42u8; 32]));
+      let encrypted = engine.encrypt_bytes(b"tagged").unwrap();
+      assert_eq!((encrypted.version, encrypted.alg), (ENCRYPTION_VERSION, ALG_AES_256_GCM));
+      
+      // Data written before the tags existed is AES-GCM v1
+      let tagged = ron::to_string(&encrypted).unwrap();
+      let untagged = tagged.replace(",version:1,alg:1", "");
+      assert_ne!(tagged, untagged);
+      let legacy: EncryptedData = ron::from_str(&untagged).unwrap();
+      assert_eq!(engine.decrypt_bytes(&legacy).unwrap(), b"tagged");
+      
+      // Binary data can't default fields, so the untagged shape is asked for
+      #[derive(Serialize)]
+      struct Untagged<'a> {
+          ciphertext: &'a [u8],
+          nonce: &'a [u8],
+      }
+      let bytes = postcard::to_stdvec(&Untagged { ciphertext: &encrypted.ciphertext, nonce: &encrypted.nonce }).unwrap();
+      let legacy: EncryptedData = crate::migrations::with_binary_layout(1, || postcard::from_bytes(&bytes)).unwrap();
+      assert_eq!(engine.decrypt_bytes(&legacy).unwrap(), b"tagged");
+      assert!(postcard::from_bytes::<EncryptedData>(&bytes).is_err());
+      
+      let unknown = EncryptedData { alg: 2, ..encrypted.clone() };
+      let err = engine.decrypt_bytes(&unknown).unwrap_err();
+      assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::Corrupted(_))));
+      
+      // A short nonce is an error rather than a panic
+      let truncated = EncryptedData { nonce: encrypted.nonce[..8].to_vec(), ..encrypted };
+      assert!(engine.decrypt_bytes(&truncated).is_err());
+  }
+  
+  #[test]
+  fn test_value_encodings() {
+      let data = b"\x00key\xff";
+      assert_eq!(encode_hex(data), "006b6579ff");
+      assert_eq!(decode_hex("006B6579ff").unwrap(), data);
+      assert!(decode_hex("abc").is_err());
+      assert!(decode_hex("zz").is_err());
+      assert!(decode_hex("é0").is_err());
+      
+      for encoding in [ValueEncoding::Base64, ValueEncoding::Hex] {
+          assert_eq!(encoding.decode(&encoding.encode(data)).unwrap(), data);
+      }
+      assert_eq!("base64".parse::<ValueEncoding>().unwrap(), ValueEncoding::Base64);
+      assert!("rot13".parse::<ValueEncoding>().is_err());
+  }
+  
+  #[test]
+  fn test_secret_string_redacts_debug() {
+      let key = SecretKey::new([42u8; 32]);
+      let engine = CryptoEngine::new(&key);
+      let encrypted = engine.encrypt("hunter2").unwrap();
+      
+      let secret = engine.decrypt_secret(&encrypted).unwrap();
+      assert_eq!(secret.expose(), "hunter2");
+      assert!(!format!("{:?}", secret).contains("hunter2"));
+  }
+  
+  #[test]
+  fn test_key_wrapping() {
+      let kek = SecretKey::new([5u8; 32]);
+      let dek = generate_key();
+      
+      let wrapped = wrap_key(&kek, &dek).unwrap();
+      let unwrapped = unwrap_key(&kek, &wrapped).unwrap();
+      assert_eq!(dek.as_bytes(), unwrapped.as_bytes());
+      
+      let wrong_kek = SecretKey::new([6u8; 32]);
+      assert!(unwrap_key(&wrong_kek, &wrapped).is_err());
+  }
+  
   #[test]
   fn test_password_hashing() {
       let password = "test_password";
@@ -154,5 +639,85 @@ mod tests {
       let key2 = derive_key_from_password(password, &salt).unwrap();
       
       assert_eq!(key1.as_bytes(), key2.as_bytes());
+      
+      assert!(derive_key_from_password(password, &salt[..16]).is_err());
+      assert!(derive_key_from_password(password, &[]).is_err());
+  }
+  
+  #[test]
+  fn test_kdf_params_from_hash() {
+      let hash = hash_password("test_password").unwrap();
+      assert_eq!(KdfParams::from_hash(&hash).unwrap(), KdfParams::RECOMMENDED);
+      
+      // Hashes from before the cost was raised carry argon2's defaults, and
+      // deriving with them must match the key those vaults were created with
+      let legacy_salt = SaltString::generate(&mut OsRng);
+      let legacy_hash = Argon2::default().hash_password(b"test_password", &legacy_salt).unwrap().to_string();
+      let legacy = KdfParams::from_hash(&legacy_hash).unwrap();
+      assert!(legacy.is_weaker_than(&KdfParams::RECOMMENDED));
+      assert!(!KdfParams::RECOMMENDED.is_weaker_than(&legacy));
+      
+      let salt = generate_salt();
+      assert_eq!(
+          derive_key_with_params("test_password", &salt, &legacy).unwrap().as_bytes(),
+          derive_key_from_password("test_password", &salt).unwrap().as_bytes(),
+      );
+  }
+  
+  #[test]
+  fn test_kdf_for_hash() {
+      let kdf = KdfAlgorithm::Argon2id.recommended();
+      let hash = kdf.hash("test_password").unwrap();
+      assert_eq!(kdf.to_string(), "Argon2id (m=65536 KiB, t=3, p=1)");
+      
+      // Unlocking goes through the costs recorded in the hash
+      let legacy_salt = SaltString::generate(&mut OsRng);
+      let legacy_hash = Argon2::default().hash_password(b"test_password", &legacy_salt).unwrap().to_string();
+      let legacy = KdfAlgorithm::Argon2id.for_hash(&legacy_hash).unwrap();
+      assert!(legacy.verify("test_password", &legacy_hash).unwrap());
+      assert!(!legacy.verify("wrong_password", &legacy_hash).unwrap());
+      assert!(legacy.verify("test_password", &hash).unwrap());
+      
+      let salt = generate_salt();
+      assert_eq!(
+          legacy.derive_key("test_password", &salt).unwrap().as_bytes(),
+          derive_key_from_password("test_password", &salt).unwrap().as_bytes(),
+      );
+      assert!(KdfAlgorithm::Argon2id.for_hash("not a hash").is_err());
+      
+      // Re-hashing never lowers a raised cost, and lifts a weak one
+      assert_eq!(KdfAlgorithm::Argon2id.for_rehash(&legacy_hash).to_string(), kdf.to_string());
+      let strong = Argon2Kdf { params: KdfParams { m_cost: 65536, t_cost: 4, p_cost: 1 } };
+      let strong_hash = strong.hash("test_password").unwrap();
+      assert_eq!(KdfAlgorithm::Argon2id.for_rehash(&strong_hash).to_string(), strong.to_string());
+  }
+  
+  #[test]
+  fn test_kdf_params_from_str() {
+      assert_eq!("m=131072,t=4,p=2".parse::<KdfParams>().unwrap(), KdfParams { m_cost: 131072, t_cost: 4, p_cost: 2 });
+      assert_eq!("t=5".parse::<KdfParams>().unwrap(), KdfParams { t_cost: 5, ..KdfParams::RECOMMENDED });
+      assert!("m=lots".parse::<KdfParams>().is_err());
+      assert!("x=1".parse::<KdfParams>().is_err());
+      assert!("t=0".parse::<KdfParams>().is_err());
+  }
+  
+  #[test]
+  fn test_keyfile_key_derivation() {
+      let salt = generate_salt();
+      let keyfile = [7u8; 64];
+      let password_key = derive_key_from_password("test_password", &salt).unwrap();
+      
+      let only1 = derive_key_from_keyfile(None, &keyfile, &salt).unwrap();
+      let only2 = derive_key_from_keyfile(None, &keyfile, &salt).unwrap();
+      let combined = derive_key_from_keyfile(Some(&password_key), &keyfile, &salt).unwrap();
+      let other = derive_key_from_keyfile(None, &[8u8; 64], &salt).unwrap();
+      
+      assert_eq!(only1.as_bytes(), only2.as_bytes());
+      assert_ne!(only1.as_bytes(), combined.as_bytes());
+      assert_ne!(only1.as_bytes(), other.as_bytes());
+      assert_ne!(
+          keyfile_fingerprint(&keyfile, &salt).unwrap(),
+          encode_base64(only1.as_bytes())
+      );
   }
 }
\ No newline at end of file
@@ -0,0 +1,166 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::identity::Identity;
+use crate::migrations;
+use crate::utils::{get_vault_dir, permissive_mode};
+use crate::vault::VaultFormat;
+
+/// Outcome of one `doctor` check
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+    /// How to fix a failed check
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), passed: true, detail: detail.into(), hint: None }
+    }
+    
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), passed: false, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Diagnose the vault setup without unlocking it. Checks stop early when a
+/// later one could only fail for the same reason, e.g. a missing directory.
+pub fn run_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+    
+    match dirs::home_dir() {
+        Some(home) => checks.push(Check::pass("Home directory", home.display().to_string())),
+        None => {
+            checks.push(Check::fail("Home directory", "could not be determined", "Set the HOME environment variable"));
+            return checks;
+        }
+    }
+    
+    let vault_dir = match get_vault_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            checks.push(Check::fail("Vault directory", e.to_string(), "Set the HOME environment variable"));
+            return checks;
+        }
+    };
+    if !vault_dir.is_dir() {
+        checks.push(Check::fail("Vault directory", format!("{} does not exist", vault_dir.display()), "Run `sentinel init`"));
+        return checks;
+    }
+    checks.push(Check::pass("Vault directory", vault_dir.display().to_string()));
+    checks.push(check_permissions("Vault directory permissions", &vault_dir, "700"));
+    
+    check_file(&mut checks, "Identity file", &vault_dir.join("identity.ron"), |contents| {
+        Ok(if Identity::inspect(std::str::from_utf8(contents)?)? { "sealed with a PIN" } else { "valid" }.to_string())
+    });
+    check_file(&mut checks, "Vault file", &vault_dir.join("vault.ron"), |contents| {
+        let data = VaultFormat::decode(contents)?;
+        migrations::check_supported(&data.version)?;
+        Ok(format!("version {}, {} secret(s)", data.version, data.secrets.len()))
+    });
+    
+    checks
+}
+
+/// Size and last change of one of the vault's files
+#[derive(Debug)]
+pub struct FileStatus {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+impl FileStatus {
+    /// `None` if the file doesn't exist
+    fn read(path: PathBuf) -> Result<Option<Self>> {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        
+        Ok(Some(Self { path, size: metadata.len(), modified: metadata.modified()?.into() }))
+    }
+}
+
+/// What `status` reports: everything readable from the files without the key
+#[derive(Debug)]
+pub struct Status {
+    pub identity: Option<FileStatus>,
+    pub vault: Option<FileStatus>,
+    /// `None` if there's no vault file
+    pub contents: Option<VaultContents>,
+}
+
+/// The unencrypted parts of a vault file
+#[derive(Debug)]
+pub struct VaultContents {
+    pub format: VaultFormat,
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+    /// Encrypted entries, whether or not their lease has run out
+    pub secrets: usize,
+}
+
+impl Status {
+    /// Both files exist and the vault file parsed
+    pub fn is_healthy(&self) -> bool {
+        self.identity.is_some() && self.contents.is_some()
+    }
+}
+
+/// Look at the identity and vault files without unlocking or prompting. A
+/// vault file that exists but doesn't parse is an error.
+pub fn status() -> Result<Status> {
+    let vault_dir = get_vault_dir()?;
+    let identity = FileStatus::read(vault_dir.join("identity.ron"))?;
+    let vault = FileStatus::read(vault_dir.join("vault.ron"))?;
+    
+    let contents = match &vault {
+        Some(file) => {
+            let bytes = fs::read(&file.path)?;
+            let data = VaultFormat::decode(&bytes)?;
+            migrations::check_supported(&data.version)?;
+            Some(VaultContents {
+                format: VaultFormat::detect(&bytes),
+                version: data.version,
+                created_at: data.created_at,
+                secrets: data.secrets.len(),
+            })
+        }
+        None => None,
+    };
+    
+    Ok(Status { identity, vault, contents })
+}
+
+/// Check that a file exists, parses, and is private to the owner
+fn check_file(checks: &mut Vec<Check>, name: &str, path: &Path, parse: impl Fn(&[u8]) -> Result<String>) {
+    if !path.exists() {
+        checks.push(Check::fail(name, format!("{} is missing", path.display()), "Run `sentinel init`, or `sentinel init --from-backup` to restore"));
+        return;
+    }
+    
+    let parsed = fs::read(path).map_err(anyhow::Error::from).and_then(|contents| parse(&contents));
+    match parsed {
+        Ok(detail) => checks.push(Check::pass(name, detail)),
+        Err(e) => checks.push(Check::fail(name, format!("{:#}", e), "Restore the file from a backup")),
+    }
+    checks.push(check_permissions(&format!("{} permissions", name), path, "600"));
+}
+
+fn check_permissions(name: &str, path: &Path, wanted: &str) -> Check {
+    match permissive_mode(path) {
+        Some(mode) => Check::fail(
+            name,
+            format!("{:o}, accessible to other users", mode),
+            format!("chmod {} {}", wanted, path.display()),
+        ),
+        None => Check::pass(name, "private to owner"),
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Failures that callers may need to tell apart. Fallible functions still
+/// return `anyhow::Result`, with these carried inside; match on them with
+/// `err.downcast_ref::<VaultError>()`.
+#[derive(Debug, Error)]
+pub enum VaultError {
+    #[error("Secret '{0}' not found")]
+    NotFound(String),
+    
+    #[error("Secret '{0}' already exists")]
+    AlreadyExists(String),
+    
+    #[error("Secret '{name}' expired on {}", .expired_at.format("%Y-%m-%d %H:%M:%S UTC"))]
+    Expired { name: String, expired_at: DateTime<Utc> },
+    
+    /// Wrong password, PIN, keyfile or authentication code, or a missing factor
+    #[error("{0}")]
+    AuthFailed(String),
+    
+    /// A vault or identity file that can't be parsed or decrypted with a valid key
+    #[error("Vault data is corrupted: {0}")]
+    Corrupted(String),
+    
+    #[error("Vault not initialized. Run 'sentinel init' first.")]
+    NotInitialized,
+    
+    #[error("Vault already initialized. Use 'sentinel add' to add secrets.")]
+    AlreadyInitialized,
+    
+    #[error("Vault version {found} is newer than this build supports ({supported}). Upgrade sentinelvault to open it.")]
+    NewerVersion { found: String, supported: String },
+    
+    #[error("System clock is {seconds}s behind the vault's last save, so expiry can't be trusted. Fix the clock or pass --allow-clock-skew.")]
+    ClockSkew { seconds: i64 },
+    
+    #[error("Vault is in read-only mode. Run 'sentinel unlock-vault' to allow changes.")]
+    ReadOnly,
+    
+    #[error("Vault is limited to {limit} secrets. Remove some or raise the limit with 'sentinel max-secrets'.")]
+    SecretLimit { limit: usize },
+}
+
+impl VaultError {
+    pub(crate) fn auth(message: &str) -> Self {
+        VaultError::AuthFailed(message.to_string())
+    }
+    
+    /// Process exit status for `get --require-active`, so scripts can tell a
+    /// typo from an expired secret. Everything else exits with 1.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VaultError::NotFound(_) => 2,
+            VaultError::Expired { .. } => 3,
+            _ => 1,
+        }
+    }
+}
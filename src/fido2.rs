@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use ctap_hid_fido2::fidokey::{AssertionExtension, CredentialExtension, GetAssertionArgsBuilder, MakeCredentialArgsBuilder};
+use ctap_hid_fido2::{Cfg, FidoKeyHidFactory};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::identity::Fido2Binding;
+
+/// Relying party the credential is registered under
+const RP_ID: &str = "sentinelvault";
+
+fn open_device() -> Result<ctap_hid_fido2::FidoKeyHid> {
+    let cfg = Cfg {
+        keep_alive_msg: "Touch your security key...".to_string(),
+        keep_alive_msg_to_stderr: true,
+        ..Cfg::init()
+    };
+    FidoKeyHidFactory::create(&cfg).map_err(|e| anyhow!("No usable FIDO2 security key: {}", e))
+}
+
+fn challenge() -> [u8; 32] {
+    let mut challenge = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut challenge);
+    challenge
+}
+
+/// Register a new hmac-secret credential on the connected security key
+pub fn register() -> Result<Fido2Binding> {
+    let device = open_device()?;
+    eprintln!("Touch your security key to register it with the vault");
+    
+    let args = MakeCredentialArgsBuilder::new(RP_ID, &challenge())
+        .extensions(&[CredentialExtension::HmacSecret(Some(true))])
+        .without_pin_and_uv()
+        .build();
+    let attestation = device.make_credential_with_args(&args)
+        .map_err(|e| anyhow!("Failed to register security key: {}", e))?;
+    
+    let mut salt = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    
+    Ok(Fido2Binding {
+        credential_id: attestation.credential_descriptor.id,
+        salt,
+    })
+}
+
+/// Ask the security key for its hmac-secret over the binding's salt. The
+/// same key always answers the same salt with the same 32 bytes.
+pub fn hmac_secret(binding: &Fido2Binding) -> Result<Zeroizing<[u8; 32]>> {
+    let salt: [u8; 32] = binding.salt.as_slice().try_into()
+        .map_err(|_| anyhow!("Security key salt must be 32 bytes"))?;
+    let device = open_device()?;
+    
+    let args = GetAssertionArgsBuilder::new(RP_ID, &challenge())
+        .credential_id(&binding.credential_id)
+        .extensions(&[AssertionExtension::HmacSecret(Some(salt))])
+        .without_pin_and_uv()
+        .build();
+    let assertions = device.get_assertion_with_args(&args)
+        .map_err(|e| anyhow!("Security key did not respond (is it the one this vault was created with?): {}", e))?;
+    
+    assertions
+        .iter()
+        .flat_map(|assertion| &assertion.extensions)
+        .find_map(|extension| match extension {
+            AssertionExtension::HmacSecret(Some(output)) => Some(Zeroizing::new(*output)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("Security key does not support the hmac-secret extension"))
+}
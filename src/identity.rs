@@ -1,22 +1,119 @@
 use anyhow::{anyhow, Result};
-use inquire::{Password, PasswordDisplayMode};
+use inquire::{Password, PasswordDisplayMode, Text};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
-use crate::crypto::{derive_key_from_password, hash_password, verify_password, SecretKey, generate_salt};
-use crate::utils::get_vault_dir;
+use crate::crypto::{bind_key_to_hardware, bind_key_to_machine, derive_key_from_keyfile, derive_key_from_password, keyfile_fingerprint, check_salt, Argon2Kdf, CryptoEngine, EncryptedData, Kdf, KdfAlgorithm, KdfParams, SecretKey, generate_salt};
+use crate::error::VaultError;
+use crate::profile;
+use crate::totp;
+use crate::utils::{confirm_action, create_private_dir, get_vault_dir, password_weaknesses, replace_private_file, secure_compare, warn_if_permissive, write_private_file, MIN_PASSWORD_LEN};
+use crate::vault::{rewrap_stored_key, stored_key_opens};
+
+/// Minimum keyfile length, matching the size of the vault key
+const MIN_KEYFILE_LEN: usize = 32;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Identity {
     pub password_hash: String,
     pub salt: Vec<u8>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Fingerprint of the keyfile required to unlock, if any
+    #[serde(default)]
+    pub keyfile_fingerprint: Option<String>,
+    /// Whether the keyfile alone unlocks the vault, without a master password
+    #[serde(default)]
+    pub keyfile_only: bool,
+    /// TOTP secret encrypted under the vault key, when two-factor auth is enabled
+    #[serde(default)]
+    pub totp_secret: Option<EncryptedData>,
+    /// Whether the machine id is mixed into the key, so the files won't open on another host
+    #[serde(default)]
+    pub machine_bound: bool,
+    /// FIDO2 security key whose hmac-secret is mixed into the key
+    #[serde(default)]
+    pub fido2: Option<Fido2Binding>,
+    /// Scheme the password hash and key derivation use
+    #[serde(default)]
+    pub kdf: KdfAlgorithm,
+    /// Argon2 cost of deriving the vault key, when it is tuned apart from
+    /// the password hash. `None` derives at the hash's own cost.
+    #[serde(default)]
+    pub key_params: Option<KdfParams>,
+    /// PIN-derived key used to keep identity.ron encrypted at rest
+    #[serde(skip)]
+    sealing: Option<Sealing>,
+}
+
+/// A credential registered on a FIDO2 security key with the hmac-secret
+/// extension. Neither field is secret; the key's answer to `salt` is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fido2Binding {
+    pub credential_id: Vec<u8>,
+    pub salt: Vec<u8>,
+}
+
+/// How the current invocation proves its right to open the vault
+#[derive(Debug, Default, Clone)]
+pub struct AuthOptions {
+    pub keyfile: Option<PathBuf>,
+    /// Read the master password from this file instead of prompting
+    pub password_file: Option<PathBuf>,
+    /// Leave a password hashed below the recommended Argon2 cost as it is
+    pub skip_kdf_upgrade: bool,
+}
+
+/// Environment variable holding the master password for non-interactive use
+pub const PASSWORD_ENV: &str = "SENTINELVAULT_PASSWORD";
+
+/// Overrides the detected machine id, to move a machine-bound vault to a new host
+pub const MACHINE_ID_ENV: &str = "SENTINELVAULT_MACHINE_ID";
+
+/// The id a machine-bound vault is tied to: `SENTINELVAULT_MACHINE_ID` if set,
+/// otherwise the operating system's stable machine identifier
+pub fn machine_id() -> Result<String> {
+    if let Some(id) = std::env::var_os(MACHINE_ID_ENV).filter(|value| !value.is_empty()) {
+        return id.into_string().map_err(|_| anyhow!("{} is not valid UTF-8", MACHINE_ID_ENV));
+    }
+    
+    machine_uid::get().map_err(|e| anyhow!("Could not read the machine id: {}", e))
+}
+
+#[derive(Debug)]
+struct Sealing {
+    salt: Vec<u8>,
+    key: SecretKey,
+}
+
+/// On-disk form of an identity encrypted under a PIN
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedIdentity {
+    pin_salt: Vec<u8>,
+    sealed: EncryptedData,
 }
 
 impl Identity {
     pub fn new(password: &str) -> Result<Self> {
-        let password_hash = hash_password(password)?;
+        Self::with_costs(password, KdfParams::RECOMMENDED, None)
+    }
+    
+    /// An identity whose password hash, and optionally key derivation, cost
+    /// more than the recommended Argon2 parameters. Lower costs are refused,
+    /// since unlocking would upgrade them straight away.
+    pub fn with_costs(password: &str, hash_params: KdfParams, key_params: Option<KdfParams>) -> Result<Self> {
+        for params in std::iter::once(&hash_params).chain(&key_params) {
+            if params.is_weaker_than(&KdfParams::RECOMMENDED) {
+                let recommended = KdfParams::RECOMMENDED;
+                return Err(anyhow!(
+                    "Argon2 costs can only be raised above m={}, t={}, p={}",
+                    recommended.m_cost, recommended.t_cost, recommended.p_cost
+                ));
+            }
+        }
+        
+        let password_hash = Argon2Kdf { params: hash_params }.hash(password)?;
         let salt = generate_salt().to_vec();
         let created_at = chrono::Utc::now();
         
@@ -24,29 +121,203 @@ impl Identity {
             password_hash,
             salt,
             created_at,
+            keyfile_fingerprint: None,
+            keyfile_only: false,
+            totp_secret: None,
+            machine_bound: false,
+            fido2: None,
+            kdf: KdfAlgorithm::default(),
+            key_params,
+            sealing: None,
         })
     }
     
+    /// Rebuild an identity from the parameters stored in a backup, so the
+    /// original master password derives the same key
+    pub fn from_backup(password_hash: &str, salt: &[u8]) -> Self {
+        Self {
+            password_hash: password_hash.to_string(),
+            salt: salt.to_vec(),
+            created_at: chrono::Utc::now(),
+            keyfile_fingerprint: None,
+            keyfile_only: false,
+            totp_secret: None,
+            machine_bound: false,
+            fido2: None,
+            kdf: KdfAlgorithm::default(),
+            key_params: None,
+            sealing: None,
+        }
+    }
+    
+    /// Identity for a vault unlocked by a keyfile alone
+    pub fn new_keyfile_only(keyfile: &[u8]) -> Result<Self> {
+        let mut identity = Self {
+            password_hash: String::new(),
+            salt: generate_salt().to_vec(),
+            created_at: chrono::Utc::now(),
+            keyfile_fingerprint: None,
+            keyfile_only: true,
+            totp_secret: None,
+            machine_bound: false,
+            fido2: None,
+            kdf: KdfAlgorithm::default(),
+            key_params: None,
+            sealing: None,
+        };
+        identity.require_keyfile(keyfile)?;
+        
+        Ok(identity)
+    }
+    
+    /// Require a keyfile in addition to the master password
+    pub fn require_keyfile(&mut self, keyfile: &[u8]) -> Result<()> {
+        self.keyfile_fingerprint = Some(keyfile_fingerprint(keyfile, &self.salt)?);
+        Ok(())
+    }
+    
+    /// Require a PIN to decrypt the identity file before the master password prompt
+    pub fn seal_with_pin(&mut self, pin: &str) -> Result<()> {
+        let salt = generate_salt().to_vec();
+        let key = derive_key_from_password(pin, &salt)?;
+        self.sealing = Some(Sealing { salt, key });
+        Ok(())
+    }
+    
+    pub fn is_sealed(&self) -> bool {
+        self.sealing.is_some()
+    }
+    
     pub fn verify_password(&self, password: &str) -> Result<bool> {
-        verify_password(password, &self.password_hash)
+        self.kdf.for_hash(&self.password_hash)?.verify(password, &self.password_hash)
     }
     
     pub fn derive_key(&self, password: &str) -> Result<SecretKey> {
         if !self.verify_password(password)? {
-            return Err(anyhow!("Invalid password"));
+            return Err(VaultError::auth("Invalid password").into());
+        }
+        
+        let kdf: Box<dyn Kdf> = match self.key_params {
+            Some(params) => Box::new(Argon2Kdf { params }),
+            None => self.kdf.for_hash(&self.password_hash)?,
+        };
+        profile::time(&format!("key derivation, {}", kdf), || kdf.derive_key(password, &self.salt))
+    }
+    
+    /// Whether the master password's hash or key derivation cost is below
+    /// [`KdfParams::RECOMMENDED`]. Keyfile-only identities have no password
+    /// to strengthen.
+    pub fn needs_kdf_upgrade(&self) -> bool {
+        let weak = |params: &KdfParams| params.is_weaker_than(&KdfParams::RECOMMENDED);
+        !self.keyfile_only
+            && self.kdf == KdfAlgorithm::Argon2id
+            && (KdfParams::from_hash(&self.password_hash).is_ok_and(|params| weak(&params)) || self.key_params.as_ref().is_some_and(weak))
+    }
+    
+    /// Derive the vault key from whichever factors this identity requires
+    pub fn unlock(&self, password: Option<&str>, keyfile: Option<&[u8]>) -> Result<SecretKey> {
+        let mut key = self.unlock_unbound(password, keyfile)?;
+        if let Some(binding) = &self.fido2 {
+            key = bind_key_to_hardware(&key, &*security_key_secret(binding)?, &self.salt)?;
+        }
+        if !self.machine_bound {
+            return Ok(key);
+        }
+        
+        bind_key_to_machine(&key, &machine_id()?, &self.salt)
+    }
+    
+    fn unlock_unbound(&self, password: Option<&str>, keyfile: Option<&[u8]>) -> Result<SecretKey> {
+        let password_key = match password {
+            Some(password) if !self.keyfile_only => Some(self.derive_key(password)?),
+            _ => None,
+        };
+        
+        let fingerprint = match &self.keyfile_fingerprint {
+            Some(fingerprint) => fingerprint,
+            None => return password_key.ok_or_else(|| VaultError::auth("Master password required").into()),
+        };
+        
+        let keyfile = keyfile.ok_or_else(|| VaultError::auth("This vault requires a keyfile (use --keyfile)"))?;
+        if !secure_compare(fingerprint, &keyfile_fingerprint(keyfile, &self.salt)?) {
+            return Err(VaultError::auth("Invalid keyfile").into());
+        }
+        
+        if !self.keyfile_only && password_key.is_none() {
+            return Err(VaultError::auth("Master password required").into());
+        }
+        
+        derive_key_from_keyfile(password_key.as_ref(), keyfile, &self.salt)
+    }
+    
+    /// Enable TOTP two-factor auth, returning the base32 secret for the authenticator app
+    pub fn enable_totp(&mut self, key: &SecretKey) -> Result<String> {
+        let secret = totp::generate_secret();
+        self.totp_secret = Some(CryptoEngine::new(key).encrypt(&secret)?);
+        Ok(secret)
+    }
+    
+    /// Replace the master password, returning the new key-encryption key.
+    ///
+    /// `old_key` is the key derived from the current password, needed to carry
+    /// the TOTP secret over.
+    pub fn change_password(&mut self, old_key: &SecretKey, new_password: &str, keyfile: Option<&[u8]>) -> Result<SecretKey> {
+        if self.keyfile_only {
+            return Err(anyhow!("Keyfile-only vaults have no master password to change"));
+        }
+        
+        if self.keyfile_fingerprint.is_some() && keyfile.is_none() {
+            return Err(VaultError::auth("This vault requires a keyfile (use --keyfile)").into());
+        }
+        
+        let totp_secret = match &self.totp_secret {
+            Some(encrypted) => Some(Zeroizing::new(CryptoEngine::new(old_key).decrypt(encrypted)?)),
+            None => None,
+        };
+        
+        self.password_hash = self.kdf.for_rehash(&self.password_hash).hash(new_password)?;
+        self.key_params = self.key_params.filter(|params| !params.is_weaker_than(&KdfParams::RECOMMENDED));
+        self.salt = generate_salt().to_vec();
+        if let (Some(_), Some(keyfile)) = (&self.keyfile_fingerprint, keyfile) {
+            self.require_keyfile(keyfile)?;
         }
         
-        derive_key_from_password(password, &self.salt)
+        let new_key = self.unlock(Some(new_password), keyfile)?;
+        if let Some(secret) = totp_secret {
+            self.totp_secret = Some(CryptoEngine::new(&new_key).encrypt(&secret)?);
+        }
+        
+        Ok(new_key)
+    }
+    
+    pub fn verify_totp(&self, key: &SecretKey, code: &str) -> Result<bool> {
+        let encrypted = match &self.totp_secret {
+            Some(encrypted) => encrypted,
+            None => return Ok(true),
+        };
+        
+        let secret = Zeroizing::new(CryptoEngine::new(key).decrypt(encrypted)?);
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        totp::verify_code(&secret, code, now)
     }
     
     pub fn save(&self) -> Result<()> {
         let vault_dir = get_vault_dir()?;
-        fs::create_dir_all(&vault_dir)?;
+        create_private_dir(&vault_dir)?;
         
         let identity_path = vault_dir.join("identity.ron");
-        let identity_data = ron::to_string(&self)?;
+        let identity_data = match &self.sealing {
+            Some(sealing) => {
+                let engine = CryptoEngine::new(&sealing.key);
+                ron::to_string(&SealedIdentity {
+                    pin_salt: sealing.salt.clone(),
+                    sealed: engine.encrypt(&ron::to_string(&self)?)?,
+                })?
+            }
+            None => ron::to_string(&self)?,
+        };
         
-        fs::write(identity_path, identity_data)?;
+        replace_private_file(&identity_path, identity_data.as_bytes())?;
         Ok(())
     }
     
@@ -55,15 +326,69 @@ impl Identity {
         let identity_path = vault_dir.join("identity.ron");
         
         if !identity_path.exists() {
-            return Err(anyhow!("Vault not initialized. Run 'sentinel init' first."));
+            return Err(VaultError::NotInitialized.into());
         }
         
+        warn_if_permissive(&identity_path);
+        log::debug!("Reading identity from {}", identity_path.display());
         let identity_data = fs::read_to_string(identity_path)?;
-        let identity: Identity = ron::from_str(&identity_data)?;
+        Self::parse(&identity_data)
+    }
+    
+    /// Read an identity file's contents, prompting for the PIN if it is sealed
+    fn parse(identity_data: &str) -> Result<Self> {
+        if let Ok(identity) = ron::from_str::<Identity>(identity_data) {
+            check_salt(&identity.salt)?;
+            return Ok(identity);
+        }
+        
+        let sealed: SealedIdentity = ron::from_str(identity_data)
+            .map_err(|e| VaultError::Corrupted(e.to_string()))?;
+        let pin = prompt_pin()?;
+        let identity = Self::unseal(&sealed, &pin)?;
+        check_salt(&identity.salt)?;
+        Ok(identity)
+    }
+    
+    /// Check that `contents` is a well-formed identity file without unlocking
+    /// it. Returns whether it is sealed under a PIN.
+    pub(crate) fn inspect(contents: &str) -> Result<bool> {
+        if let Ok(identity) = ron::from_str::<Identity>(contents) {
+            check_salt(&identity.salt)?;
+            return Ok(false);
+        }
+        
+        ron::from_str::<SealedIdentity>(contents)
+            .map(|_| true)
+            .map_err(|e| VaultError::Corrupted(e.to_string()).into())
+    }
+    
+    fn unseal(sealed: &SealedIdentity, pin: &str) -> Result<Self> {
+        let key = derive_key_from_password(pin, &sealed.pin_salt)?;
+        let engine = CryptoEngine::new(&key);
+        let identity_data = engine
+            .decrypt(&sealed.sealed)
+            .map_err(|_| VaultError::auth("Invalid PIN"))?;
+        
+        let mut identity: Identity = ron::from_str(&identity_data)?;
+        identity.sealing = Some(Sealing {
+            salt: sealed.pin_salt.clone(),
+            key,
+        });
         
         Ok(identity)
     }
     
+    /// Delete the identity file, leaving the vault uninitialized
+    pub fn delete() -> Result<()> {
+        let identity_path = get_vault_dir()?.join("identity.ron");
+        if identity_path.exists() {
+            fs::remove_file(&identity_path)
+                .map_err(|e| anyhow!("Failed to remove {}: {}", identity_path.display(), e))?;
+        }
+        Ok(())
+    }
+    
     pub fn exists() -> bool {
         let vault_dir = get_vault_dir().ok();
         if let Some(dir) = vault_dir {
@@ -74,6 +399,24 @@ impl Identity {
     }
 }
 
+#[cfg(feature = "fido2")]
+fn security_key_secret(binding: &Fido2Binding) -> Result<Zeroizing<[u8; 32]>> {
+    crate::fido2::hmac_secret(binding)
+}
+
+#[cfg(not(feature = "fido2"))]
+fn security_key_secret(_binding: &Fido2Binding) -> Result<Zeroizing<[u8; 32]>> {
+    Err(anyhow!("This vault requires a FIDO2 security key. Rebuild with `--features fido2` to unlock it."))
+}
+
+/// Register the connected security key, for `init --fido2`
+pub fn register_security_key() -> Result<Fido2Binding> {
+    #[cfg(feature = "fido2")]
+    return crate::fido2::register();
+    #[cfg(not(feature = "fido2"))]
+    Err(anyhow!("FIDO2 support is not compiled in. Rebuild with `--features fido2`."))
+}
+
 pub fn prompt_master_password() -> Result<String> {
     let password = Password::new("Enter master password:")
         .with_display_mode(PasswordDisplayMode::Masked)
@@ -86,13 +429,40 @@ pub fn prompt_master_password() -> Result<String> {
     Ok(password)
 }
 
+/// The master password from `--password-file` or `SENTINELVAULT_PASSWORD`,
+/// prompting only when neither is set
+pub fn resolve_master_password(options: &AuthOptions) -> Result<Zeroizing<String>> {
+    if let Some(path) = &options.password_file {
+        let contents = Zeroizing::new(
+            fs::read_to_string(path)
+                .map_err(|e| anyhow!("Failed to read password file {}: {}", path.display(), e))?,
+        );
+        return Ok(Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    
+    if let Some(password) = std::env::var_os(PASSWORD_ENV).filter(|value| !value.is_empty()) {
+        eprintln!("Warning: using the master password from {}, which other processes may be able to read", PASSWORD_ENV);
+        let password = password
+            .into_string()
+            .map_err(|_| anyhow!("{} is not valid UTF-8", PASSWORD_ENV))?;
+        return Ok(Zeroizing::new(password));
+    }
+    
+    Ok(Zeroizing::new(prompt_master_password()?))
+}
+
 pub fn prompt_new_master_password() -> Result<String> {
     let password = Password::new("Create master password (min 8 characters):")
         .with_display_mode(PasswordDisplayMode::Masked)
         .prompt()?;
     
-    if password.len() < 8 {
-        return Err(anyhow!("Password must be at least 8 characters long"));
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(anyhow!("Password must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    
+    let weaknesses = password_weaknesses(&password);
+    if !weaknesses.is_empty() {
+        eprintln!("Warning: weak master password ({})", weaknesses.join(", "));
     }
     
     let confirm = Password::new("Confirm master password:")
@@ -106,16 +476,250 @@ pub fn prompt_new_master_password() -> Result<String> {
     Ok(password)
 }
 
-pub fn authenticate() -> Result<SecretKey> {
+pub fn prompt_backup_passphrase() -> Result<String> {
+    let passphrase = Password::new("Enter backup passphrase:")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    Ok(passphrase)
+}
+
+pub fn prompt_new_backup_passphrase() -> Result<String> {
+    let passphrase = Password::new("Create backup passphrase (min 8 characters):")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    if passphrase.len() < MIN_PASSWORD_LEN {
+        return Err(anyhow!("Passphrase must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    
+    let confirm = Password::new("Confirm backup passphrase:")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    if passphrase != confirm {
+        return Err(anyhow!("Passphrases do not match"));
+    }
+    
+    Ok(passphrase)
+}
+
+pub fn prompt_pin() -> Result<String> {
+    let pin = Password::new("Enter PIN:")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    Ok(pin)
+}
+
+pub fn prompt_new_pin() -> Result<String> {
+    let pin = Password::new("Create PIN (min 4 characters):")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    if pin.len() < 4 {
+        return Err(anyhow!("PIN must be at least 4 characters long"));
+    }
+    
+    let confirm = Password::new("Confirm PIN:")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .prompt()?;
+    
+    if pin != confirm {
+        return Err(anyhow!("PINs do not match"));
+    }
+    
+    Ok(pin)
+}
+
+pub fn read_keyfile(path: &Path) -> Result<Zeroizing<Vec<u8>>> {
+    let keyfile = Zeroizing::new(
+        fs::read(path).map_err(|e| anyhow!("Failed to read keyfile {}: {}", path.display(), e))?,
+    );
+    
+    if keyfile.len() < MIN_KEYFILE_LEN {
+        return Err(anyhow!("Keyfile must contain at least {} bytes", MIN_KEYFILE_LEN));
+    }
+    
+    Ok(keyfile)
+}
+
+pub fn prompt_totp_code() -> Result<String> {
+    let code = Text::new("Enter 6-digit authentication code:").prompt()?;
+    Ok(code)
+}
+
+/// The password and keyfile given for one unlock
+struct Credentials {
+    password: Option<Zeroizing<String>>,
+    keyfile: Option<Zeroizing<Vec<u8>>>,
+}
+
+impl Credentials {
+    /// Read or prompt for the factors `identity` needs
+    fn collect(identity: &Identity, options: &AuthOptions) -> Result<Self> {
+        let keyfile = options.keyfile.as_deref().map(read_keyfile).transpose()?;
+        
+        let password = if identity.keyfile_only {
+            None
+        } else {
+            Some(resolve_master_password(options)?)
+        };
+        
+        Ok(Self { password, keyfile })
+    }
+    
+    fn password(&self) -> Option<&str> {
+        self.password.as_deref().map(String::as_str)
+    }
+    
+    fn keyfile(&self) -> Option<&[u8]> {
+        self.keyfile.as_deref().map(Vec::as_slice)
+    }
+}
+
+/// Unlock with the password and/or keyfile, without the TOTP check
+fn unlock_identity(identity: &Identity, options: &AuthOptions) -> Result<SecretKey> {
+    let credentials = Credentials::collect(identity, options)?;
+    identity.unlock(credentials.password(), credentials.keyfile())
+}
+
+/// Unlock with every factor the identity requires. If the master password's
+/// Argon2 cost is below the recommendation, it is re-hashed at the stronger
+/// cost and the vault's data key re-wrapped, unless
+/// `options.skip_kdf_upgrade` is set. The returned key is the one now in use.
+pub fn authenticate(options: &AuthOptions) -> Result<SecretKey> {
+    let mut identity = Identity::load()?;
+    let credentials = Credentials::collect(&identity, options)?;
+    let key = identity.unlock(credentials.password(), credentials.keyfile())?;
+    verify_second_factor(&identity, &key)?;
+    
+    if options.skip_kdf_upgrade || !identity.needs_kdf_upgrade() {
+        return Ok(key);
+    }
+    
+    match upgrade_kdf(&mut identity, &key, &credentials) {
+        Ok(Some(new_key)) => Ok(new_key),
+        Ok(None) => Ok(key),
+        Err(e) => {
+            eprintln!("Warning: could not strengthen the master password's key derivation: {:#}", e);
+            Ok(key)
+        }
+    }
+}
+
+/// Re-hash the same master password at the recommended cost and re-wrap the
+/// vault's data key under the new key. `None` if the vault can't be re-wrapped
+/// yet, e.g. it still needs its own migration on open.
+fn upgrade_kdf(identity: &mut Identity, key: &SecretKey, credentials: &Credentials) -> Result<Option<SecretKey>> {
+    let password = credentials.password().ok_or_else(|| anyhow!("No master password to re-hash"))?;
+    let new_key = identity.change_password(key, password, credentials.keyfile())?;
+    
+    if !rewrap_stored_key(key, &new_key)? {
+        return Ok(None);
+    }
+    if let Err(e) = identity.save() {
+        // The identity on disk still derives the old key, so the vault must match it
+        rewrap_stored_key(&new_key, key)?;
+        return Err(e);
+    }
+    log::info!("Upgraded the master password's Argon2 cost to {:?}", KdfParams::RECOMMENDED);
+    
+    Ok(Some(new_key))
+}
+
+/// Prompt for every factor an already loaded identity requires
+pub fn authenticate_identity(identity: &Identity, options: &AuthOptions) -> Result<SecretKey> {
+    let key = unlock_identity(identity, options)?;
+    verify_second_factor(identity, &key)?;
+    Ok(key)
+}
+
+fn verify_second_factor(identity: &Identity, key: &SecretKey) -> Result<()> {
+    if identity.totp_secret.is_some() {
+        let code = prompt_totp_code()?;
+        if !identity.verify_totp(key, &code)? {
+            return Err(VaultError::auth("Invalid authentication code").into());
+        }
+    }
+    
+    Ok(())
+}
+
+/// Check the master password again before revealing a protected secret.
+/// Keyfile-only vaults have no password, so they ask for confirmation instead.
+pub fn reauthenticate(options: &AuthOptions, confirmation: &str) -> Result<()> {
     let identity = Identity::load()?;
-    let password = prompt_master_password()?;
     
-    identity.derive_key(&password)
+    if identity.keyfile_only {
+        if !confirm_action(confirmation, false)? {
+            return Err(VaultError::auth("Cancelled").into());
+        }
+        return Ok(());
+    }
+    
+    unlock_identity(&identity, options)?;
+    Ok(())
+}
+
+/// Copy the identity file to `path` (0600) as it is, PIN seal included, so
+/// access can be backed up separately from the secrets
+pub fn export_identity(path: &Path, overwrite: bool) -> Result<()> {
+    let identity_path = get_vault_dir()?.join("identity.ron");
+    if !identity_path.exists() {
+        return Err(VaultError::NotInitialized.into());
+    }
+    
+    let contents = fs::read_to_string(&identity_path)?;
+    Identity::inspect(&contents)?;
+    write_private_file(path, contents.as_bytes(), overwrite)
+}
+
+/// Install an identity written by [`export_identity`]. It must unlock with
+/// the given credentials and, if there is a vault, open it; otherwise the
+/// current identity is left alone.
+pub fn import_identity(path: &Path, options: &AuthOptions, overwrite: bool) -> Result<()> {
+    let vault_dir = get_vault_dir()?;
+    let identity_path = vault_dir.join("identity.ron");
+    if identity_path.exists() && !overwrite {
+        return Err(anyhow!("{} already exists. Use --force to overwrite.", identity_path.display()));
+    }
+    
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let identity = Identity::parse(&contents)?;
+    let key = authenticate_identity(&identity, options)?;
+    
+    match stored_key_opens(&key) {
+        Ok(true) => {}
+        Ok(false) => return Err(VaultError::auth("The imported identity does not open this vault").into()),
+        Err(e) if matches!(e.downcast_ref::<VaultError>(), Some(VaultError::NotInitialized)) => {
+            log::info!("No vault file yet; only the identity's own credentials were checked");
+        }
+        Err(e) => return Err(e),
+    }
+    
+    create_private_dir(&vault_dir)?;
+    replace_private_file(&identity_path, contents.as_bytes())
+}
+
+/// Turn off TOTP, guarded by the password only so a lost authenticator can be recovered
+pub fn disable_two_factor(options: &AuthOptions) -> Result<bool> {
+    let mut identity = Identity::load()?;
+    unlock_identity(&identity, options)?;
+    
+    if identity.totp_secret.take().is_none() {
+        return Ok(false);
+    }
+    
+    identity.save()?;
+    Ok(true)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::SALT_LEN;
     use tempfile::TempDir;
     
     #[test]
@@ -138,6 +742,38 @@ mod tests {
         assert_eq!(key1.as_bytes(), key2.as_bytes());
     }
     
+    #[test]
+    fn test_machine_bound_key() {
+        let password = "test_password_123";
+        let mut identity = Identity::new(password).unwrap();
+        let unbound = identity.unlock(Some(password), None).unwrap();
+        
+        identity.machine_bound = true;
+        std::env::set_var(MACHINE_ID_ENV, "machine-a");
+        let on_a = identity.unlock(Some(password), None).unwrap();
+        assert_eq!(on_a.as_bytes(), identity.unlock(Some(password), None).unwrap().as_bytes());
+        assert_ne!(on_a.as_bytes(), unbound.as_bytes());
+        
+        std::env::set_var(MACHINE_ID_ENV, "machine-b");
+        let on_b = identity.unlock(Some(password), None).unwrap();
+        std::env::remove_var(MACHINE_ID_ENV);
+        assert_ne!(on_a.as_bytes(), on_b.as_bytes());
+    }
+    
+    #[cfg(not(feature = "fido2"))]
+    #[test]
+    fn test_security_key_required_without_fido2_support() {
+        let password = "test_password_123";
+        let mut identity = Identity::new(password).unwrap();
+        identity.fido2 = Some(Fido2Binding {
+            credential_id: vec![1, 2, 3],
+            salt: vec![0u8; 32],
+        });
+        
+        let err = identity.unlock(Some(password), None).unwrap_err();
+        assert!(err.to_string().contains("FIDO2"));
+    }
+    
     #[test]
     fn test_identity_persistence() {
         let temp_dir = TempDir::new().unwrap();
@@ -149,5 +785,147 @@ mod tests {
         
         let loaded_identity = Identity::load().unwrap();
         assert!(loaded_identity.verify_password(password).unwrap());
+        assert_eq!(loaded_identity.salt.len(), SALT_LEN);
+        
+        // Identities from before the KDF was recorded are Argon2id
+        let written = ron::to_string(&identity).unwrap();
+        let legacy = written.replace(",kdf:Argon2id", "");
+        assert_ne!(legacy, written);
+        let legacy: Identity = ron::from_str(&legacy).unwrap();
+        assert_eq!(legacy.kdf, KdfAlgorithm::Argon2id);
+        assert!(legacy.verify_password(password).unwrap());
+    }
+    
+    #[test]
+    fn test_separate_hash_and_key_costs() {
+        use crate::crypto::derive_key_with_params;
+        
+        let password = "test_password_123";
+        let hash_params = KdfParams { t_cost: 4, ..KdfParams::RECOMMENDED };
+        let key_params = KdfParams { t_cost: 5, ..KdfParams::RECOMMENDED };
+        let mut identity = Identity::with_costs(password, hash_params, Some(key_params)).unwrap();
+        assert_eq!(KdfParams::from_hash(&identity.password_hash).unwrap(), hash_params);
+        assert!(!identity.needs_kdf_upgrade());
+        
+        let key = identity.derive_key(password).unwrap();
+        assert_eq!(key.as_bytes(), derive_key_with_params(password, &identity.salt, &key_params).unwrap().as_bytes());
+        assert_ne!(key.as_bytes(), derive_key_with_params(password, &identity.salt, &hash_params).unwrap().as_bytes());
+        
+        // A new password keeps both raised costs
+        identity.change_password(&key, "new_password_456", None).unwrap();
+        assert_eq!(KdfParams::from_hash(&identity.password_hash).unwrap(), hash_params);
+        assert_eq!(identity.key_params, Some(key_params));
+        
+        let weak = KdfParams { m_cost: 19456, ..KdfParams::RECOMMENDED };
+        assert!(Identity::with_costs(password, weak, None).is_err());
+        assert!(Identity::with_costs(password, KdfParams::RECOMMENDED, Some(weak)).is_err());
+    }
+    
+    #[test]
+    fn test_sealed_identity_roundtrip() {
+        let password = "test_password_123";
+        let mut identity = Identity::new(password).unwrap();
+        identity.seal_with_pin("1234").unwrap();
+        
+        let sealing = identity.sealing.as_ref().unwrap();
+        let sealed = SealedIdentity {
+            pin_salt: sealing.salt.clone(),
+            sealed: CryptoEngine::new(&sealing.key)
+                .encrypt(&ron::to_string(&identity).unwrap())
+                .unwrap(),
+        };
+        
+        // The sealed form must not expose the password hash
+        let sealed_data = ron::to_string(&sealed).unwrap();
+        assert!(!sealed_data.contains(&identity.password_hash));
+        assert!(ron::from_str::<Identity>(&sealed_data).is_err());
+        
+        let unsealed = Identity::unseal(&sealed, "1234").unwrap();
+        assert!(unsealed.is_sealed());
+        assert!(unsealed.verify_password(password).unwrap());
+        assert_eq!(unsealed.salt, identity.salt);
+        
+        assert!(Identity::unseal(&sealed, "9999").is_err());
+    }
+    
+    #[test]
+    fn test_password_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("password");
+        fs::write(&path, "test_password_123\n").unwrap();
+        
+        let options = AuthOptions {
+            password_file: Some(path),
+            ..AuthOptions::default()
+        };
+        assert_eq!(resolve_master_password(&options).unwrap().as_str(), "test_password_123");
+        
+        let missing = AuthOptions {
+            password_file: Some(temp_dir.path().join("missing")),
+            ..AuthOptions::default()
+        };
+        assert!(resolve_master_password(&missing).is_err());
+    }
+    
+    #[test]
+    fn test_keyfile_unlock() {
+        let password = "test_password_123";
+        let keyfile = [3u8; 64];
+        
+        let mut identity = Identity::new(password).unwrap();
+        let password_key = identity.unlock(Some(password), None).unwrap();
+        identity.require_keyfile(&keyfile).unwrap();
+        
+        let combined = identity.unlock(Some(password), Some(&keyfile)).unwrap();
+        assert_ne!(combined.as_bytes(), password_key.as_bytes());
+        assert!(identity.unlock(Some(password), None).is_err());
+        assert!(identity.unlock(None, Some(&keyfile)).is_err());
+        assert!(identity.unlock(Some(password), Some(&[4u8; 64])).is_err());
+        assert!(identity.unlock(Some("wrong_password"), Some(&keyfile)).is_err());
+        
+        let keyfile_only = Identity::new_keyfile_only(&keyfile).unwrap();
+        let key1 = keyfile_only.unlock(None, Some(&keyfile)).unwrap();
+        let key2 = keyfile_only.unlock(None, Some(&keyfile)).unwrap();
+        assert_eq!(key1.as_bytes(), key2.as_bytes());
+        assert!(keyfile_only.unlock(None, None).is_err());
+    }
+    
+    #[test]
+    fn test_totp_secret_encrypted_in_identity() {
+        let password = "test_password_123";
+        let mut identity = Identity::new(password).unwrap();
+        let key = identity.derive_key(password).unwrap();
+        
+        // Without 2FA every code is accepted, since there is nothing to check
+        assert!(identity.verify_totp(&key, "000000").unwrap());
+        
+        let secret = identity.enable_totp(&key).unwrap();
+        let identity_data = ron::to_string(&identity).unwrap();
+        assert!(!identity_data.contains(&secret));
+        
+        let other_key = SecretKey::new([9u8; 32]);
+        assert!(identity.verify_totp(&other_key, "000000").is_err());
+    }
+    
+    #[test]
+    fn test_change_password() {
+        let mut identity = Identity::new("old_password_123").unwrap();
+        let old_key = identity.derive_key("old_password_123").unwrap();
+        identity.enable_totp(&old_key).unwrap();
+        let old_salt = identity.salt.clone();
+        
+        let new_key = identity.change_password(&old_key, "new_password_456", None).unwrap();
+        
+        assert_ne!(identity.salt, old_salt);
+        assert!(identity.verify_password("new_password_456").unwrap());
+        assert!(!identity.verify_password("old_password_123").unwrap());
+        assert_eq!(identity.unlock(Some("new_password_456"), None).unwrap().as_bytes(), new_key.as_bytes());
+        
+        // The TOTP secret is re-encrypted under the new key
+        assert!(identity.verify_totp(&new_key, "000000").is_ok());
+        assert!(identity.verify_totp(&old_key, "000000").is_err());
+        
+        let mut keyfile_only = Identity::new_keyfile_only(&[1u8; 32]).unwrap();
+        assert!(keyfile_only.change_password(&new_key, "new_password_456", None).is_err());
     }
 }
\ No newline at end of file
@@ -7,6 +7,12 @@ use std::collections::HashMap;
 pub struct Lease {
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    /// Push `expires_at` forward by the original duration on every read
+    #[serde(default)]
+    pub sliding: bool,
+    /// Original lease length in seconds, kept so sliding renewals know the window
+    #[serde(default)]
+    pub window_seconds: Option<i64>,
 }
 
 impl Lease {
@@ -15,13 +21,59 @@ impl Lease {
         Self {
             expires_at: now + duration,
             created_at: now,
+            sliding: false,
+            window_seconds: None,
         }
     }
     
+    pub fn new_sliding(duration: Duration) -> Self {
+        Self {
+            sliding: true,
+            window_seconds: Some(duration.num_seconds()),
+            ..Self::new(duration)
+        }
+    }
+    
+    /// Restart a sliding lease's window from now. Returns false for fixed or
+    /// already expired leases, which are left untouched.
+    pub fn renew(&mut self) -> bool {
+        if !self.sliding || self.is_expired() {
+            return false;
+        }
+        
+        self.expires_at = Utc::now() + self.duration();
+        true
+    }
+    
+    /// Push the expiry back by `extra`, keeping `created_at`. A sliding
+    /// lease's window grows by the same amount, so later reads keep it.
+    /// Fails, leaving the lease as it was, if the new expiry is out of range.
+    pub fn extend(&mut self, extra: Duration) -> Result<()> {
+        let too_far = || anyhow!("Extending by {} days would put the expiry out of range", extra.num_days());
+        let expires_at = self.expires_at.checked_add_signed(extra).ok_or_else(too_far)?;
+        let window_seconds = match self.window_seconds {
+            Some(seconds) => Some(seconds.checked_add(extra.num_seconds()).ok_or_else(too_far)?),
+            None => None,
+        };
+        
+        self.expires_at = expires_at;
+        self.window_seconds = window_seconds;
+        Ok(())
+    }
+    
     pub fn is_expired(&self) -> bool {
         Utc::now() > self.expires_at
     }
     
+    /// Total length of the lease, from creation to expiry. For sliding leases
+    /// this is the window each read renews.
+    pub fn duration(&self) -> Duration {
+        match self.window_seconds {
+            Some(seconds) => Duration::seconds(seconds),
+            None => self.expires_at - self.created_at,
+        }
+    }
+    
     pub fn time_remaining(&self) -> Option<Duration> {
         let now = Utc::now();
         if now < self.expires_at {
@@ -35,12 +87,16 @@ impl Lease {
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct LeaseManager {
     leases: HashMap<String, Lease>,
+    /// Remaining successful reads before a secret is destroyed
+    #[serde(default)]
+    read_limits: HashMap<String, u64>,
 }
 
 impl LeaseManager {
     pub fn new() -> Self {
         Self {
             leases: HashMap::new(),
+            read_limits: HashMap::new(),
         }
     }
     
@@ -49,14 +105,101 @@ impl LeaseManager {
         self.leases.insert(secret_name, lease);
     }
     
+    /// Give every named secret the same lease, all starting now
+    pub fn add_leases(&mut self, names: &[String], duration: Duration) {
+        for name in names {
+            self.add_lease(name.clone(), duration);
+        }
+    }
+    
+    pub fn add_sliding_lease(&mut self, secret_name: String, duration: Duration) {
+        self.leases.insert(secret_name, Lease::new_sliding(duration));
+    }
+    
     pub fn get_lease(&self, secret_name: &str) -> Option<&Lease> {
         self.leases.get(secret_name)
     }
     
+    /// Renew a sliding lease after the secret was used. Returns true if the lease changed.
+    pub fn renew_lease(&mut self, secret_name: &str) -> bool {
+        self.leases
+            .get_mut(secret_name)
+            .is_some_and(|lease| lease.renew())
+    }
+    
+    /// Extend a secret's unexpired lease by `extra`
+    pub fn extend_lease(&mut self, secret_name: &str, extra: Duration) -> Result<()> {
+        match self.leases.get_mut(secret_name) {
+            Some(lease) if !lease.is_expired() => lease.extend(extra),
+            Some(_) => Err(anyhow!("The lease on '{}' has already expired. Set a new one with `sentinel expire`.", secret_name)),
+            None => Err(anyhow!("'{}' has no expiry to extend. Set one with `sentinel expire`.", secret_name)),
+        }
+    }
+    
+    /// Attach an existing lease as-is, e.g. one carried over by `merge`
+    pub fn set_lease(&mut self, secret_name: String, lease: Lease) {
+        self.leases.insert(secret_name, lease);
+    }
+    
     pub fn remove_lease(&mut self, secret_name: &str) -> Option<Lease> {
         self.leases.remove(secret_name)
     }
     
+    pub fn set_read_limit(&mut self, secret_name: String, max_reads: u64) {
+        self.read_limits.insert(secret_name, max_reads);
+    }
+    
+    pub fn get_read_limit(&self, secret_name: &str) -> Option<u64> {
+        self.read_limits.get(secret_name).copied()
+    }
+    
+    pub fn remove_read_limit(&mut self, secret_name: &str) -> Option<u64> {
+        self.read_limits.remove(secret_name)
+    }
+    
+    /// Record one read against a secret's read limit, returning the reads left.
+    /// Returns `None` when the secret has no read limit.
+    pub fn consume_read(&mut self, secret_name: &str) -> Option<u64> {
+        let remaining = self.read_limits.get_mut(secret_name)?;
+        *remaining = remaining.saturating_sub(1);
+        let left = *remaining;
+        
+        if left == 0 {
+            self.read_limits.remove(secret_name);
+        }
+        
+        Some(left)
+    }
+    
+    /// Move a secret's lease and read limit, if it has them, to a new name
+    pub fn rename(&mut self, old: &str, new: &str) {
+        if let Some(lease) = self.leases.remove(old) {
+            self.leases.insert(new.to_string(), lease);
+        }
+        if let Some(reads) = self.read_limits.remove(old) {
+            self.read_limits.insert(new.to_string(), reads);
+        }
+    }
+    
+    /// Keep only the leases and read limits of secrets `keep` accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.leases.retain(|name, _| keep(name));
+        self.read_limits.retain(|name, _| keep(name));
+    }
+    
+    /// Every secret name with a lease or read limit attached
+    pub fn tracked_secrets(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .leases
+            .keys()
+            .chain(self.read_limits.keys())
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    
     pub fn get_expired_secrets(&self) -> Vec<String> {
         self.leases
             .iter()
@@ -87,6 +230,19 @@ impl LeaseManager {
             .count()
     }
     
+    /// Secrets whose lease is still running but ends within `window`, sorted
+    /// by name
+    pub fn expiring_within(&self, window: Duration) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .leases
+            .iter()
+            .filter(|(_, lease)| lease.time_remaining().is_some_and(|remaining| remaining <= window))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+    
     pub fn list_active_leases(&self) -> Vec<(String, &Lease)> {
         self.leases
             .iter()
@@ -144,6 +300,7 @@ mod tests {
         
         assert!(!lease.is_expired());
         assert!(lease.time_remaining().is_some());
+        assert_eq!(lease.duration(), duration);
     }
     
     #[test]
@@ -167,6 +324,28 @@ mod tests {
         assert_eq!(manager.expired_leases_count(), 0);
     }
     
+    #[test]
+    fn test_add_leases() {
+        let mut manager = LeaseManager::new();
+        let names = vec!["temp-1".to_string(), "temp-2".to_string()];
+        
+        manager.add_leases(&names, Duration::hours(1));
+        
+        assert_eq!(manager.active_leases_count(), 2);
+        assert_eq!(manager.get_lease("temp-2").unwrap().duration(), Duration::hours(1));
+    }
+    
+    #[test]
+    fn test_expiring_within() {
+        let mut manager = LeaseManager::new();
+        manager.add_lease("soon".to_string(), Duration::hours(2));
+        manager.add_lease("later".to_string(), Duration::days(3));
+        manager.add_lease("gone".to_string(), Duration::milliseconds(-1));
+        
+        assert_eq!(manager.expiring_within(Duration::hours(24)), vec!["soon"]);
+        assert!(manager.expiring_within(Duration::hours(1)).is_empty());
+    }
+    
     #[test]
     fn test_parse_duration() {
         assert_eq!(parse_duration("10s").unwrap(), Duration::seconds(10));
@@ -197,4 +376,71 @@ mod tests {
         assert!(manager.get_lease("expired").is_none());
         assert!(manager.get_lease("active").is_some());
     }
+    
+    #[test]
+    fn test_read_limit_consumption() {
+        let mut manager = LeaseManager::new();
+        manager.set_read_limit("one_time".to_string(), 2);
+        
+        assert_eq!(manager.get_read_limit("one_time"), Some(2));
+        assert_eq!(manager.consume_read("one_time"), Some(1));
+        assert_eq!(manager.consume_read("one_time"), Some(0));
+        assert!(manager.get_read_limit("one_time").is_none());
+        
+        // Secrets without a read limit are unaffected
+        assert!(manager.consume_read("unlimited").is_none());
+    }
+    
+    #[test]
+    fn test_sliding_lease_renewal() {
+        let mut lease = Lease::new_sliding(Duration::minutes(10));
+        assert_eq!(lease.duration(), Duration::minutes(10));
+        
+        lease.expires_at = Utc::now() + Duration::minutes(1);
+        assert!(lease.renew());
+        assert!(lease.time_remaining().unwrap() > Duration::minutes(9));
+        
+        let mut fixed = Lease::new(Duration::minutes(10));
+        assert!(!fixed.renew());
+        
+        let mut lapsed = Lease::new_sliding(Duration::minutes(10));
+        lapsed.expires_at = Utc::now() - Duration::seconds(1);
+        assert!(!lapsed.renew());
+        assert!(lapsed.is_expired());
+    }
+    
+    #[test]
+    fn test_extend_lease() {
+        let mut manager = LeaseManager::new();
+        manager.add_lease("api_key".to_string(), Duration::minutes(10));
+        let created_at = manager.get_lease("api_key").unwrap().created_at;
+        
+        manager.extend_lease("api_key", Duration::minutes(5)).unwrap();
+        let lease = manager.get_lease("api_key").unwrap();
+        assert_eq!(lease.created_at, created_at);
+        assert_eq!(lease.expires_at - lease.created_at, Duration::minutes(15));
+        assert!(lease.time_remaining().unwrap() > Duration::minutes(14));
+        
+        manager.add_sliding_lease("session".to_string(), Duration::minutes(10));
+        manager.extend_lease("session", Duration::minutes(5)).unwrap();
+        assert_eq!(manager.get_lease("session").unwrap().duration(), Duration::minutes(15));
+        
+        assert!(manager.extend_lease("no_lease", Duration::minutes(5)).is_err());
+        manager.add_lease("lapsed".to_string(), Duration::seconds(-1));
+        assert!(manager.extend_lease("lapsed", Duration::minutes(5)).is_err());
+        
+        let expires_at = manager.get_lease("api_key").unwrap().expires_at;
+        assert!(manager.extend_lease("api_key", Duration::MAX).is_err());
+        assert_eq!(manager.get_lease("api_key").unwrap().expires_at, expires_at);
+    }
+    
+    #[test]
+    fn test_tracked_secrets() {
+        let mut manager = LeaseManager::new();
+        manager.add_lease("both".to_string(), Duration::minutes(10));
+        manager.set_read_limit("both".to_string(), 1);
+        manager.set_read_limit("reads_only".to_string(), 3);
+        
+        assert_eq!(manager.tracked_secrets(), vec!["both", "reads_only"]);
+    }
 }
\ No newline at end of file
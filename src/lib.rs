@@ -0,0 +1,34 @@
+//! SentinelVault: a lightweight zero-trust secrets manager.
+//!
+//! The CLI authenticates interactively via [`vault::Vault::load`]. Embedders
+//! that already hold the master password or derived key should use
+//! [`vault::Vault::open_with_password`] or [`vault::Vault::open_with_key`],
+//! which never prompt for the master password.
+//!
+//! Errors are `anyhow::Error`s; failures worth handling programmatically
+//! carry an [`error::VaultError`] that can be recovered with `downcast_ref`.
+
+pub mod batch;
+pub mod benchmark;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod crypto;
+pub mod doctor;
+pub mod error;
+#[cfg(feature = "fido2")]
+pub mod fido2;
+pub mod identity;
+pub mod lease;
+pub mod migrations;
+pub mod profile;
+#[cfg(feature = "qr-backup")]
+pub mod qr;
+pub mod rotation;
+pub mod scan;
+pub mod session;
+pub mod storage;
+pub mod templates;
+pub mod totp;
+pub mod utils;
+pub mod vault;
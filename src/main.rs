@@ -1,110 +1,1314 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, Zeroizing};
 
-mod cli;
-mod crypto;
-mod identity;
-mod lease;
-mod utils;
-mod vault;
+use sentinelvault::batch;
+use sentinelvault::benchmark;
+use sentinelvault::cli::{Cli, Commands, IdentityCommand};
+use sentinelvault::clipboard;
+use sentinelvault::config::{Config, VAULT_DIR_ENV};
+use sentinelvault::crypto::{generate_salt, KdfParams, SecretString, ValueEncoding};
+use sentinelvault::doctor;
+use sentinelvault::error::VaultError;
+use sentinelvault::identity::{authenticate, disable_two_factor, export_identity, import_identity, machine_id, prompt_master_password, prompt_new_backup_passphrase, read_keyfile, reauthenticate, AuthOptions, Identity};
+use sentinelvault::lease::parse_duration;
+use sentinelvault::profile;
+use sentinelvault::migrations;
+use sentinelvault::rotation::write_rotated;
+use sentinelvault::scan::{self, MIN_SCAN_LENGTH};
+use sentinelvault::session::IdleKey;
+use sentinelvault::templates::find_template;
+use sentinelvault::utils::{clear_printed_text, confirm_action, create_private_dir, credential_path, env_var_name, json_field, format_ago, format_bytes, format_duration, in_namespace, numbered_path, read_piped_value, render_table, render_tree, split_command_line, write_private_file, TerminalGuard};
+use sentinelvault::vault::{BackupData, InitOptions, LoadOptions, MergeStrategy, SecretStatus, Vault};
 
-use cli::{Cli, Commands};
-use vault::Vault;
+/// Longest `watch` sleeps between re-reading the vault, so removals and new
+/// leases made by other processes are noticed
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Commands that open the vault warn about leases ending this soon
+const EXPIRY_WARNING_HOURS: i64 = 24;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    init_logging(cli.verbose);
+    if let Some(dir) = &cli.vault_dir {
+        // Set before anything reads the config, so the flag wins over both
+        std::env::set_var(VAULT_DIR_ENV, dir);
+    }
+    let mut config = Config::load()?;
+    if cli.utc {
+        config.utc = Some(true);
+    }
+    let mut unlocker = Unlocker {
+        auth: AuthOptions {
+            keyfile: cli.keyfile.clone(),
+            password_file: cli.password_file.clone(),
+            skip_kdf_upgrade: cli.no_kdf_upgrade,
+        },
+        session: None,
+        allow_clock_skew: cli.allow_clock_skew,
+        cleanup: !cli.no_cleanup,
+        warn_expiring: !cli.quiet,
+    };
+    
+    if cli.profile {
+        profile::enable();
+    }
+    // A shell reports each of its commands instead
+    let timed = !matches!(cli.command, Commands::Shell { .. });
+    // Only `get --require-active` promises scripts distinct exit codes
+    let distinct_exit_codes = matches!(cli.command, Commands::Get { require_active: true, .. });
+    let start = Instant::now();
+    let result = run(cli.command, cli.dry_run, &config, &mut unlocker);
+    if timed {
+        profile::finish(start.elapsed());
+    }
+    if let (Err(err), true) = (&result, distinct_exit_codes) {
+        let code = err.downcast_ref::<VaultError>().map_or(1, VaultError::exit_code);
+        if code != 1 {
+            eprintln!("Error: {:?}", err);
+            std::process::exit(code);
+        }
+    }
+    result
+}
+
+/// Send `log` output to stderr at the level picked by -v. Without it nothing
+/// is logged, unless `RUST_LOG` asks for it.
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Info,
+        _ => log::LevelFilter::Debug,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+}
+
+/// Where commands get their vault from: a fresh authentication per command,
+/// or the key held by an interactive shell
+struct Unlocker {
+    auth: AuthOptions,
+    session: Option<IdleKey>,
+    allow_clock_skew: bool,
+    /// False when --no-cleanup was given
+    cleanup: bool,
+    /// Cleared by --quiet, and after the first warning so a shell only warns once
+    warn_expiring: bool,
+}
+
+impl Unlocker {
+    fn open(&mut self) -> Result<Vault> {
+        self.open_with(LoadOptions { cleanup: self.cleanup })
+    }
+    
+    /// Open without purging expired secrets, for maintenance commands
+    fn open_without_cleanup(&mut self) -> Result<Vault> {
+        self.open_with(LoadOptions { cleanup: false })
+    }
+    
+    fn open_with(&mut self, options: LoadOptions) -> Result<Vault> {
+        let vault = match &mut self.session {
+            Some(session) => {
+                let key = profile::time("authentication", || session.get_or_authenticate(&self.auth))?;
+                Vault::open_with_key_using(key, &options)?
+            }
+            None => Vault::load_with(&self.auth, &options)?,
+        };
+        let vault = self.check_clock(vault)?;
+        self.warn_expiring(&vault);
+        Ok(vault)
+    }
     
-    match cli.command {
-        Commands::Init => {
+    /// Drop the shell's key once the credentials behind it change, so the
+    /// next command unlocks with the new ones
+    fn forget_key(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.lock();
+        }
+    }
+    
+    fn warn_expiring(&mut self, vault: &Vault) {
+        if !self.warn_expiring {
+            return;
+        }
+        self.warn_expiring = false;
+        
+        let expiring = vault.expiring_within(chrono::Duration::hours(EXPIRY_WARNING_HOURS));
+        match expiring.len() {
+            0 => {}
+            1 => eprintln!("⚠ 1 secret expires in the next {}h: {}", EXPIRY_WARNING_HOURS, expiring[0]),
+            n => eprintln!("⚠ {} secrets expire in the next {}h: {}", n, EXPIRY_WARNING_HOURS, expiring.join(", ")),
+        }
+    }
+    
+    /// Refuse a vault whose last save is in the future unless --allow-clock-skew was given
+    fn check_clock(&self, vault: Vault) -> Result<Vault> {
+        if let Some(skew) = vault.clock_skew() {
+            if !self.allow_clock_skew {
+                return Err(VaultError::ClockSkew { seconds: skew.num_seconds() }.into());
+            }
+            eprintln!("Warning: system clock is {}s behind the vault's last save", skew.num_seconds());
+        }
+        Ok(vault)
+    }
+}
+
+fn run(command: Commands, dry_run: bool, config: &Config, unlocker: &mut Unlocker) -> Result<()> {
+    let auth = unlocker.auth.clone();
+    if dry_run && !matches!(command, Commands::Prune | Commands::Remove { .. } | Commands::ChangePassword) {
+        return Err(anyhow!("--dry-run is only supported by prune, remove and change-password"));
+    }
+    
+    match command {
+        Commands::Init { require_pin, keyfile_only, enable_2fa, max_secret_size, max_secrets, from_backup, bind_machine, fido2, format, import_env, hash_cost, key_cost } => {
+            let format = format.parse()?;
+            let hash_cost: Option<KdfParams> = hash_cost.map(|costs| costs.parse()).transpose()?;
+            let key_cost: Option<KdfParams> = key_cost.map(|costs| costs.parse()).transpose()?;
             println!("Initializing SentinelVault...");
-            Vault::init()?;
+            let options = InitOptions {
+                require_pin,
+                keyfile: auth.keyfile.clone(),
+                keyfile_only,
+                enable_2fa,
+                max_secret_size,
+                max_secrets,
+                bind_machine,
+                fido2,
+                format,
+                import_env,
+                hash_cost,
+                key_cost,
+            };
+            let summary = match from_backup {
+                Some(path) => Vault::init_from_backup(&BackupData::load(&path)?, &options)?,
+                None => Vault::init(&options)?,
+            };
             println!("Vault initialized successfully!");
+            if !summary.imported.is_empty() {
+                println!("Imported {} secret(s) from the environment: {}", summary.imported.len(), summary.imported.join(", "));
+                eprintln!("Warning: the values are still set in this shell's environment; unset them once you no longer need them there");
+            } else if options.import_env.is_some() {
+                eprintln!("No environment variables matched --import-env; the vault is empty");
+            }
+            
+            if let Some(uri) = summary.totp_uri {
+                println!("Two-factor authentication enabled. Add this to your authenticator app:");
+                println!("{}", uri);
+                #[cfg(feature = "qr-backup")]
+                {
+                    use sentinelvault::qr::{self, QrOutput};
+                    let code = qr::render(&uri, QrOutput::Terminal)?;
+                    println!("{}", String::from_utf8_lossy(&code));
+                }
+            }
         }
-        Commands::Add { name, value } => {
-            let mut vault = Vault::load()?;
+        Commands::Add { name, value, stdin, protected, if_absent, replace, decode, tags, description } => {
+            let piped = value.is_none() && (stdin || !std::io::stdin().is_terminal());
+            if piped && unlocker.session.is_some() {
+                return Err(anyhow!("The shell reads its commands from stdin. Pass --value instead."));
+            }
+            let decode: Option<ValueEncoding> = decode.map(|encoding| encoding.parse()).transpose()?;
+            let mut vault = unlocker.open()?;
+            let exists = vault.secret_status(&name)? != SecretStatus::Missing;
+            if if_absent && exists {
+                eprintln!("Secret '{}' already exists; left unchanged", name);
+                return Ok(());
+            }
+            if replace && !exists {
+                return Err(VaultError::NotFound(name).into());
+            }
             let secret_value = match value {
-                Some(v) => v,
+                Some(v) => Zeroizing::new(v),
+                None if piped => read_piped_value(std::io::stdin())?,
                 None => {
                     use inquire::{Password, PasswordDisplayMode};
-                    Password::new("Enter secret value:")
+                    Zeroizing::new(Password::new("Enter secret value:")
                         .with_display_mode(PasswordDisplayMode::Masked)
-                        .prompt()?
+                        .prompt()?)
+                }
+            };
+            let secret_value = match decode {
+                Some(encoding) => {
+                    let bytes = Zeroizing::new(encoding.decode(secret_value.trim())?);
+                    Zeroizing::new(String::from_utf8(bytes.to_vec())
+                        .map_err(|_| anyhow!("Decoded value is not valid UTF-8. Secrets are stored as text, so store binary values encoded, without --decode"))?)
                 }
+                None => secret_value,
             };
-            vault.add_secret(&name, &secret_value)?;
+            vault.add_secret_with_meta(&name, &secret_value, &tags, description.as_deref())?;
+            if protected {
+                vault.set_protected(&name, true)?;
+            }
             println!("Secret '{}' added successfully!", name);
         }
-        Commands::Get { name } => {
-            let vault = Vault::load()?;
-            match vault.get_secret(&name)? {
-                Some(value) => println!("{}", value),
+        Commands::AddTemplate { name, template, protected } => {
+            let template = find_template(&template)?;
+            let mut vault = unlocker.open()?;
+            let mut values = Vec::new();
+            for field in template.fields {
+                use inquire::{Password, PasswordDisplayMode, Text};
+                let prompt = format!("{}:", field.name);
+                let value = if field.secret {
+                    Password::new(&prompt)
+                        .with_display_mode(PasswordDisplayMode::Masked)
+                        .without_confirmation()
+                        .prompt()?
+                } else {
+                    let mut text = Text::new(&prompt);
+                    if let Some(default) = field.default {
+                        text = text.with_default(default);
+                    }
+                    text.prompt()?
+                };
+                values.push(value);
+            }
+            let json = Zeroizing::new(template.to_json(&values)?);
+            values.zeroize();
+            vault.add_secret(&name, &json)?;
+            if protected {
+                vault.set_protected(&name, true)?;
+            }
+            println!("Secret '{}' added from the '{}' template. Read fields with `get {} --field <field>`.", name, template.name, name);
+        }
+        Commands::Alias { alias, target } => {
+            let mut vault = unlocker.open()?;
+            vault.add_alias(&alias, &target)?;
+            println!("'{}' now refers to '{}'", alias, target);
+        }
+        Commands::Rename { old, new } => {
+            let mut vault = unlocker.open()?;
+            vault.rename_secret(&old, &new)?;
+            println!("Renamed '{}' to '{}'", old, new);
+        }
+        Commands::Get { name, field, clear, notify, no_newline, env_fallback, encode, require_active, paranoid } => {
+            let encode: Option<ValueEncoding> = encode.map(|encoding| encoding.parse()).transpose()?;
+            let mut vault = if require_active {
+                // Keep expired secrets around long enough to say when they expired
+                let vault = unlocker.open_without_cleanup()?;
+                vault.ensure_active(&name)?;
+                vault
+            } else {
+                unlocker.open()?
+            };
+            if vault.is_protected(&name)? {
+                eprintln!("'{}' is protected. Re-enter your credentials to reveal it.", name);
+                reauthenticate(&auth, &format!("Reveal protected secret '{}'?", name))?;
+            }
+            let mut secret = match &field {
+                Some(field) => vault.get_secret_field(&name, field)?,
+                None => vault.get_secret(&name)?,
+            };
+            if secret.is_some() {
+                log::info!("'{}' read from the vault", name);
+            } else if env_fallback {
+                let var = env_var_name(&name);
+                if let Ok(value) = std::env::var(&var) {
+                    log::info!("'{}' not in the vault; read from ${}", name, var);
+                    let value = Zeroizing::new(value);
+                    secret = Some(SecretString::new(match &field {
+                        Some(field) => json_field(&value, field)?,
+                        None => value.to_string(),
+                    }));
+                }
+            }
+            if let (Some(encoding), Some(value)) = (encode, &secret) {
+                secret = Some(SecretString::new(encoding.encode(value.expose().as_bytes())));
+            }
+            match secret {
+                Some(value) => {
+                    let _guard = if paranoid && std::io::stdout().is_terminal() {
+                        Some(TerminalGuard::new(std::io::stdout())?)
+                    } else {
+                        None
+                    };
+                    if no_newline {
+                        let mut stdout = std::io::stdout();
+                        stdout.write_all(value.expose().as_bytes())?;
+                        stdout.flush()?;
+                    } else {
+                        println!("{}", value.expose());
+                    }
+                    if notify {
+                        notify_access(&name);
+                    }
+                    if let Some(seconds) = clear {
+                        let seconds = seconds.unwrap_or_else(|| config.clear_seconds());
+                        clear_printed_text(value.expose(), Duration::from_secs(seconds))?;
+                    }
+                }
+                None if require_active => return Err(VaultError::NotFound(name).into()),
                 None => println!("Secret '{}' not found", name),
             }
         }
-        Commands::List => {
-            let vault = Vault::load()?;
-            let secrets = vault.list_secrets()?;
-            if secrets.is_empty() {
+        Commands::Clip { name, clear_after, clipboard_backend } => {
+            let backend = match clipboard_backend {
+                Some(backend) => backend.parse()?,
+                None => clipboard::Backend::detect()?,
+            };
+            let clear_after = parse_duration(&clear_after)?;
+            let wait = clear_after.to_std().map_err(|_| anyhow!("--clear-after must be positive"))?;
+            
+            let mut vault = unlocker.open()?;
+            if vault.is_protected(&name)? {
+                eprintln!("'{}' is protected. Re-enter your credentials to reveal it.", name);
+                reauthenticate(&auth, &format!("Reveal protected secret '{}'?", name))?;
+            }
+            let value = vault.get_secret(&name)?.ok_or_else(|| VaultError::NotFound(name.clone()))?;
+            log::info!("'{}' copied to the clipboard", name);
+            
+            println!("Copied '{}' to the clipboard. Clearing it in {}; leave this running until then...", name, format_duration(clear_after));
+            if clipboard::copy_then_clear(backend, value.expose(), wait)? {
+                println!("Clipboard cleared");
+            } else {
+                println!("Clipboard changed since; left as it is");
+            }
+        }
+        Commands::Has { name } => {
+            let present = {
+                let vault = unlocker.open()?;
+                matches!(vault.secret_status(&name)?, SecretStatus::Permanent | SecretStatus::Expires(_))
+            };
+            if unlocker.session.is_some() {
+                // A shell has no exit status to report through
+                println!("{}", if present { "yes" } else { "no" });
+            } else if !present {
+                std::process::exit(1);
+            }
+        }
+        Commands::List { namespace, tag, values: true, i_understand_plaintext, .. } => {
+            if !i_understand_plaintext {
+                if !std::io::stdout().is_terminal() {
+                    return Err(anyhow!("Refusing to print secret values to a non-terminal. Use --i-understand-plaintext to override."));
+                }
+                if !confirm_action("Print every secret value in plaintext?", false)? {
+                    println!("Cancelled");
+                    return Ok(());
+                }
+            }
+            
+            let vault = unlocker.open()?;
+            let mut values = vault.list_values()?;
+            values.retain(|(name, _)| listed(&vault, name, namespace.as_deref(), tag.as_deref()));
+            if values.iter().any(|(name, _)| vault.is_protected(name).unwrap_or(false)) {
+                reauthenticate(&auth, "Reveal protected secrets?")?;
+            }
+            
+            if values.is_empty() {
                 println!("No secrets stored in vault");
+            }
+            for (name, value) in &values {
+                println!("{} = {}", name, value.expose());
+            }
+        }
+        Commands::List { namespace, tag, older_than: Some(older_than), .. } => {
+            let unused_for = parse_duration(&older_than)?;
+            let vault = unlocker.open()?;
+            let mut stale = vault.stale_secrets(unused_for);
+            stale.retain(|(name, _)| listed(&vault, name, namespace.as_deref(), tag.as_deref()));
+            
+            if stale.is_empty() {
+                println!("No secrets unused for {}", older_than);
+            } else {
+                let now = chrono::Utc::now();
+                println!("Secrets unused for {} (least recently used first):", older_than);
+                for (name, last_used) in stale {
+                    println!("  • {} (last used {})", name, format_ago(now - last_used));
+                }
+            }
+        }
+        Commands::List { namespace, tag, expired: true, .. } => {
+            // Opening normally would purge exactly the secrets asked about
+            let vault = unlocker.open_without_cleanup()?;
+            let mut expired = vault.list_expired_secrets();
+            expired.retain(|(name, _)| listed(&vault, name, namespace.as_deref(), tag.as_deref()));
+            
+            if expired.is_empty() {
+                println!("No expired secrets");
+            } else {
+                let now = chrono::Utc::now();
+                println!("Expired secrets (purged the next time the vault is opened normally):");
+                for (name, expired_at) in expired {
+                    println!("  • {} (expired {}, {})", name, config.format_time(expired_at), format_ago(now - expired_at));
+                }
+                println!("Renew one with `sentinel expire <name> --after <duration> --no-cleanup` to keep it.");
+            }
+        }
+        Commands::List { namespace, tag, tree, format, .. } => {
+            let vault = unlocker.open()?;
+            let mut secrets = vault.list_secrets()?;
+            // Only non-empty with --no-cleanup
+            let mut expired = vault.list_expired_secrets();
+            let mut aliases = vault.list_aliases();
+            let (namespace, tag) = (namespace.as_deref(), tag.as_deref());
+            secrets.retain(|(name, _)| listed(&vault, name, namespace, tag));
+            expired.retain(|(name, _)| listed(&vault, name, namespace, tag));
+            aliases.retain(|(alias, _)| listed(&vault, alias, namespace, tag));
+            
+            if secrets.is_empty() && expired.is_empty() && aliases.is_empty() {
+                println!("No secrets stored in vault");
+            } else if tree {
+                let names: Vec<String> = secrets.into_iter().map(|(name, _)| name).collect();
+                for line in render_tree(&names) {
+                    println!("  {}", line);
+                }
+            } else if format == "table" {
+                let last_accessed = |name: &str| match vault.data().secrets.get(name).and_then(|entry| entry.last_accessed) {
+                    Some(at) => config.format_time(at),
+                    None => "never".to_string(),
+                };
+                let tags = |name: &str| match vault.data().secrets.get(name) {
+                    Some(entry) if !entry.tags.is_empty() => entry.tags.join(", "),
+                    _ => "-".to_string(),
+                };
+                let mut rows = Vec::new();
+                for (name, expires_at) in secrets {
+                    let expires = expires_at.map_or_else(|| "-".to_string(), |at| config.format_time(at));
+                    let (accessed, tags) = (last_accessed(&name), tags(&name));
+                    rows.push(vec![name, expires, accessed, tags]);
+                }
+                for (name, expired_at) in expired {
+                    let (accessed, tags) = (last_accessed(&name), tags(&name));
+                    rows.push(vec![name, format!("EXPIRED {}", config.format_time(expired_at)), accessed, tags]);
+                }
+                for (alias, target) in aliases {
+                    rows.push(vec![format!("{} → {}", alias, target), "-".to_string(), "-".to_string(), "-".to_string()]);
+                }
+                for line in render_table(&["Name", "Expires", "Last Accessed", "Tags"], &rows, &[false, true, true, false]) {
+                    println!("{}", line);
+                }
             } else {
                 println!("Stored secrets:");
                 for (name, expires_at) in secrets {
-                    match expires_at {
-                        Some(exp) => println!("  • {} (expires: {})", name, exp.format("%Y-%m-%d %H:%M:%S")),
-                        None => println!("  • {} (no expiration)", name),
+                    let remaining = vault.data().lease_manager.get_lease(&name).and_then(|lease| lease.time_remaining());
+                    let description = match &vault.data().secrets[&name].description {
+                        Some(description) => format!(" - {}", description),
+                        None => String::new(),
+                    };
+                    match (expires_at, remaining) {
+                        (Some(exp), Some(remaining)) => println!(
+                            "  • {} (expires: {}, in {}){}",
+                            name,
+                            config.format_time(exp),
+                            format_duration(remaining),
+                            description
+                        ),
+                        (Some(exp), None) => println!("  • {} (expires: {}){}", name, config.format_time(exp), description),
+                        (None, _) => println!("  • {} (no expiration){}", name, description),
+                    }
+                }
+                for (name, expired_at) in expired {
+                    println!("  • {} (EXPIRED: {})", name, config.format_time(expired_at));
+                }
+                for (alias, target) in aliases {
+                    match vault.secret_status(&alias)? {
+                        SecretStatus::Missing => println!("  • {} (alias of '{}', which no longer exists)", alias, target),
+                        _ => println!("  • {} (alias of '{}')", alias, target),
                     }
                 }
             }
         }
-        Commands::Expire { name, after } => {
-            let mut vault = Vault::load()?;
-            vault.set_expiry(&name, &after)?;
-            println!("Set expiry for '{}' to {}", name, after);
+        Commands::Search { pattern, glob } => {
+            let vault = unlocker.open()?;
+            let found = vault.search_secrets(&pattern, glob);
+            if found.is_empty() {
+                println!("No secrets match '{}'", pattern);
+            }
+            for name in found {
+                println!("  • {}", name);
+            }
+        }
+        Commands::Last { limit } => {
+            let vault = unlocker.open()?;
+            let recent = vault.recent_secrets(limit);
+            if recent.is_empty() {
+                println!("No secrets have been read yet");
+            }
+            let now = chrono::Utc::now();
+            for (name, accessed) in recent {
+                println!("  • {} ({})", name, format_ago(now - accessed));
+            }
         }
-        Commands::Remove { name } => {
-            let mut vault = Vault::load()?;
-            if vault.remove_secret(&name)? {
-                println!("Secret '{}' removed successfully!", name);
+        Commands::Watch { name, on_expire, timeout } => {
+            let mut vault = unlocker.open_without_cleanup()?;
+            if vault.secret_status(&name)? == SecretStatus::Missing {
+                return Err(VaultError::NotFound(name).into());
+            }
+            
+            // A timeout too far out to represent never comes, same as none
+            let deadline = timeout.and_then(|minutes| Instant::now().checked_add(Duration::from_secs(minutes.saturating_mul(60))));
+            loop {
+                vault.reload()?;
+                let wait = match vault.secret_status(&name)? {
+                    SecretStatus::Missing => {
+                        println!("Secret '{}' was removed", name);
+                        break;
+                    }
+                    SecretStatus::Expired => {
+                        println!("Secret '{}' has expired", name);
+                        break;
+                    }
+                    SecretStatus::Expires(remaining) => remaining.to_std().unwrap_or_default().min(WATCH_POLL_INTERVAL),
+                    SecretStatus::Permanent => WATCH_POLL_INTERVAL,
+                };
+                
+                let wait = match deadline {
+                    Some(deadline) if Instant::now() >= deadline => {
+                        return Err(anyhow!("Timed out waiting for '{}' to expire", name));
+                    }
+                    Some(deadline) => wait.min(deadline - Instant::now()),
+                    None => wait,
+                };
+                std::thread::sleep(wait);
+            }
+            
+            if let Some(command) = on_expire {
+                run_hook(&command, &name)?;
+            }
+        }
+        Commands::Expire { name, glob: true, after: Some(after), sliding, .. } => {
+            let mut vault = unlocker.open()?;
+            let matched = vault.match_secrets(std::slice::from_ref(&name), true)?;
+            if matched.is_empty() {
+                println!("No matching secrets found");
+                return Ok(());
+            }
+            
+            vault.set_expiries(&matched, &after, sliding)?;
+            println!("Set expiry for {} secret(s) to {}: {}", matched.len(), after, matched.join(", "));
+        }
+        Commands::Expire { name, after, reads, sliding, clear, .. } => {
+            let mut vault = unlocker.open()?;
+            if clear {
+                if vault.clear_expiry(&name)? {
+                    println!("Secret '{}' no longer expires", name);
+                } else {
+                    println!("Secret '{}' had no expiry", name);
+                }
+            }
+            if let Some(after) = after {
+                vault.set_expiry(&name, &after, sliding)?;
+                if sliding {
+                    println!("Set sliding expiry for '{}' to {} after last use", name, after);
+                } else {
+                    println!("Set expiry for '{}' to {}", name, after);
+                }
+            }
+            if let Some(reads) = reads {
+                vault.set_read_limit(&name, reads)?;
+                println!("Secret '{}' will be removed after {} read(s)", name, reads);
+            }
+        }
+        Commands::Renew { name, by } => {
+            let mut vault = unlocker.open()?;
+            let expires_at = vault.extend_expiry(&name, &by)?;
+            println!("Secret '{}' now expires {}", name, config.format_time(expires_at));
+        }
+        Commands::Remove { all: true, keep_identity, .. } => {
+            let mut vault = unlocker.open_without_cleanup()?;
+            let count = vault.data().secrets.len();
+            if dry_run {
+                println!("Would remove {} secret(s)", count);
+                if !keep_identity {
+                    println!("Would delete the vault and identity");
+                }
+                return Ok(());
+            }
+            
+            if keep_identity {
+                eprintln!("This removes all {} secret(s) and their leases. The identity is kept.", count);
+            } else {
+                eprintln!("This deletes the vault and identity, including all {} secret(s). It cannot be undone.", count);
+            }
+            // Re-entering the password is the confirmation, so --force can't skip it
+            reauthenticate(&auth, "Remove every secret?")?;
+            
+            if keep_identity {
+                let removed = vault.remove_all()?;
+                println!("Removed {} secret(s)", removed);
             } else {
-                println!("Secret '{}' not found", name);
+                let removed = vault.destroy()?;
+                println!("Removed {} secret(s) and deleted the vault. Run `sentinel init` to start again.", removed);
+            }
+        }
+        Commands::Remove { older_than: Some(older_than), force, .. } => {
+            let unused_for = parse_duration(&older_than)?;
+            let mut vault = unlocker.open()?;
+            let stale: Vec<String> = vault.stale_secrets(unused_for).into_iter().map(|(name, _)| name).collect();
+            if !dry_run && !force && !stale.is_empty() {
+                return Err(anyhow!(
+                    "{} secret(s) unused for {}. Review them with `sentinel list --older-than {}`, then pass --force to remove them.",
+                    stale.len(), older_than, older_than
+                ));
+            }
+            
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            if !dry_run {
+                vault.remove_secrets(&stale, false)?;
+            }
+            for name in &stale {
+                println!("  • {}", name);
+            }
+            println!("{} {} secret(s) unused for {}", verb, stale.len(), older_than);
+        }
+        Commands::Remove { expired: true, .. } => {
+            let mut vault = unlocker.open_without_cleanup()?;
+            let removed = vault.remove_expired(dry_run)?;
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            
+            for name in &removed {
+                println!("  • {}", name);
             }
+            println!("{} {} expired secret(s)", verb, removed.len());
         }
-        Commands::Backup { format } => {
-            let vault = Vault::load()?;
-            let backup_data = vault.create_backup()?;
+        Commands::Remove { names, glob, force, .. } => {
+            let mut vault = unlocker.open()?;
+            let single = !glob && names.len() == 1;
+            let matched = vault.match_secrets(&names, glob)?;
             
-            match format.as_str() {
-                "json" => {
-                    let json_backup = serde_json::to_string_pretty(&backup_data)?;
-                    println!("{}", json_backup);
+            if matched.is_empty() {
+                if single {
+                    println!("Secret '{}' not found", names[0]);
+                } else {
+                    println!("No matching secrets found");
                 }
-                #[cfg(feature = "qr-backup")]
-                "qr" => {
-                    use qrcode::QrCode;
-                    let backup_str = ron::to_string(&backup_data)?;
-                    let code = QrCode::new(&backup_str)?;
-                    let string = code.render::<char>()
-                        .quiet_zone(false)
-                        .module_dimensions(2, 1)
-                        .build();
-                    println!("{}", string);
+                return Ok(());
+            }
+            
+            if dry_run {
+                for name in &matched {
+                    println!("  • {}", name);
                 }
-                _ => {
-                    let ron_backup = ron::to_string(&backup_data)?;
-                    println!("{}", ron_backup);
+                println!("Would remove {} secret(s)", matched.len());
+                return Ok(());
+            }
+            
+            if glob && !force && matched.len() > 1 && matched.len() == vault.data().secrets.len() + vault.data().aliases.len() {
+                return Err(anyhow!(
+                    "Pattern matches all {} secrets in the vault. Use --force to remove them all.",
+                    matched.len()
+                ));
+            }
+            
+            let prompt = match matched.as_slice() {
+                [name] => format!("Remove secret '{}'?", name),
+                _ => format!("Remove {} secrets ({})?", matched.len(), matched.join(", ")),
+            };
+            if !confirm_action(&prompt, force)? {
+                println!("Cancelled");
+                return Ok(());
+            }
+            
+            if single {
+                vault.remove_secret(&names[0])?;
+                println!("Secret '{}' removed successfully!", names[0]);
+            } else {
+                let removed = vault.remove_secrets(&names, glob)?;
+                for name in &matched {
+                    println!("  • {}", name);
+                }
+                println!("Matched and removed {} secret(s)", removed);
+            }
+        }
+        Commands::Backup { format, output, force, dir, keep, only, glob, encrypt } => {
+            if dir.is_some() && format == "qr" {
+                return Err(anyhow!("QR backups can't be rotated. Use --output instead of --dir."));
+            }
+            let vault = unlocker.open()?;
+            let backup_data = match selected_secrets(&vault, &only, glob)? {
+                Some(names) => vault.create_backup_of(&names)?,
+                None => vault.create_backup()?,
+            };
+            
+            let parts = if encrypt {
+                let passphrase = Zeroizing::new(prompt_new_backup_passphrase()?);
+                let hash = &backup_data.identity_hash;
+                // Keyfile-only vaults have no master password to collide with
+                if !hash.is_empty() && backup_data.identity_kdf.for_hash(hash)?.verify(&passphrase, hash)? {
+                    return Err(anyhow!("Use a backup passphrase different from the master password"));
+                }
+                vec![ron::to_string(&backup_data.encrypt(&passphrase)?)?.into_bytes()]
+            } else {
+                render_backup(&backup_data, &format, output.as_deref())?
+            };
+            let total = parts.len();
+            
+            if let Some(dir) = dir {
+                let extension = match format.as_str() {
+                    _ if encrypt => "svb",
+                    "json" | "yaml" => format.as_str(),
+                    _ => "ron",
+                };
+                let (path, removed) = write_rotated(&dir, &parts[0], extension, keep)?;
+                println!("Backup written to {}", path.display());
+                for old in &removed {
+                    println!("  • removed {}", old.display());
+                }
+                return Ok(());
+            }
+            
+            match output {
+                Some(path) if total == 1 => {
+                    write_private_file(&path, &parts[0], force)?;
+                    println!("Backup written to {}", path.display());
+                }
+                Some(path) => {
+                    for (i, contents) in parts.iter().enumerate() {
+                        let part_path = numbered_path(&path, i + 1);
+                        write_private_file(&part_path, contents, force)?;
+                        println!("Backup part {} of {} written to {}", i + 1, total, part_path.display());
+                    }
+                }
+                None => {
+                    for (i, contents) in parts.iter().enumerate() {
+                        if total > 1 {
+                            println!("Part {} of {}:", i + 1, total);
+                        }
+                        println!("{}", String::from_utf8_lossy(contents));
+                    }
                 }
             }
         }
-        Commands::Stats => {
-            let vault = Vault::load()?;
-            let stats = vault.get_stats()?;
-            println!("Vault Statistics:");
-            println!("  Total secrets: {}", stats.total_secrets);
-            println!("  Active leases: {}", stats.active_leases);
-            println!("  Expired secrets: {}", stats.expired_secrets);
-            println!("  Vault size: {} bytes", stats.vault_size);
+        Commands::Import { path, format } => {
+            if format != "csv" {
+                return Err(anyhow!("Unsupported import format: {}. Use csv", format));
+            }
+            let contents = Zeroizing::new(std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?);
+            let mut vault = unlocker.open()?;
+            let count = vault.import_csv(&contents)?;
+            println!("Imported {} secret(s) from {}", count, path.display());
+        }
+        Commands::Batch { file } => {
+            let script = Zeroizing::new(match &file {
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?,
+                None => std::io::read_to_string(std::io::stdin())?,
+            });
+            let ops = batch::parse(&script)?;
+            let mut vault = unlocker.open()?;
+            vault.apply_batch(&ops)?;
+            println!("Applied {} operation(s)", ops.len());
+        }
+        Commands::Export { format, output, dir, force, i_understand_plaintext, only, glob } => {
+            match (format.as_str(), &dir) {
+                ("csv", None) | ("systemd", Some(_)) => {}
+                ("systemd", None) => return Err(anyhow!("--format systemd writes one file per secret. Pass --dir.")),
+                ("csv", Some(_)) => return Err(anyhow!("--dir is only used with --format systemd")),
+                _ => return Err(anyhow!("Unsupported export format: {}. Use csv or systemd", format)),
+            }
+            if !i_understand_plaintext {
+                return Err(anyhow!("Export writes every secret value in plaintext. Pass --i-understand-plaintext to continue."));
+            }
+            
+            let vault = unlocker.open()?;
+            let selected = selected_secrets(&vault, &only, glob)?;
+            let names = match &selected {
+                Some(selected) => selected.clone(),
+                None => vault.list_secrets()?.into_iter().map(|(name, _)| name).collect(),
+            };
+            if names.iter().any(|name| vault.is_protected(name).unwrap_or(false)) {
+                reauthenticate(&auth, "Export protected secrets?")?;
+            }
+            if let Some(dir) = dir {
+                let mut values = vault.list_values()?;
+                if let Some(selected) = &selected {
+                    values.retain(|(name, _)| selected.contains(name));
+                }
+                // Check every name and target before writing anything
+                let paths = values
+                    .iter()
+                    .map(|(name, _)| credential_path(&dir, name))
+                    .collect::<Result<Vec<_>>>()?;
+                let existing: Vec<String> = paths.iter().filter(|path| path.exists()).map(|path| path.display().to_string()).collect();
+                if !force && !existing.is_empty() {
+                    return Err(anyhow!("{} already exist(s). Use --force to overwrite.", existing.join(", ")));
+                }
+                
+                create_private_dir(&dir)?;
+                for ((_, value), path) in values.iter().zip(&paths) {
+                    write_private_file(path, value.expose().as_bytes(), force)?;
+                }
+                println!("Wrote {} credential file(s) to {}", paths.len(), dir.display());
+                return Ok(());
+            }
+            let csv = match &selected {
+                Some(selected) => vault.export_csv_of(selected)?,
+                None => vault.export_csv()?,
+            };
+            match output {
+                Some(path) => {
+                    write_private_file(&path, csv.as_bytes(), force)?;
+                    println!("Secrets exported to {}", path.display());
+                }
+                None => print!("{}", csv.as_str()),
+            }
+        }
+        Commands::Restore { path, format, force } => {
+            let backup = BackupData::load_as(&path, format.as_deref())?;
+            if !Identity::exists() {
+                println!("No vault yet; creating one from {}.", path.display());
+                let options = InitOptions { keyfile: auth.keyfile.clone(), ..InitOptions::default() };
+                Vault::init_from_backup(&backup, &options)?;
+                println!("Restored {} secret(s)", backup.vault_data.secrets.len());
+                return Ok(());
+            }
+            
+            let mut vault = unlocker.open()?;
+            let prompt = format!(
+                "Replace the vault's {} secret(s) with the {} in the backup?",
+                vault.data().secrets.len(), backup.vault_data.secrets.len()
+            );
+            if !confirm_action(&prompt, force)? {
+                println!("Cancelled");
+                return Ok(());
+            }
+            let count = vault.restore_from_backup(backup)?;
+            println!("Restored {} secret(s) from {}", count, path.display());
+        }
+        Commands::Merge { path, strategy } => {
+            let strategy: MergeStrategy = strategy.parse()?;
+            let backup = BackupData::load(&path)?;
+            let mut vault = unlocker.open()?;
+            
+            println!("Unlock {} with the master password it was made with.", path.display());
+            let password = Zeroizing::new(prompt_master_password()?);
+            let keyfile = auth.keyfile.as_deref().map(read_keyfile).transpose()?;
+            let their_key = backup.unlock(&password, keyfile.as_deref().map(Vec::as_slice))?;
+            
+            let mut theirs = backup.vault_data;
+            migrations::upgrade(&mut theirs)?;
+            let report = vault.merge(&theirs, &their_key, strategy)?;
+            println!(
+                "Merged {}: {} added, {} updated, {} skipped",
+                path.display(), report.added.len(), report.updated.len(), report.skipped.len()
+            );
+        }
+        Commands::Stats { detailed, watch: false, .. } => {
+            let vault = unlocker.open()?;
+            print_stats(&vault, config, detailed)?;
+        }
+        Commands::Stats { detailed, watch: true, interval } => {
+            let every = parse_duration(&interval)?;
+            if every <= chrono::Duration::zero() {
+                return Err(anyhow!("--interval must be positive"));
+            }
+            let interval = every.to_std()?;
+            let mut vault = unlocker.open()?;
+            loop {
+                // Clear the screen and move the cursor home before each redraw
+                print!("\x1b[2J\x1b[H");
+                print_stats(&vault, config, detailed)?;
+                println!("\nRefreshing every {}. Press Ctrl-C to stop.", format_duration(every));
+                std::io::stdout().flush()?;
+                
+                std::thread::sleep(interval);
+                vault.reload()?;
+            }
+        }
+        Commands::Prune => {
+            let mut vault = unlocker.open_without_cleanup()?;
+            let report = vault.prune(dry_run)?;
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            
+            for name in &report.expired {
+                println!("  • {} (expired)", name);
+            }
+            for name in &report.orphaned {
+                println!("  • {} (orphaned lease)", name);
+            }
+            println!("{} {} expired secret(s) and {} orphaned lease(s)", verb, report.expired.len(), report.orphaned.len());
+        }
+        Commands::Protect { name } => {
+            let mut vault = unlocker.open()?;
+            vault.set_protected(&name, true)?;
+            println!("Secret '{}' is now protected", name);
+        }
+        Commands::Unprotect { name } => {
+            let mut vault = unlocker.open()?;
+            if vault.is_protected(&name)? {
+                reauthenticate(&auth, &format!("Remove protection from '{}'?", name))?;
+            }
+            vault.set_protected(&name, false)?;
+            println!("Secret '{}' is no longer protected", name);
+        }
+        Commands::NamePolicy { pattern: None, clear: false } => {
+            let vault = unlocker.open()?;
+            match &vault.data().name_pattern {
+                Some(pattern) => println!("Secret names must match: {}", pattern),
+                None => println!("No name policy set"),
+            }
+        }
+        Commands::NamePolicy { pattern, .. } => {
+            let mut vault = unlocker.open()?;
+            let mismatched = vault.set_name_pattern(pattern.as_deref())?;
+            match &pattern {
+                Some(pattern) => println!("Secret names must now match: {}", pattern),
+                None => println!("Name policy removed"),
+            }
+            if !mismatched.is_empty() {
+                eprintln!("Warning: existing secrets that don't match: {}", mismatched.join(", "));
+            }
+        }
+        Commands::MaxSecrets { limit: None, clear: false } => {
+            let vault = unlocker.open()?;
+            match vault.data().max_secrets {
+                Some(limit) => println!("Vault holds {} of at most {} secrets", vault.data().secrets.len(), limit),
+                None => println!("No secret limit set"),
+            }
+        }
+        Commands::MaxSecrets { limit, .. } => {
+            let mut vault = unlocker.open()?;
+            vault.set_max_secrets(limit)?;
+            match limit {
+                Some(limit) => println!("Vault is now limited to {} secrets", limit),
+                None => println!("Secret limit removed"),
+            }
+            let count = vault.data().secrets.len();
+            if limit.is_some_and(|limit| count > limit) {
+                eprintln!("Warning: vault already holds {} secrets; no more can be added until some are removed", count);
+            }
+        }
+        Commands::LockVault => {
+            let mut vault = unlocker.open()?;
+            vault.set_locked(true)?;
+            println!("Vault is now read-only");
+        }
+        Commands::UnlockVault => {
+            let mut vault = unlocker.open()?;
+            vault.set_locked(false)?;
+            println!("Vault is writable again");
+        }
+        Commands::Fingerprint => {
+            let vault = unlocker.open()?;
+            println!("{}", vault.data().fingerprint());
+        }
+        Commands::AuditValues => {
+            let vault = unlocker.open()?;
+            let audit = vault.audit_values()?;
+            
+            if audit.is_empty() {
+                println!("No reused or weak secret values found");
+            }
+            for group in &audit.duplicates {
+                println!("  • {} share the same value", group.join(", "));
+            }
+            for (name, weaknesses) in &audit.weak {
+                println!("  • {} is weak: {}", name, weaknesses.join(", "));
+            }
+        }
+        Commands::ChangePassword => {
+            Vault::change_password(&auth, dry_run)?;
+            if dry_run {
+                println!("Dry run: the vault can be re-keyed under the new password. Nothing was changed.");
+            } else {
+                unlocker.forget_key();
+                println!("Master password changed successfully!");
+            }
+        }
+        Commands::Identity { action: IdentityCommand::Export { out, force } } => {
+            export_identity(&out, force)?;
+            println!("Identity exported to {}", out.display());
+            eprintln!("It unlocks the vault with your master password; keep it apart from your secret backups.");
+        }
+        Commands::Identity { action: IdentityCommand::Import { input, force } } => {
+            import_identity(&input, &auth, force)?;
+            unlocker.forget_key();
+            println!("Identity imported from {}", input.display());
+        }
+        Commands::Disable2fa => {
+            if disable_two_factor(&auth)? {
+                println!("Two-factor authentication disabled");
+            } else {
+                println!("Two-factor authentication is not enabled");
+            }
+        }
+        Commands::Shell { timeout } => {
+            let minutes = timeout.unwrap_or_else(|| config.shell_timeout_minutes());
+            run_shell(unlocker, config, Duration::from_secs(minutes.saturating_mul(60)))?
+        }
+        Commands::Doctor => {
+            let checks = doctor::run_checks();
+            let color = std::io::stdout().is_terminal();
+            for check in &checks {
+                let mark = match (check.passed, color) {
+                    (true, true) => "\x1b[32m✓\x1b[0m",
+                    (false, true) => "\x1b[31m✗\x1b[0m",
+                    (true, false) => "✓",
+                    (false, false) => "✗",
+                };
+                println!("{} {}: {}", mark, check.name, check.detail);
+                if let Some(hint) = &check.hint {
+                    println!("    → {}", hint);
+                }
+            }
+            
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            if failed > 0 {
+                return Err(anyhow!("{} check(s) failed", failed));
+            }
+            println!("No problems found");
+        }
+        Commands::Status => {
+            let status = doctor::status()?;
+            for (label, file) in [("Identity", &status.identity), ("Vault", &status.vault)] {
+                match file {
+                    Some(file) => println!(
+                        "{:<9} {} ({}, modified {})",
+                        format!("{}:", label), file.path.display(), format_bytes(file.size), config.format_time(file.modified)
+                    ),
+                    None => println!("{:<9} missing", format!("{}:", label)),
+                }
+            }
+            if let Some(contents) = &status.contents {
+                println!("Format:   {:?}", contents.format);
+                println!("Version:  {}", contents.version);
+                println!("Created:  {}", config.format_time(contents.created_at));
+                println!("Secrets:  {}", contents.secrets);
+            }
+            
+            if !status.is_healthy() {
+                return Err(anyhow!("Vault is not initialized; run `sentinel init`"));
+            }
+        }
+        Commands::Scan { path } => {
+            let vault = unlocker.open()?;
+            let secrets = vault.decrypt_all()?;
+            let findings = scan::scan(&path, &secrets)?;
+            for finding in &findings {
+                println!("{}:{}: contains secret '{}'", finding.path.display(), finding.line, finding.secret);
+            }
+            
+            let short = secrets.values().filter(|value| value.expose().len() < MIN_SCAN_LENGTH).count();
+            if short > 0 {
+                eprintln!("Skipped {} secret(s) shorter than {} characters", short, MIN_SCAN_LENGTH);
+            }
+            if !findings.is_empty() {
+                return Err(anyhow!("Found {} plaintext secret(s)", findings.len()));
+            }
+            println!("No stored secrets found in {}", path.display());
+        }
+        Commands::MachineId => println!("{}", machine_id()?),
+        Commands::Benchmark { target_ms } => {
+            let target = Duration::from_millis(target_ms);
+            println!("Argon2id key derivation, 1 lane:");
+            println!("  {:<10} {:<7} {:>10}", "Memory", "Passes", "Time");
+            let timings = benchmark::time_kdf_grid(target)?;
+            for timing in &timings {
+                println!(
+                    "  {:<10} {:<7} {:>7.1} ms",
+                    format_bytes(u64::from(timing.params.m_cost) * 1024), timing.params.t_cost, timing.elapsed.as_secs_f64() * 1000.0
+                );
+            }
+            
+            let current = KdfParams::RECOMMENDED;
+            let current_time = match timings.iter().find(|timing| timing.params == current) {
+                Some(timing) => timing.elapsed,
+                None => benchmark::time_kdf(current, &generate_salt())?.elapsed,
+            };
+            println!(
+                "The default cost, m={} KiB, t={}, takes {:.1} ms here.",
+                current.m_cost, current.t_cost, current_time.as_secs_f64() * 1000.0
+            );
+            match benchmark::recommend(&timings, target) {
+                Some(params) if params.is_weaker_than(&current) => println!(
+                    "Strongest within {} ms is m={} KiB, t={}, below the default, which can't be lowered.",
+                    target_ms, params.m_cost, params.t_cost
+                ),
+                Some(params) => println!(
+                    "Strongest within {} ms: m={} KiB ({}), t={}. Suggested: sentinel init --key-cost m={},t={}",
+                    target_ms, params.m_cost, format_bytes(u64::from(params.m_cost) * 1024), params.t_cost, params.m_cost, params.t_cost
+                ),
+                None => println!("Nothing tried finishes within {} ms", target_ms),
+            }
+            
+            let (encrypt, decrypt) = benchmark::cipher_throughput(64 * 1024 * 1024)?;
+            println!("AES-256-GCM: encrypt {:.0} MiB/s, decrypt {:.0} MiB/s", encrypt, decrypt);
+        }
+        Commands::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        }
+    }
+    
+    Ok(())
+}
+
+/// Authenticate once, then run commands read from stdin until `exit`
+fn run_shell(unlocker: &mut Unlocker, config: &Config, timeout: Duration) -> Result<()> {
+    if unlocker.session.is_some() {
+        return Err(anyhow!("Already running a shell"));
+    }
+    
+    let key = authenticate(&unlocker.auth)?;
+    unlocker.session = Some(IdleKey::new(key, timeout));
+    println!("Vault unlocked. Type 'help' for commands or 'exit' to quit.");
+    
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("sentinel> ");
+        std::io::stdout().flush()?;
+        
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            break;
+        }
+        
+        let words = match split_command_line(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("exit") | Some("quit") => break,
+            _ => {}
+        }
+        
+        // Reuse the normal argument parsing, as if the line followed `sentinel`
+        match Cli::try_parse_from(std::iter::once("sentinel".to_string()).chain(words)) {
+            Ok(cli) if cli.vault_dir.is_some() => {
+                eprintln!("Error: --vault-dir cannot be changed inside the shell");
+            }
+            Ok(cli) => {
+                let start = Instant::now();
+                let result = if cli.utc {
+                    let config = Config { utc: Some(true), ..config.clone() };
+                    run(cli.command, cli.dry_run, &config, unlocker)
+                } else {
+                    run(cli.command, cli.dry_run, config, unlocker)
+                };
+                profile::finish(start.elapsed());
+                if let Err(e) = result {
+                    eprintln!("Error: {:#}", e);
+                }
+            }
+            Err(e) => {
+                let _ = e.print();
+            }
+        }
+    }
+    
+    if let Some(mut session) = unlocker.session.take() {
+        session.lock();
+    }
+    
+    Ok(())
+}
+
+/// Run a `watch --on-expire` command through the shell, with the secret's name
+/// in `SENTINEL_SECRET`
+fn run_hook(command: &str, name: &str) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = std::process::Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = std::process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    
+    let status = shell.arg(command).env("SENTINEL_SECRET", name).status()?;
+    if !status.success() {
+        return Err(anyhow!("--on-expire command failed with {}", status));
+    }
+    Ok(())
+}
+
+/// Pop up a desktop notification that a secret was read. Only the name and
+/// time are shown. Failures are reported on stderr and never abort `get`.
+#[cfg_attr(not(feature = "notify"), allow(unused_variables))]
+fn notify_access(name: &str) {
+    #[cfg(feature = "notify")]
+    {
+        let body = format!("Secret '{}' accessed at {}", name, chrono::Local::now().format("%H:%M"));
+        if let Err(e) = notify_rust::Notification::new().summary("SentinelVault").body(&body).show() {
+            eprintln!("Warning: could not show notification: {}", e);
+        }
+    }
+    #[cfg(not(feature = "notify"))]
+    eprintln!("Warning: notifications need a build with the `notify` feature");
+}
+
+/// The secrets an `--only` list selects, or `None` without one
+fn selected_secrets(vault: &Vault, only: &[String], glob: bool) -> Result<Option<Vec<String>>> {
+    if only.is_empty() {
+        return Ok(None);
+    }
+    
+    let matched = vault.match_secrets(only, glob)?;
+    if matched.is_empty() {
+        return Err(anyhow!("No secrets match --only {}", only.join(",")));
+    }
+    Ok(Some(matched))
+}
+
+/// Whether `list` shows `name` under its namespace and tag filters
+fn listed(vault: &Vault, name: &str, namespace: Option<&str>, tag: Option<&str>) -> bool {
+    namespace.is_none_or(|namespace| in_namespace(name, namespace)) && tag.is_none_or(|tag| vault.has_tag(name, tag))
+}
+
+fn print_stats(vault: &Vault, config: &Config, detailed: bool) -> Result<()> {
+    let stats = vault.get_stats()?;
+    println!("Vault Statistics:");
+    match stats.max_secrets {
+        Some(limit) => println!("  Total secrets: {} / {}", stats.total_secrets, limit),
+        None => println!("  Total secrets: {}", stats.total_secrets),
+    }
+    println!("  Active leases: {}", stats.active_leases);
+    println!("  Expired secrets: {}", stats.expired_secrets);
+    println!("  Vault size: {} bytes", stats.vault_size);
+    
+    if detailed {
+        let details = vault.get_detailed_stats()?;
+        if let Some((name, created)) = details.oldest_secret {
+            println!("  Oldest secret: {} ({})", name, config.format_time(created));
+        }
+        if let Some((name, created)) = details.newest_secret {
+            println!("  Newest secret: {} ({})", name, config.format_time(created));
+        }
+        if let Some((name, count)) = details.most_accessed {
+            println!("  Most accessed: {} ({} reads)", name, count);
+        }
+        if let Some(average) = details.average_lease {
+            println!("  Average lease: {} minutes", average.num_minutes());
         }
     }
     
     Ok(())
 }
+
+/// Serialize a backup in the requested format. QR backups are rendered as an
+/// image when `output` ends in `.png` or `.svg`, and are split into several
+/// parts when the backup does not fit in a single code.
+#[cfg_attr(not(feature = "qr-backup"), allow(unused_variables))]
+fn render_backup(backup_data: &BackupData, format: &str, output: Option<&Path>) -> Result<Vec<Vec<u8>>> {
+    match format {
+        "json" => Ok(vec![serde_json::to_string_pretty(backup_data)?.into_bytes()]),
+        "yaml" => Ok(vec![serde_yaml::to_string(backup_data)?.into_bytes()]),
+        #[cfg(feature = "qr-backup")]
+        "qr" => {
+            use sentinelvault::qr::{self, QrOutput};
+            let backup_str = ron::to_string(backup_data)?;
+            let rendering = QrOutput::for_path(output);
+            
+            qr::split_payload(&backup_str)
+                .iter()
+                .map(|part| qr::render(part, rendering))
+                .collect()
+        }
+        _ => Ok(vec![ron::to_string(backup_data)?.into_bytes()]),
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer};
+use std::cell::Cell;
+
+use crate::error::VaultError;
+use crate::vault::VaultData;
+
+/// Schema version written by this build
+pub const CURRENT_VERSION: &str = "0.2.0";
+
+/// Binary vault layout written by this build. Postcard can't default a
+/// missing field the way RON does, so every field added to the binary
+/// layout bumps this and is read with a `since_layout_*` function.
+///
+/// 1. The first binary layout
+/// 2. `EncryptedData` gains `version` and `alg`
+/// 3. `VaultData` gains `aliases`
+/// 4. `SecretEntry` gains `tags` and `description`
+pub const BINARY_LAYOUT: u8 = 4;
+
+thread_local! {
+    /// Layout of the binary vault being decoded on this thread
+    static DECODING_LAYOUT: Cell<u8> = const { Cell::new(BINARY_LAYOUT) };
+}
+
+/// Decode binary data in `f` as written at `layout`
+pub(crate) fn with_binary_layout<T>(layout: u8, f: impl FnOnce() -> T) -> T {
+    /// Puts the previous layout back, even if `f` panics
+    struct Restore(u8);
+    
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DECODING_LAYOUT.with(|current| current.set(self.0));
+        }
+    }
+    
+    let _restore = Restore(DECODING_LAYOUT.with(|current| current.replace(layout)));
+    f()
+}
+
+/// Whether the data being decoded is binary from before `layout`. Never for
+/// human-readable formats, where a missing field takes its serde default.
+pub(crate) fn predates_layout<'de, D: Deserializer<'de>>(deserializer: &D, layout: u8) -> bool {
+    !deserializer.is_human_readable() && DECODING_LAYOUT.with(Cell::get) < layout
+}
+
+/// `deserialize_with` for fields added in layout 3. Older binary data has
+/// nothing to read for them, so they take their default.
+pub(crate) fn since_layout_3<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    since_layout(deserializer, 3)
+}
+
+/// `deserialize_with` for fields added in layout 4
+pub(crate) fn since_layout_4<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    since_layout(deserializer, 4)
+}
+
+fn since_layout<'de, D, T>(deserializer: D, layout: u8) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    if predates_layout(&deserializer, layout) {
+        return Ok(T::default());
+    }
+    T::deserialize(deserializer)
+}
+
+type Migration = fn(&mut VaultData) -> Result<()>;
+
+/// Upgrade steps in order. Each takes a vault stored at the first version
+/// and brings it to the second.
+const MIGRATIONS: &[(&str, &str, Migration)] = &[
+    ("0.1.0", "0.2.0", migrate_0_1_0),
+];
+
+/// 0.2.0 adds the size limit, read-only flag, protected secrets and sliding
+/// leases. They all deserialize with defaults, so only the version changes.
+/// Envelope encryption needs the key and is handled when the vault is opened.
+fn migrate_0_1_0(_data: &mut VaultData) -> Result<()> {
+    Ok(())
+}
+
+fn parse_version(version: &str) -> Result<(u64, u64, u64)> {
+    let parts: Vec<u64> = version
+        .split('.')
+        .map(|part| part.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| VaultError::Corrupted(format!("invalid vault version '{}'", version)))?;
+    
+    match parts.as_slice() {
+        [major, minor, patch] => Ok((*major, *minor, *patch)),
+        _ => Err(VaultError::Corrupted(format!("invalid vault version '{}'", version)).into()),
+    }
+}
+
+/// Fail if `version` is malformed or newer than this build understands
+pub fn check_supported(version: &str) -> Result<()> {
+    if parse_version(version)? > parse_version(CURRENT_VERSION)? {
+        return Err(VaultError::NewerVersion {
+            found: version.to_string(),
+            supported: CURRENT_VERSION.to_string(),
+        }
+        .into());
+    }
+    
+    Ok(())
+}
+
+/// Run every migration between the stored version and [`CURRENT_VERSION`].
+/// Returns true if the vault was upgraded and should be saved.
+pub fn upgrade(data: &mut VaultData) -> Result<bool> {
+    check_supported(&data.version)?;
+    
+    let mut upgraded = false;
+    while data.version != CURRENT_VERSION {
+        let (_, to, migrate) = MIGRATIONS
+            .iter()
+            .find(|(from, _, _)| *from == data.version)
+            .ok_or_else(|| anyhow!("No migration from vault version {}", data.version))?;
+        
+        migrate(data)?;
+        data.version = to.to_string();
+        upgraded = true;
+    }
+    
+    Ok(upgraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_binary_layout_restored_after_panic() {
+        let result = std::panic::catch_unwind(|| with_binary_layout(1, || panic!("decoding failed")));
+        assert!(result.is_err());
+        assert_eq!(DECODING_LAYOUT.with(Cell::get), BINARY_LAYOUT);
+        assert_eq!(with_binary_layout(1, || DECODING_LAYOUT.with(Cell::get)), 1);
+    }
+    
+    #[test]
+    fn test_upgrade_from_0_1_0() {
+        // A vault as written before versioned migrations existed
+        let old = r#"(secrets:{},lease_manager:(leases:{}),created_at:"2024-01-01T00:00:00Z",version:"0.1.0")"#;
+        let mut data: VaultData = ron::from_str(old).unwrap();
+        
+        assert!(upgrade(&mut data).unwrap());
+        assert_eq!(data.version, CURRENT_VERSION);
+        assert!(!upgrade(&mut data).unwrap());
+    }
+    
+    #[test]
+    fn test_newer_version_is_refused() {
+        let mut data = VaultData {
+            version: "9.0.0".to_string(),
+            ..VaultData::default()
+        };
+        
+        let err = upgrade(&mut data).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NewerVersion { .. })));
+    }
+    
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("0.1.0").unwrap(), (0, 1, 0));
+        assert!(parse_version("0.1.0").unwrap() < parse_version("0.10.0").unwrap());
+        assert!(parse_version("1.0").is_err());
+        assert!(parse_version("abc").is_err());
+    }
+}
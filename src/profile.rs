@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set once by `--profile`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// How many timed phases are running, so nested phases aren't counted twice
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+/// Time spent in outermost phases since the last `finish`, in microseconds
+static PHASE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Turn on timing output for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Run `f`, and with profiling on print how long it took to stderr. A phase
+/// inside another one is reported on its own and as part of the outer one.
+pub fn time<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return f();
+    }
+    
+    let nested = DEPTH.fetch_add(1, Ordering::Relaxed) > 0;
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    DEPTH.fetch_sub(1, Ordering::Relaxed);
+    
+    if !nested {
+        PHASE_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+    report(phase, elapsed);
+    result
+}
+
+/// Report a whole command that took `total`, less the phases already
+/// printed for it
+pub fn finish(total: Duration) {
+    if !is_enabled() {
+        return;
+    }
+    
+    let phases = Duration::from_micros(PHASE_MICROS.swap(0, Ordering::Relaxed));
+    report("command body", total.saturating_sub(phases));
+}
+
+fn report(phase: &str, elapsed: Duration) {
+    eprintln!("[profile] {}: {:.1} ms", phase, elapsed.as_secs_f64() * 1000.0);
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::path::Path;
+
+/// Payload bytes per QR code, leaving room for the part header within the
+/// 2331-byte capacity of a version 40 code at the default (M) error correction
+const MAX_PART_LEN: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QrOutput {
+    Terminal,
+    Png,
+    Svg,
+}
+
+impl QrOutput {
+    /// Pick the rendering from an output file extension
+    pub fn for_path(path: Option<&Path>) -> Self {
+        let extension = path
+            .and_then(|p| p.extension())
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => QrOutput::Png,
+            Some("svg") => QrOutput::Svg,
+            _ => QrOutput::Terminal,
+        }
+    }
+}
+
+/// Split a payload into parts that each fit in one QR code. When more than one
+/// part is needed, each is prefixed with an `SVB<n>/<m>:` header so the
+/// original can be reassembled in order.
+pub fn split_payload(payload: &str) -> Vec<String> {
+    if payload.len() <= MAX_PART_LEN {
+        return vec![payload.to_string()];
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for c in payload.chars() {
+        if current.len() + c.len_utf8() > MAX_PART_LEN {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("SVB{}/{}:{}", i + 1, total, chunk))
+        .collect()
+}
+
+pub fn render(part: &str, output: QrOutput) -> Result<Vec<u8>> {
+    let code = QrCode::new(part.as_bytes())
+        .map_err(|e| anyhow!("Failed to encode QR code: {}", e))?;
+
+    match output {
+        QrOutput::Terminal => {
+            let string = code.render::<char>()
+                .quiet_zone(false)
+                .module_dimensions(2, 1)
+                .build();
+            Ok(string.into_bytes())
+        }
+        QrOutput::Png => {
+            let image = code.render::<image::Luma<u8>>().build();
+            let mut png = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut png, image::ImageFormat::Png)?;
+            Ok(png.into_inner())
+        }
+        QrOutput::Svg => {
+            let string = code.render::<svg::Color>()
+                .min_dimensions(400, 400)
+                .build();
+            Ok(string.into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_payload_is_single_part() {
+        let parts = split_payload("(secrets:{})");
+        assert_eq!(parts, vec!["(secrets:{})"]);
+    }
+
+    #[test]
+    fn test_large_payload_is_split_with_headers() {
+        let payload = "x".repeat(MAX_PART_LEN * 2 + 10);
+        let parts = split_payload(&payload);
+
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].starts_with("SVB1/3:"));
+        assert!(parts[2].starts_with("SVB3/3:"));
+
+        let rejoined: String = parts
+            .iter()
+            .map(|part| part.split_once(':').unwrap().1)
+            .collect();
+        assert_eq!(rejoined, payload);
+
+        for part in &parts {
+            assert!(render(part, QrOutput::Terminal).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_output_from_path() {
+        assert_eq!(QrOutput::for_path(Some(Path::new("b.PNG"))), QrOutput::Png);
+        assert_eq!(QrOutput::for_path(Some(Path::new("b.svg"))), QrOutput::Svg);
+        assert_eq!(QrOutput::for_path(Some(Path::new("b.txt"))), QrOutput::Terminal);
+        assert_eq!(QrOutput::for_path(None), QrOutput::Terminal);
+    }
+}
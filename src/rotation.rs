@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::{create_private_dir, write_private_file};
+
+/// Rotated backups are named `sentinelvault-<timestamp>.<extension>`
+const FILE_PREFIX: &str = "sentinelvault-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+/// Backups written before names carried milliseconds
+const LEGACY_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+pub fn backup_file_name(at: DateTime<Utc>, extension: &str) -> String {
+    format!("{}{}.{}", FILE_PREFIX, at.format(TIMESTAMP_FORMAT), extension)
+}
+
+/// When a rotated backup was taken, from its file name. `None` for files
+/// that weren't written by rotation.
+fn backup_time(file_name: &str) -> Option<DateTime<Utc>> {
+    let (stem, _extension) = file_name.strip_prefix(FILE_PREFIX)?.rsplit_once('.')?;
+    NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(stem, LEGACY_TIMESTAMP_FORMAT))
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Rotated backups in `dir`, oldest first. Other files are ignored.
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| anyhow!("Failed to read {}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        let time = path.file_name().and_then(|name| backup_time(&name.to_string_lossy()));
+        if let Some(time) = time.filter(|_| path.is_file()) {
+            backups.push((time, path));
+        }
+    }
+    
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Write `contents` as a new timestamped backup in `dir` (created 0700 if
+/// needed), then delete the oldest backups beyond `keep`. Returns the new
+/// file and the ones removed.
+pub fn write_rotated(dir: &Path, contents: &[u8], extension: &str, keep: Option<usize>) -> Result<(PathBuf, Vec<PathBuf>)> {
+    if keep == Some(0) {
+        return Err(anyhow!("--keep must be at least 1"));
+    }
+    
+    create_private_dir(dir)?;
+    // Two backups in the same millisecond take the next free one, so names
+    // stay unique and in order
+    let mut at = Utc::now();
+    let mut path = dir.join(backup_file_name(at, extension));
+    while path.exists() {
+        at += chrono::Duration::milliseconds(1);
+        path = dir.join(backup_file_name(at, extension));
+    }
+    write_private_file(&path, contents, false)?;
+    
+    let mut removed = Vec::new();
+    if let Some(keep) = keep {
+        let backups = list_backups(dir)?;
+        let excess = backups.len().saturating_sub(keep);
+        for old in backups.into_iter().take(excess) {
+            fs::remove_file(&old).map_err(|e| anyhow!("Failed to remove {}: {}", old.display(), e))?;
+            removed.push(old);
+        }
+    }
+    
+    Ok((path, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_backup_names_round_trip() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap() + chrono::Duration::milliseconds(250);
+        let name = backup_file_name(at, "ron");
+        assert_eq!(name, "sentinelvault-20240309T140500.250Z.ron");
+        assert_eq!(backup_time(&name), Some(at));
+        assert_eq!(
+            backup_time("sentinelvault-20240309T140500Z.ron"),
+            Some(Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap())
+        );
+        
+        assert_eq!(backup_time("notes.txt"), None);
+        assert_eq!(backup_time("sentinelvault-latest.ron"), None);
+    }
+    
+    #[test]
+    fn test_rotation_keeps_newest() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("backups");
+        fs::create_dir(&dir).unwrap();
+        for day in 1..=3 {
+            let at = Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap();
+            fs::write(dir.join(backup_file_name(at, "ron")), "old").unwrap();
+        }
+        fs::write(dir.join("README"), "not a backup").unwrap();
+        
+        let (path, removed) = write_rotated(&dir, b"new", "json", Some(2)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(removed.len(), 2);
+        assert!(removed[0].ends_with("sentinelvault-20240101T000000.000Z.ron"));
+        assert!(removed[1].ends_with("sentinelvault-20240102T000000.000Z.ron"));
+        
+        let remaining = list_backups(&dir).unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(remaining[1], path);
+        assert!(dir.join("README").exists());
+        
+        assert!(write_rotated(&dir, b"new", "json", Some(0)).is_err());
+    }
+    
+    #[test]
+    fn test_rotation_names_are_unique() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("backups");
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|i| write_rotated(&dir, format!("backup {}", i).as_bytes(), "ron", None).unwrap().0)
+            .collect();
+        
+        assert_eq!(list_backups(&dir).unwrap(), paths);
+    }
+}
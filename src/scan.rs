@@ -0,0 +1,146 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::SecretString;
+
+/// Values shorter than this turn up in ordinary text too often to report
+pub const MIN_SCAN_LENGTH: usize = 6;
+/// Bigger files are skipped rather than read into memory
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// Directories holding tool state rather than files someone wrote
+const SKIPPED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// A stored secret's value appearing in plaintext
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub path: PathBuf,
+    /// 1-based
+    pub line: usize,
+    /// Name of the secret, never its value
+    pub secret: String,
+}
+
+/// Look through every text file under `root` (or `root` itself, if it's a
+/// file) for lines containing one of the `secrets` values. Symlinks, binary
+/// files and values shorter than `MIN_SCAN_LENGTH` are skipped, as are files
+/// and directories under `root` that can't be read, with a warning.
+pub fn scan(root: &Path, secrets: &HashMap<String, SecretString>) -> Result<Vec<Finding>> {
+    let mut secrets: Vec<(&String, &str)> = secrets
+        .iter()
+        .map(|(name, value)| (name, value.expose()))
+        .filter(|(_, value)| value.len() >= MIN_SCAN_LENGTH)
+        .collect();
+    secrets.sort();
+    
+    let mut findings = Vec::new();
+    if !secrets.is_empty() {
+        scan_path(root, &secrets, &mut findings)?;
+    }
+    findings.sort_by(|a, b| (&a.path, a.line, &a.secret).cmp(&(&b.path, b.line, &b.secret)));
+    Ok(findings)
+}
+
+fn scan_path(path: &Path, secrets: &[(&String, &str)], findings: &mut Vec<Finding>) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if SKIPPED_DIRS.iter().any(|dir| entry.file_name() == *dir) {
+                continue;
+            }
+            if let Err(e) = scan_path(&entry.path(), secrets, findings) {
+                if !is_permission_denied(&e) {
+                    return Err(e);
+                }
+                eprintln!("Warning: skipping {}: {}", entry.path().display(), e);
+            }
+        }
+    } else if metadata.is_file() && metadata.len() <= MAX_FILE_SIZE {
+        scan_file(path, secrets, findings)?;
+    }
+    
+    Ok(())
+}
+
+fn is_permission_denied(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+fn scan_file(path: &Path, secrets: &[(&String, &str)], findings: &mut Vec<Finding>) -> Result<()> {
+    let bytes = fs::read(path)?;
+    // Same check git uses to call a file binary
+    if bytes[..bytes.len().min(8000)].contains(&0) {
+        return Ok(());
+    }
+    
+    let contents = String::from_utf8_lossy(&bytes);
+    for (i, line) in contents.lines().enumerate() {
+        for (name, value) in secrets {
+            if line.contains(value) {
+                findings.push(Finding { path: path.to_path_buf(), line: i + 1, secret: name.to_string() });
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_scan_finds_plaintext_secrets() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("src/config.py"), "host = 'db'\npassword = 'hunter2-prod'\n").unwrap();
+        fs::write(root.join(".env"), "TOKEN=sk-0123456789\nPIN=1234\n").unwrap();
+        fs::write(root.join(".git/leak"), "hunter2-prod").unwrap();
+        fs::write(root.join("blob.bin"), b"\0hunter2-prod").unwrap();
+        
+        let secrets = HashMap::from([
+            ("db/password".to_string(), SecretString::new("hunter2-prod".to_string())),
+            ("api_token".to_string(), SecretString::new("sk-0123456789".to_string())),
+            ("pin".to_string(), SecretString::new("1234".to_string())),
+        ]);
+        let findings = scan(root, &secrets).unwrap();
+        assert_eq!(findings, vec![
+            Finding { path: root.join(".env"), line: 1, secret: "api_token".to_string() },
+            Finding { path: root.join("src/config.py"), line: 2, secret: "db/password".to_string() },
+        ]);
+        
+        assert_eq!(scan(&root.join("src/config.py"), &secrets).unwrap().len(), 1);
+        assert!(scan(&root.join("missing"), &secrets).is_err());
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_unreadable_entries() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("private")).unwrap();
+        fs::write(root.join("private/notes"), "hunter2-prod").unwrap();
+        fs::write(root.join("locked.txt"), "hunter2-prod").unwrap();
+        fs::write(root.join("open.txt"), "hunter2-prod").unwrap();
+        fs::set_permissions(root.join("private"), fs::Permissions::from_mode(0o000)).unwrap();
+        fs::set_permissions(root.join("locked.txt"), fs::Permissions::from_mode(0o000)).unwrap();
+        
+        let secrets = HashMap::from([("db/password".to_string(), SecretString::new("hunter2-prod".to_string()))]);
+        let findings = scan(root, &secrets);
+        let readable_anyway = fs::read(root.join("locked.txt")).is_ok();
+        fs::set_permissions(root.join("private"), fs::Permissions::from_mode(0o755)).unwrap();
+        
+        // Running as root reads everything, so there's nothing to skip
+        if !readable_anyway {
+            assert_eq!(findings.unwrap(), vec![
+                Finding { path: root.join("open.txt"), line: 1, secret: "db/password".to_string() },
+            ]);
+        }
+    }
+}
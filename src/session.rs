@@ -0,0 +1,175 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+use crate::crypto::SecretKey;
+use crate::identity::{authenticate, AuthOptions};
+
+/// Holds a derived key for a long-lived process and wipes it once it has
+/// gone unused for longer than the idle timeout. A watchdog thread does the
+/// wiping, so the key goes away on time even while the process sits blocked
+/// waiting for input.
+pub struct IdleKey {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    key: SecretKey,
+    active: bool,
+    last_used: Instant,
+    timeout: Duration,
+    /// Bumped by every `renew`, so the watchdog started before it stops
+    generation: u64,
+}
+
+impl State {
+    fn is_expired(&self) -> bool {
+        !self.active || self.last_used.elapsed() > self.timeout
+    }
+    
+    fn lock(&mut self) {
+        self.key.zeroize();
+        self.active = false;
+    }
+}
+
+impl IdleKey {
+    pub fn new(key: SecretKey, timeout: Duration) -> Self {
+        let idle = Self {
+            state: Arc::new(Mutex::new(State {
+                key,
+                active: true,
+                last_used: Instant::now(),
+                timeout,
+                generation: 0,
+            })),
+        };
+        idle.watch();
+        idle
+    }
+    
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Start a watchdog that wipes the key once it times out
+    fn watch(&self) {
+        let state = Arc::downgrade(&self.state);
+        let generation = self.state().generation;
+        std::thread::spawn(move || watchdog(state, generation));
+    }
+    
+    pub fn is_expired(&self) -> bool {
+        self.state().is_expired()
+    }
+    
+    /// A copy of the key, if it was used within the timeout. Each call
+    /// restarts the idle timer; a timed out key is zeroized and stays
+    /// unavailable until `renew`.
+    pub fn get(&mut self) -> Option<SecretKey> {
+        let mut state = self.state();
+        if state.is_expired() {
+            state.lock();
+            return None;
+        }
+        
+        state.last_used = Instant::now();
+        Some(state.key.clone())
+    }
+    
+    /// Zeroize the key now instead of waiting for the timeout
+    pub fn lock(&mut self) {
+        self.state().lock();
+    }
+    
+    pub fn renew(&mut self, key: SecretKey) {
+        {
+            let mut state = self.state();
+            state.key = key;
+            state.active = true;
+            state.last_used = Instant::now();
+            state.generation += 1;
+        }
+        self.watch();
+    }
+    
+    /// Like `get`, but prompts for credentials again once the key has timed out
+    pub fn get_or_authenticate(&mut self, options: &AuthOptions) -> Result<SecretKey> {
+        if let Some(key) = self.get() {
+            return Ok(key);
+        }
+        
+        let key = authenticate(options)?;
+        self.renew(key.clone());
+        Ok(key)
+    }
+}
+
+/// Sleep until the key's idle timeout, then wipe it unless it was used in
+/// the meantime. Stops once the key is locked, renewed or dropped.
+fn watchdog(state: Weak<Mutex<State>>, generation: u64) {
+    loop {
+        let Some(shared) = state.upgrade() else {
+            return;
+        };
+        let remaining = {
+            let mut current = shared.lock().unwrap_or_else(|e| e.into_inner());
+            if current.generation != generation || !current.active {
+                return;
+            }
+            match current.timeout.checked_sub(current.last_used.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => remaining,
+                _ => {
+                    current.lock();
+                    return;
+                }
+            }
+        };
+        
+        // Don't keep the key alive while waiting
+        drop(shared);
+        std::thread::sleep(remaining);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_key_available_within_timeout() {
+        let mut idle = IdleKey::new(SecretKey::new([7u8; 32]), Duration::from_secs(60));
+        
+        assert!(!idle.is_expired());
+        assert_eq!(idle.get().unwrap().as_bytes(), &[7u8; 32]);
+    }
+    
+    #[test]
+    fn test_key_zeroized_after_timeout() {
+        let mut idle = IdleKey::new(SecretKey::new([7u8; 32]), Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(30));
+        
+        assert!(idle.is_expired());
+        assert!(idle.get().is_none());
+        assert_eq!(idle.state().key.as_bytes(), &[0u8; 32]);
+        
+        idle.renew(SecretKey::new([9u8; 32]));
+        assert_eq!(idle.get().unwrap().as_bytes(), &[9u8; 32]);
+        
+        idle.lock();
+        assert!(idle.get().is_none());
+        assert_eq!(idle.state().key.as_bytes(), &[0u8; 32]);
+    }
+    
+    #[test]
+    fn test_idle_key_wiped_without_access() {
+        let idle = IdleKey::new(SecretKey::new([7u8; 32]), Duration::from_millis(20));
+        std::thread::sleep(Duration::from_millis(200));
+        
+        // Nothing asked for the key, and it's gone anyway
+        let state = idle.state();
+        assert!(!state.active);
+        assert_eq!(state.key.as_bytes(), &[0u8; 32]);
+    }
+}
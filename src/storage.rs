@@ -0,0 +1,152 @@
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::VaultError;
+use crate::utils::{create_private_dir, get_vault_path, replace_private_file, warn_if_permissive};
+
+/// Where a vault's serialized data lives. The bytes are the RON-encoded
+/// `VaultData`; secret values inside it are already encrypted.
+pub trait VaultStorage {
+    /// The stored vault. Fails with `VaultError::NotInitialized` if nothing
+    /// has been written yet.
+    fn read(&self) -> Result<Vec<u8>>;
+    
+    /// Replace the stored vault
+    fn write(&self, data: &[u8]) -> Result<()>;
+    
+    /// Delete the stored vault. Nothing having been written is not an error.
+    fn delete(&self) -> Result<()>;
+    
+    /// Stored size in bytes, or 0 if nothing has been written yet
+    fn size(&self) -> Result<u64> {
+        match self.read() {
+            Ok(data) => Ok(data.len() as u64),
+            Err(e) if matches!(e.downcast_ref::<VaultError>(), Some(VaultError::NotInitialized)) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The vault file on disk, written with owner-only permissions
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+    
+    /// `~/.sentinelvault/vault.ron`, or wherever the config points
+    pub fn default_location() -> Result<Self> {
+        Ok(Self::new(get_vault_path()?))
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl VaultStorage for FileStorage {
+    fn read(&self) -> Result<Vec<u8>> {
+        if !self.path.exists() {
+            return Err(VaultError::NotInitialized.into());
+        }
+        
+        if let Some(parent) = self.path.parent() {
+            warn_if_permissive(parent);
+        }
+        warn_if_permissive(&self.path);
+        let data = fs::read(&self.path)?;
+        log::debug!("Read {} bytes from {}", data.len(), self.path.display());
+        Ok(data)
+    }
+    
+    fn write(&self, data: &[u8]) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_private_dir(parent)?;
+        }
+        replace_private_file(&self.path, data)?;
+        log::debug!("Wrote {} bytes to {}", data.len(), self.path.display());
+        Ok(())
+    }
+    
+    fn delete(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .map_err(|e| anyhow!("Failed to remove {}: {}", self.path.display(), e))?;
+            log::debug!("Removed {}", self.path.display());
+        }
+        Ok(())
+    }
+    
+    fn size(&self) -> Result<u64> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        Ok(fs::metadata(&self.path)?.len())
+    }
+}
+
+/// Keeps the vault in RAM only, for tests and throwaway vaults
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    data: RefCell<Option<Vec<u8>>>,
+}
+
+impl VaultStorage for MemoryStorage {
+    fn read(&self) -> Result<Vec<u8>> {
+        self.data.borrow().clone().ok_or_else(|| VaultError::NotInitialized.into())
+    }
+    
+    fn write(&self, data: &[u8]) -> Result<()> {
+        *self.data.borrow_mut() = Some(data.to_vec());
+        Ok(())
+    }
+    
+    fn delete(&self) -> Result<()> {
+        self.data.borrow_mut().take();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_file_storage_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("nested").join("vault.ron"));
+        
+        let err = storage.read().unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotInitialized)));
+        assert_eq!(storage.size().unwrap(), 0);
+        
+        storage.write(b"(secrets:{})").unwrap();
+        assert_eq!(storage.read().unwrap(), b"(secrets:{})");
+        assert_eq!(storage.size().unwrap(), 12);
+        
+        storage.write(b"()").unwrap();
+        assert_eq!(storage.read().unwrap(), b"()");
+        
+        storage.delete().unwrap();
+        assert_eq!(storage.size().unwrap(), 0);
+        storage.delete().unwrap();
+    }
+    
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryStorage::default();
+        assert!(storage.read().is_err());
+        assert_eq!(storage.size().unwrap(), 0);
+        
+        storage.write(b"(secrets:{})").unwrap();
+        assert_eq!(storage.read().unwrap(), b"(secrets:{})");
+        assert_eq!(storage.size().unwrap(), 12);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+/// One field prompted for by a template
+#[derive(Debug)]
+pub struct TemplateField {
+    pub name: &'static str,
+    /// Masked at the prompt
+    pub secret: bool,
+    pub default: Option<&'static str>,
+}
+
+/// A built-in shape for a multi-field credential, stored as one JSON secret
+#[derive(Debug)]
+pub struct Template {
+    pub name: &'static str,
+    pub fields: &'static [TemplateField],
+}
+
+const fn field(name: &'static str) -> TemplateField {
+    TemplateField { name, secret: false, default: None }
+}
+
+const fn secret_field(name: &'static str) -> TemplateField {
+    TemplateField { name, secret: true, default: None }
+}
+
+const fn field_with_default(name: &'static str, default: &'static str) -> TemplateField {
+    TemplateField { name, secret: false, default: Some(default) }
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "database",
+        fields: &[
+            field("host"),
+            field_with_default("port", "5432"),
+            field("user"),
+            secret_field("password"),
+            field("database"),
+        ],
+    },
+    Template {
+        name: "aws",
+        fields: &[
+            field("access_key_id"),
+            secret_field("secret_access_key"),
+            field_with_default("region", "us-east-1"),
+        ],
+    },
+    Template {
+        name: "oauth",
+        fields: &[
+            field("client_id"),
+            secret_field("client_secret"),
+            field("token_url"),
+        ],
+    },
+];
+
+pub fn find_template(name: &str) -> Result<&'static Template> {
+    TEMPLATES
+        .iter()
+        .find(|template| template.name == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = TEMPLATES.iter().map(|template| template.name).collect();
+            anyhow!("Unknown template '{}'. Available templates: {}", name, names.join(", "))
+        })
+}
+
+impl Template {
+    /// Encode one value per field, in field order, as a JSON object. Every
+    /// field must be filled in so `get --field` can rely on it.
+    pub fn to_json(&self, values: &[String]) -> Result<String> {
+        if values.len() != self.fields.len() {
+            return Err(anyhow!("Template '{}' needs {} values, got {}", self.name, self.fields.len(), values.len()));
+        }
+        
+        let mut object = Map::new();
+        for (field, value) in self.fields.iter().zip(values) {
+            if value.is_empty() {
+                return Err(anyhow!("Field '{}' cannot be empty", field.name));
+            }
+            object.insert(field.name.to_string(), Value::String(value.clone()));
+        }
+        
+        Ok(Value::Object(object).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::json_field;
+    
+    #[test]
+    fn test_find_template() {
+        assert_eq!(find_template("aws").unwrap().fields.len(), 3);
+        let err = find_template("ftp").unwrap_err().to_string();
+        assert!(err.contains("database, aws, oauth"));
+    }
+    
+    #[test]
+    fn test_template_to_json() {
+        let template = find_template("database").unwrap();
+        let values: Vec<String> = ["db.internal", "5432", "app", "s3cr3t, \"quoted\"", "orders"]
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        
+        let json = template.to_json(&values).unwrap();
+        assert_eq!(json_field(&json, "password").unwrap(), "s3cr3t, \"quoted\"");
+        assert_eq!(json_field(&json, "port").unwrap(), "5432");
+        
+        assert!(template.to_json(&values[..2]).is_err());
+        let mut missing = values.clone();
+        missing[2].clear();
+        assert!(template.to_json(&missing).is_err());
+    }
+}
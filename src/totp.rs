@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+use crate::utils::secure_compare;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const SECRET_LEN: usize = 20;
+const DIGITS: u32 = 6;
+const STEP_SECONDS: u64 = 30;
+/// Number of time steps either side of now that are still accepted
+const SKEW_STEPS: u64 = 1;
+
+/// Generate a new random TOTP secret, base32 encoded for authenticator apps
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    encode_base32(&secret)
+}
+
+/// Build the `otpauth://` URI understood by authenticator apps
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/SentinelVault:{}?secret={}&issuer=SentinelVault&digits={}&period={}",
+        account, secret, DIGITS, STEP_SECONDS
+    )
+}
+
+/// Check a code against the current time step and its neighbours
+pub fn verify_code(secret: &str, code: &str, unix_time: u64) -> Result<bool> {
+    let key = decode_base32(secret)?;
+    let code = code.trim();
+    let counter = unix_time / STEP_SECONDS;
+    
+    let mut matched = false;
+    for step in counter.saturating_sub(SKEW_STEPS)..=counter + SKEW_STEPS {
+        let expected = format!("{:0width$}", hotp(&key, step)?, width = DIGITS as usize);
+        matched |= secure_compare(&expected, code);
+    }
+    
+    Ok(matched)
+}
+
+/// RFC 4226 HOTP value for a counter
+fn hotp(key: &[u8], counter: u64) -> Result<u32> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key)
+        .map_err(|e| anyhow!("Invalid TOTP key: {}", e))?;
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    
+    Ok(binary % 10u32.pow(DIGITS))
+}
+
+fn encode_base32(data: &[u8]) -> String {
+    let mut output = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    
+    output
+}
+
+fn decode_base32(data: &str) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    
+    for c in data.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())
+            .ok_or_else(|| anyhow!("Invalid base32 character in TOTP secret"))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // RFC 6238 SHA-1 test secret "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    
+    #[test]
+    fn test_base32_roundtrip() {
+        assert_eq!(encode_base32(b"12345678901234567890"), RFC_SECRET);
+        assert_eq!(decode_base32(RFC_SECRET).unwrap(), b"12345678901234567890");
+        assert!(decode_base32("not-base32!").is_err());
+    }
+    
+    #[test]
+    fn test_rfc6238_vectors() {
+        assert!(verify_code(RFC_SECRET, "287082", 59).unwrap());
+        assert!(verify_code(RFC_SECRET, "081804", 1111111109).unwrap());
+        assert!(verify_code(RFC_SECRET, "050471", 1111111111).unwrap());
+        assert!(!verify_code(RFC_SECRET, "000000", 59).unwrap());
+    }
+    
+    #[test]
+    fn test_skew_window() {
+        // Code for T=59 is still accepted one step later, but not two
+        assert!(verify_code(RFC_SECRET, "287082", 59 + 30).unwrap());
+        assert!(!verify_code(RFC_SECRET, "287082", 59 + 60).unwrap());
+    }
+}
@@ -1,12 +1,33 @@
 use anyhow::{anyhow, Result};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use zeroize::Zeroizing;
 
-/// Get the vault directory path (~/.sentinelvault)
+use crate::config::Config;
+
+/// Get the vault directory path (~/.sentinelvault unless `SENTINELVAULT_DIR`
+/// or `vault_dir` in the config file says otherwise)
 pub fn get_vault_dir() -> Result<PathBuf> {
     let home_dir = dirs::home_dir()
         .ok_or_else(|| anyhow!("Could not determine home directory"))?;
     
-    Ok(home_dir.join(".sentinelvault"))
+    let vault_dir = match Config::load()?.vault_dir {
+        Some(dir) => home_dir.join(expand_home(&dir, &home_dir)),
+        None => home_dir.join(".sentinelvault"),
+    };
+    log::debug!("Vault directory: {}", vault_dir.display());
+    Ok(vault_dir)
+}
+
+/// Replace a leading `~` in `path` with `home`
+pub fn expand_home(path: &Path, home: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home.join(rest),
+        Err(_) => path.to_path_buf(),
+    }
 }
 
 /// Get the vault file path (~/.sentinelvault/vault.ron)
@@ -34,7 +55,48 @@ pub fn format_bytes(bytes: u64) -> String {
     format!("{:.1} {}", size, UNITS[unit_index])
 }
 
-/// Sanitize secret names to prevent path traversal
+/// Format a duration as days, hours and minutes, e.g. "2h 15m". Durations under
+/// a minute are shown in seconds, and negative ones as "0s".
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let total = duration.num_seconds().max(0);
+    if total < 60 {
+        return format!("{}s", total);
+    }
+    
+    let (days, hours, minutes) = (total / 86_400, total % 86_400 / 3_600, total % 3_600 / 60);
+    let parts: Vec<String> = [(days, "d"), (hours, "h"), (minutes, "m")]
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+    
+    parts.join(" ")
+}
+
+/// Describe how long ago something happened, e.g. "5m ago"
+pub fn format_ago(elapsed: chrono::Duration) -> String {
+    if elapsed.num_days() > 0 {
+        format!("{}d ago", elapsed.num_days())
+    } else if elapsed.num_hours() > 0 {
+        format!("{}h ago", elapsed.num_hours())
+    } else if elapsed.num_minutes() > 0 {
+        format!("{}m ago", elapsed.num_minutes())
+    } else {
+        "just now".to_string()
+    }
+}
+
+/// Secret size limit for vaults that don't configure one
+pub const DEFAULT_MAX_SECRET_SIZE: usize = 10_000;
+
+/// Largest size limit a vault may be configured with (1 MiB)
+pub const MAX_SECRET_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Separates namespaces in secret names, e.g. `prod/db/password`
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// Sanitize secret names to prevent path traversal. `/` separates namespaces,
+/// but every segment must be non-empty and must not be `.`, `..` or reserved.
 pub fn sanitize_secret_name(name: &str) -> Result<String> {
     if name.is_empty() {
         return Err(anyhow!("Secret name cannot be empty"));
@@ -45,29 +107,214 @@ pub fn sanitize_secret_name(name: &str) -> Result<String> {
     }
     
     // Check for invalid characters
-    let invalid_chars = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0'];
+    let invalid_chars = ['\\', ':', '*', '?', '"', '<', '>', '|', '\0'];
     if name.chars().any(|c| invalid_chars.contains(&c) || c.is_control()) {
         return Err(anyhow!("Secret name contains invalid characters"));
     }
     
-    // Prevent reserved names
+    // Prevent reserved names, and `..` segments climbing out of a namespace
     let reserved_names = [".", "..", "CON", "PRN", "AUX", "NUL"];
-    let upper_name = name.to_uppercase();
-    if reserved_names.contains(&upper_name.as_str()) {
-        return Err(anyhow!("Secret name is reserved"));
+    for segment in name.split(NAMESPACE_SEPARATOR) {
+        if segment.is_empty() {
+            return Err(anyhow!("Secret name has an empty namespace segment"));
+        }
+        if reserved_names.contains(&segment.to_uppercase().as_str()) {
+            return Err(anyhow!("Secret name is reserved"));
+        }
     }
     
     Ok(name.to_string())
 }
 
+/// The environment variable `get --env-fallback` reads for a secret: the
+/// name uppercased, with anything but letters and digits replaced by `_`
+/// (`prod/db-pass` becomes `PROD_DB_PASS`)
+pub fn env_var_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Whether `name` is inside `namespace` (or is `namespace` itself). Matches
+/// whole segments, so `prod` covers `prod/db` but not `production/db`.
+pub fn in_namespace(name: &str, namespace: &str) -> bool {
+    let namespace = namespace.trim_end_matches(NAMESPACE_SEPARATOR);
+    namespace.is_empty()
+        || name == namespace
+        || name.strip_prefix(namespace).is_some_and(|rest| rest.starts_with(NAMESPACE_SEPARATOR))
+}
+
+/// Render sorted secret names as an indented tree, one line per namespace
+/// (suffixed with `/`) and per secret
+pub fn render_tree(names: &[String]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut previous: Vec<&str> = Vec::new();
+    
+    for name in names {
+        let segments: Vec<&str> = name.split(NAMESPACE_SEPARATOR).collect();
+        let (leaf, namespaces) = segments.split_last().expect("split yields at least one segment");
+        
+        // Namespaces shared with the previous name are already printed
+        let shared = namespaces
+            .iter()
+            .zip(previous.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        for (depth, namespace) in namespaces.iter().enumerate().skip(shared) {
+            lines.push(format!("{}{}/", "  ".repeat(depth), namespace));
+        }
+        lines.push(format!("{}{}", "  ".repeat(namespaces.len()), leaf));
+        
+        previous = namespaces.to_vec();
+    }
+    
+    lines
+}
+
+/// Widest a table cell gets before it's cut short with `…`
+pub const MAX_CELL_WIDTH: usize = 40;
+
+/// Lay rows out in columns padded to the widest cell, under a header and a
+/// rule. Columns flagged in `right_align` are right-aligned, which suits
+/// times and counts.
+pub fn render_table(headers: &[&str], rows: &[Vec<String>], right_align: &[bool]) -> Vec<String> {
+    let truncate = |cell: &str| -> String {
+        if cell.chars().count() <= MAX_CELL_WIDTH {
+            return cell.to_string();
+        }
+        let mut cut: String = cell.chars().take(MAX_CELL_WIDTH - 1).collect();
+        cut.push('…');
+        cut
+    };
+    let header: Vec<String> = headers.iter().map(|cell| cell.to_string()).collect();
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(|cell| truncate(cell)).collect()).collect();
+    
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    
+    let line = |row: &[String]| -> String {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| match right_align.get(i) {
+                Some(true) => format!("{:>width$}", cell, width = width),
+                _ => format!("{:<width$}", cell, width = width),
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    
+    let mut lines = vec![line(&header)];
+    lines.push(widths.iter().map(|&width| "─".repeat(width)).collect::<Vec<_>>().join("  "));
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines
+}
+
+/// Compile a name policy. The pattern must match the whole name, not just part of it.
+pub fn compile_name_pattern(pattern: &str) -> Result<regex::Regex> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|e| anyhow!("Invalid name pattern '{}': {}", pattern, e))
+}
+
+/// Check a name against the vault's name policy. This only ever narrows what
+/// `sanitize_secret_name` allows, which must still be applied.
+pub fn check_name_pattern(name: &str, pattern: &str) -> Result<()> {
+    if !compile_name_pattern(pattern)?.is_match(name) {
+        return Err(anyhow!("Secret name '{}' does not match the vault's name policy '{}'", name, pattern));
+    }
+    
+    Ok(())
+}
+
+/// Match a name against a glob pattern supporting `*` and `?` wildcards
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Pick out the environment variables matching any of the comma-separated
+/// glob patterns, keyed by secret name: `DB_PASSWORD` becomes `db_password`.
+/// Variables that aren't valid UTF-8 are skipped.
+pub fn select_env_vars(
+    patterns: &str,
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Vec<(String, Zeroizing<String>)>> {
+    let patterns: Vec<&str> = patterns.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+    if patterns.is_empty() {
+        return Err(anyhow!("No environment variable patterns given"));
+    }
+    
+    let mut selected: BTreeMap<String, (String, Zeroizing<String>)> = BTreeMap::new();
+    for (var, value) in vars {
+        let (Ok(var), Ok(value)) = (var.into_string(), value.into_string()) else {
+            continue;
+        };
+        if !patterns.iter().any(|pattern| glob_match(pattern, &var)) {
+            continue;
+        }
+        
+        let name = var.to_lowercase();
+        if let Some((other, _)) = selected.get(&name) {
+            return Err(anyhow!("Environment variables {} and {} would both be stored as '{}'", other, var, name));
+        }
+        selected.insert(name, (var, Zeroizing::new(value)));
+    }
+    
+    Ok(selected.into_iter().map(|(name, (_, value))| (name, value)).collect())
+}
+
+/// Check secret tags and return them sorted without duplicates. A tag is
+/// a single word: no whitespace or commas.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut normalized = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+            return Err(anyhow!("Invalid tag '{}': tags are single words without commas", tag));
+        }
+        normalized.push(tag.to_string());
+    }
+    
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
 /// Validate secret value
-pub fn validate_secret_value(value: &str) -> Result<()> {
+pub fn validate_secret_value(value: &str, max_size: usize) -> Result<()> {
     if value.is_empty() {
         return Err(anyhow!("Secret value cannot be empty"));
     }
     
-    if value.len() > 10_000 {
-        return Err(anyhow!("Secret value too long (max 10,000 characters)"));
+    if value.len() > max_size {
+        return Err(anyhow!("Secret value too long (max {} bytes)", max_size));
     }
     
     // Check for null bytes
@@ -78,6 +325,312 @@ pub fn validate_secret_value(value: &str) -> Result<()> {
     Ok(())
 }
 
+/// Extract `field` from a JSON object. Dots address nested objects
+/// (`db.password`). Strings are returned unquoted, other values as JSON.
+pub fn json_field(json: &str, field: &str) -> Result<String> {
+    let root: serde_json::Value = serde_json::from_str(json)
+        .map_err(|_| anyhow!("Secret value is not valid JSON"))?;
+    
+    let mut value = &root;
+    for key in field.split('.') {
+        value = value
+            .get(key)
+            .ok_or_else(|| anyhow!("Field '{}' not found", field))?;
+    }
+    
+    match value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Read a secret value piped in, as in `echo "$TOKEN" | sentinel add name --stdin`.
+/// A single trailing newline, the one `echo` adds, is dropped.
+pub fn read_piped_value(mut reader: impl std::io::Read) -> Result<Zeroizing<String>> {
+    let mut bytes = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut bytes)?;
+    let text = std::str::from_utf8(&bytes).map_err(|_| anyhow!("Secret value on stdin is not valid UTF-8"))?;
+    let text = text.strip_suffix('\n').map(|text| text.strip_suffix('\r').unwrap_or(text)).unwrap_or(text);
+    
+    Ok(Zeroizing::new(text.to_string()))
+}
+
+/// Split a line into words like a shell would: whitespace separates words,
+/// quotes group them, and a backslash escapes the next character outside single quotes
+pub fn split_command_line(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or_else(|| anyhow!("Trailing backslash"))?;
+                current.push(escaped);
+                in_word = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quote"));
+    }
+    if in_word {
+        words.push(current);
+    }
+    
+    Ok(words)
+}
+
+/// Minimum length for the master password and for stored values to pass the strength check
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+const COMMON_WORDS: &[&str] = &[
+    "password", "passw0rd", "secret", "admin", "root", "letmein", "welcome", "qwerty",
+    "monkey", "dragon", "master", "login", "changeme", "default", "iloveyou", "sunshine",
+    "football", "baseball", "shadow", "trustno1", "abc123", "123456", "12345678",
+];
+
+/// List the reasons a password or secret value looks weak. Empty means it passed.
+pub fn password_weaknesses(value: &str) -> Vec<&'static str> {
+    let mut reasons = Vec::new();
+    
+    if value.chars().count() < MIN_PASSWORD_LEN {
+        reasons.push("too short");
+    }
+    if value.chars().all(|c| c.is_ascii_digit()) {
+        reasons.push("digits only");
+    } else if value.chars().all(|c| c.is_lowercase()) {
+        reasons.push("all lowercase");
+    }
+    
+    // Catch the usual variations like "Password1!" as well
+    let stem = value
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation())
+        .to_lowercase();
+    if COMMON_WORDS.contains(&stem.as_str()) || COMMON_WORDS.contains(&value.to_lowercase().as_str()) {
+        reasons.push("common dictionary word");
+    }
+    
+    reasons
+}
+
+/// Write a file readable only by the owner (0600 on Unix), creating parent
+/// directories. Refuses to replace an existing file unless `overwrite` is set.
+pub fn write_private_file(path: &Path, contents: &[u8], overwrite: bool) -> Result<()> {
+    if path.exists() && !overwrite {
+        return Err(anyhow!("{} already exists. Use --force to overwrite.", path.display()));
+    }
+    
+    let mut file = open_private_file(path)?;
+    file.write_all(contents)?;
+    
+    Ok(())
+}
+
+/// Replace `path` with `contents` (0600 on Unix) so that a crash leaves
+/// either the old file or the new one. The bytes are written and synced to
+/// `<path>.tmp` first, then renamed over `path`.
+pub fn replace_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp_name = path.file_name().ok_or_else(|| anyhow!("{} is not a file path", path.display()))?.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    
+    let mut file = open_private_file(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(anyhow!("Failed to replace {}: {}", path.display(), e));
+    }
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::File::open(parent)?.sync_all()?;
+    }
+    
+    Ok(())
+}
+
+/// Open `path` for writing from scratch, owner-only, creating parent directories
+fn open_private_file(path: &Path) -> Result<fs::File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    
+    let file = options.open(path)?;
+    // `mode` only applies to newly created files
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    
+    Ok(file)
+}
+
+/// Create a directory (and its parents) and restrict it to the owner (0700 on Unix).
+/// The mode is applied even if the directory already existed.
+pub fn create_private_dir(path: &Path) -> Result<()> {
+    fs::create_dir_all(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
+    }
+    
+    Ok(())
+}
+
+/// Permission bits that give group or other users access to `path`, if any.
+/// Always `None` on platforms without Unix modes.
+pub fn permissive_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some(mode);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    
+    None
+}
+
+/// Print a warning to stderr if `path` is accessible to other users
+pub fn warn_if_permissive(path: &Path) {
+    if let Some(mode) = permissive_mode(path) {
+        let wanted = if path.is_dir() { "700" } else { "600" };
+        eprintln!(
+            "Warning: {} has permissions {:o} and may be readable by other users. Run: chmod {} {}",
+            path.display(), mode, wanted, path.display()
+        );
+    }
+}
+
+/// ANSI sequence that moves the cursor up `lines` lines and clears everything below it
+pub fn clear_lines_sequence(lines: usize) -> String {
+    format!("\x1b[{}F\x1b[J", lines)
+}
+
+/// Save the window title on the terminal's title stack, replace it with a
+/// fixed one and turn bracketed paste off
+const GUARD_START: &str = "\x1b[22;0t\x1b]0;sentinel\x07\x1b[?2004l";
+/// Put the saved title back. Bracketed paste stays off: shells that use it
+/// turn it back on at their next prompt.
+const GUARD_END: &str = "\x1b[23;0t";
+
+/// Keeps a revealed secret out of the window title and bracketed paste
+/// handling while it's alive, for `get --paranoid`. The title is restored
+/// when it's dropped.
+pub struct TerminalGuard<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TerminalGuard<W> {
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(GUARD_START.as_bytes())?;
+        out.flush()?;
+        Ok(Self { out })
+    }
+}
+
+impl<W: Write> Drop for TerminalGuard<W> {
+    fn drop(&mut self) {
+        let _ = self.out.write_all(GUARD_END.as_bytes());
+        let _ = self.out.flush();
+    }
+}
+
+/// Wait for `delay`, then erase `text` that was just printed to stdout.
+/// Does nothing when stdout is not a terminal, since piped output can't be taken back.
+pub fn clear_printed_text(text: &str, delay: std::time::Duration) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return Ok(());
+    }
+    
+    std::thread::sleep(delay);
+    let lines = text.lines().count().max(1);
+    write!(stdout, "{}", clear_lines_sequence(lines))?;
+    stdout.flush()?;
+    
+    Ok(())
+}
+
+/// Number a file for multi-part output: `backup.png` becomes `backup-2.png`
+pub fn numbered_path(path: &Path, part: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, part, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, part),
+    };
+    path.with_file_name(file_name)
+}
+
+/// File for a secret in a systemd credentials directory: `dir/<name>`. Only
+/// names that are a single plain file name are accepted, so nothing can be
+/// written outside `dir`.
+pub fn credential_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(file)), None) if file == name => Ok(dir.join(file)),
+        _ => Err(anyhow!(
+            "'{}' is not a valid credential file name (systemd credentials can't contain '/'). Leave it out with --only.",
+            name
+        )),
+    }
+}
+
+/// Ask the user to confirm a destructive action, defaulting to no.
+/// `assume_yes` skips the prompt; without it a non-interactive session is an error.
+pub fn confirm_action(message: &str, assume_yes: bool) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+    
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!("Confirmation required but stdin is not a terminal. Use --force to proceed."));
+    }
+    
+    let confirmed = inquire::Confirm::new(message)
+        .with_default(false)
+        .prompt()?;
+    
+    Ok(confirmed)
+}
+
 /// Secure string comparison to prevent timing attacks
 pub fn secure_compare(a: &str, b: &str) -> bool {
     if a.len() != b.len() {
@@ -111,6 +664,25 @@ mod tests {
         assert_eq!(format_bytes(1048576), "1.0 MB");
     }
     
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::seconds(0)), "0s");
+        assert_eq!(format_duration(chrono::Duration::seconds(59)), "59s");
+        assert_eq!(format_duration(chrono::Duration::seconds(60)), "1m");
+        assert_eq!(format_duration(chrono::Duration::seconds(8_100)), "2h 15m");
+        assert_eq!(format_duration(chrono::Duration::hours(24)), "1d");
+        assert_eq!(format_duration(chrono::Duration::minutes(3 * 1_440 + 61)), "3d 1h 1m");
+        assert_eq!(format_duration(chrono::Duration::seconds(-5)), "0s");
+    }
+    
+    #[test]
+    fn test_format_ago() {
+        assert_eq!(format_ago(chrono::Duration::seconds(30)), "just now");
+        assert_eq!(format_ago(chrono::Duration::minutes(5)), "5m ago");
+        assert_eq!(format_ago(chrono::Duration::minutes(150)), "2h ago");
+        assert_eq!(format_ago(chrono::Duration::days(3)), "3d ago");
+    }
+    
     #[test]
     fn test_sanitize_secret_name() {
         assert!(sanitize_secret_name("valid_name").is_ok());
@@ -118,20 +690,283 @@ mod tests {
         assert!(sanitize_secret_name("valid.name").is_ok());
         
         assert!(sanitize_secret_name("").is_err());
-        assert!(sanitize_secret_name("name/with/slash").is_err());
+        assert!(sanitize_secret_name("prod/db/password").is_ok());
+        assert!(sanitize_secret_name("prod/../password").is_err());
+        assert!(sanitize_secret_name("../password").is_err());
+        assert!(sanitize_secret_name("prod/./password").is_err());
+        assert!(sanitize_secret_name("/password").is_err());
+        assert!(sanitize_secret_name("prod//password").is_err());
+        assert!(sanitize_secret_name("prod/").is_err());
+        assert!(sanitize_secret_name("prod/con").is_err());
         assert!(sanitize_secret_name("name\\with\\backslash").is_err());
         assert!(sanitize_secret_name("CON").is_err());
         assert!(sanitize_secret_name("name\0with\0null").is_err());
     }
     
+    #[test]
+    fn test_env_var_name() {
+        assert_eq!(env_var_name("db-pass"), "DB_PASS");
+        assert_eq!(env_var_name("prod/api.key2"), "PROD_API_KEY2");
+        assert_eq!(env_var_name("GITHUB_TOKEN"), "GITHUB_TOKEN");
+    }
+    
+    #[test]
+    fn test_in_namespace() {
+        assert!(in_namespace("prod/db/password", "prod"));
+        assert!(in_namespace("prod/db/password", "prod/db/"));
+        assert!(in_namespace("prod", "prod"));
+        assert!(!in_namespace("production/db", "prod"));
+        assert!(in_namespace("anything", ""));
+    }
+    
+    #[test]
+    fn test_render_tree() {
+        let names: Vec<String> = ["api_key", "prod/db/password", "prod/db/user", "prod/token", "staging/db/password"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        
+        assert_eq!(render_tree(&names), vec![
+            "api_key",
+            "prod/",
+            "  db/",
+            "    password",
+            "    user",
+            "  token",
+            "staging/",
+            "  db/",
+            "    password",
+        ]);
+    }
+    
+    #[test]
+    fn test_render_table() {
+        let rows = vec![
+            vec!["api_key".to_string(), "-".to_string(), "3".to_string()],
+            vec!["a".repeat(50), "2026-01-01 00:00".to_string(), "12".to_string()],
+        ];
+        let lines = render_table(&["Name", "Expires", "Reads"], &rows, &[false, true, true]);
+        
+        let name_width = MAX_CELL_WIDTH;
+        assert_eq!(lines[0], format!("{:<name_width$}  {:>16}  Reads", "Name", "Expires"));
+        assert_eq!(lines[1], format!("{}  {}  {}", "─".repeat(name_width), "─".repeat(16), "─".repeat(5)));
+        assert_eq!(lines[2], format!("{:<name_width$}  {:>16}      3", "api_key", "-"));
+        assert_eq!(lines[3], format!("{}…  2026-01-01 00:00     12", "a".repeat(name_width - 1)));
+    }
+    
+    #[test]
+    fn test_name_pattern() {
+        assert!(check_name_pattern("db_password", "[a-z0-9_.-]+").is_ok());
+        assert!(check_name_pattern("DB_PASSWORD", "[a-z0-9_.-]+").is_err());
+        // Anchored, so a partial match is not enough
+        assert!(check_name_pattern("db password", "[a-z_]+").is_err());
+        assert!(check_name_pattern("svc.key", "svc\\..+|other").is_ok());
+        assert!(compile_name_pattern("[unclosed").is_err());
+    }
+    
     #[test]
     fn test_validate_secret_value() {
-        assert!(validate_secret_value("valid_value").is_ok());
-        assert!(validate_secret_value("").is_err());
-        assert!(validate_secret_value("value\0with\0null").is_err());
+        assert!(validate_secret_value("valid_value", DEFAULT_MAX_SECRET_SIZE).is_ok());
+        assert!(validate_secret_value("", DEFAULT_MAX_SECRET_SIZE).is_err());
+        assert!(validate_secret_value("value\0with\0null", DEFAULT_MAX_SECRET_SIZE).is_err());
         
         let long_value = "a".repeat(10_001);
-        assert!(validate_secret_value(&long_value).is_err());
+        assert!(validate_secret_value(&long_value, DEFAULT_MAX_SECRET_SIZE).is_err());
+        assert!(validate_secret_value(&long_value, 20_000).is_ok());
+    }
+    
+    #[test]
+    fn test_expand_home() {
+        let home = Path::new("/home/me");
+        assert_eq!(expand_home(Path::new("~"), home), home);
+        assert_eq!(expand_home(Path::new("~/vaults/work"), home), home.join("vaults/work"));
+        assert_eq!(expand_home(Path::new("~other/vault"), home), Path::new("~other/vault"));
+        assert_eq!(expand_home(Path::new("/srv/vault"), home), Path::new("/srv/vault"));
+        assert_eq!(expand_home(Path::new("vaults"), home), Path::new("vaults"));
+    }
+    
+    #[test]
+    fn test_normalize_tags() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        assert_eq!(normalize_tags(&tags(&["prod", " db", "prod"])).unwrap(), tags(&["db", "prod"]));
+        assert!(normalize_tags(&[]).unwrap().is_empty());
+        assert!(normalize_tags(&tags(&[""])).is_err());
+        assert!(normalize_tags(&tags(&["two words"])).is_err());
+    }
+    
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("db-*", "db-password"));
+        assert!(glob_match("db-*", "db-"));
+        assert!(glob_match("*_token", "github_token"));
+        assert!(glob_match("key?", "key1"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("a*b*c", "a-x-b-y-c"));
+        
+        assert!(!glob_match("db-*", "prod-db-password"));
+        assert!(!glob_match("key?", "key"));
+        assert!(!glob_match("key?", "key12"));
+        assert!(!glob_match("exact", "exact_not"));
+    }
+    
+    #[test]
+    fn test_select_env_vars() {
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(OsString, OsString)> {
+            pairs.iter().map(|(k, v)| (k.into(), v.into())).collect()
+        };
+        let env = vars(&[("AWS_ACCESS_KEY_ID", "AKIA"), ("AWS_REGION", "eu-west-1"), ("DB_PASSWORD", "hunter2"), ("HOME", "/root")]);
+        
+        let selected = select_env_vars("AWS_*, DB_PASSWORD", env.clone()).unwrap();
+        let names: Vec<&str> = selected.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["aws_access_key_id", "aws_region", "db_password"]);
+        assert_eq!(selected[2].1.as_str(), "hunter2");
+        
+        assert!(select_env_vars("NOPE_*", env.clone()).unwrap().is_empty());
+        assert!(select_env_vars(" , ", env).is_err());
+        assert!(select_env_vars("*", vars(&[("TOKEN", "a"), ("token", "b")])).is_err());
+    }
+    
+    #[test]
+    fn test_write_private_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join("backup.ron");
+        
+        write_private_file(&path, b"first", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        
+        assert!(write_private_file(&path, b"second", false).is_err());
+        write_private_file(&path, b"second", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+    
+    #[test]
+    fn test_replace_private_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.ron");
+        
+        replace_private_file(&path, b"first").unwrap();
+        // Left behind by a write that never finished
+        fs::write(temp_dir.path().join("vault.ron.tmp"), b"fir").unwrap();
+        replace_private_file(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!temp_dir.path().join("vault.ron.tmp").exists());
+        
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_private_dir_and_permissive_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("vault");
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(permissive_mode(&dir), Some(0o755));
+        
+        create_private_dir(&dir).unwrap();
+        assert_eq!(permissive_mode(&dir), None);
+        
+        let file = dir.join("vault.ron");
+        fs::write(&file, "()").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(permissive_mode(&file), Some(0o644));
+        
+        write_private_file(&file, b"()", true).unwrap();
+        assert_eq!(permissive_mode(&file), None);
+    }
+    
+    #[test]
+    fn test_read_piped_value() {
+        assert_eq!(read_piped_value(&b"token\n"[..]).unwrap().as_str(), "token");
+        assert_eq!(read_piped_value(&b"token\r\n"[..]).unwrap().as_str(), "token");
+        assert_eq!(read_piped_value(&b"line one\nline two\n\n"[..]).unwrap().as_str(), "line one\nline two\n");
+        assert_eq!(read_piped_value(&b"  no newline "[..]).unwrap().as_str(), "  no newline ");
+        assert!(read_piped_value(&b"\xff\xfe"[..]).is_err());
+    }
+    
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line("get api_key\n").unwrap(), vec!["get", "api_key"]);
+        assert_eq!(
+            split_command_line(r#"add "my key" --value 'a "quoted" value'"#).unwrap(),
+            vec!["add", "my key", "--value", "a \"quoted\" value"]
+        );
+        assert_eq!(split_command_line(r"add x --value a\ b").unwrap(), vec!["add", "x", "--value", "a b"]);
+        assert_eq!(split_command_line(r#"add x --value """#).unwrap(), vec!["add", "x", "--value", ""]);
+        assert!(split_command_line("   ").unwrap().is_empty());
+        assert!(split_command_line("get 'open").is_err());
+    }
+    
+    #[test]
+    fn test_json_field() {
+        let creds = r#"{"username":"admin","port":5432,"db":{"password":"hunter2"}}"#;
+        assert_eq!(json_field(creds, "username").unwrap(), "admin");
+        assert_eq!(json_field(creds, "port").unwrap(), "5432");
+        assert_eq!(json_field(creds, "db.password").unwrap(), "hunter2");
+        assert!(json_field(creds, "missing").is_err());
+        assert!(json_field(creds, "db.missing").is_err());
+        assert!(json_field("not json", "username").is_err());
+    }
+    
+    #[test]
+    fn test_password_weaknesses() {
+        assert!(password_weaknesses("c0rrect-Horse-battery").is_empty());
+        assert_eq!(password_weaknesses("abc"), vec!["too short", "all lowercase"]);
+        assert_eq!(password_weaknesses("12345678"), vec!["digits only", "common dictionary word"]);
+        assert_eq!(password_weaknesses("Password1!"), vec!["common dictionary word"]);
+        assert_eq!(password_weaknesses("longlowercasephrase"), vec!["all lowercase"]);
+    }
+    
+    #[test]
+    fn test_clear_lines_sequence() {
+        assert_eq!(clear_lines_sequence(1), "\x1b[1F\x1b[J");
+        assert_eq!(clear_lines_sequence(3), "\x1b[3F\x1b[J");
+    }
+    
+    #[test]
+    fn test_terminal_guard() {
+        let mut out = Vec::new();
+        {
+            let guard = TerminalGuard::new(&mut out).unwrap();
+            guard.out.write_all(b"value\n").unwrap();
+        }
+        assert_eq!(out, b"\x1b[22;0t\x1b]0;sentinel\x07\x1b[?2004lvalue\n\x1b[23;0t");
+    }
+    
+    #[test]
+    fn test_numbered_path() {
+        assert_eq!(numbered_path(Path::new("/tmp/backup.png"), 2), Path::new("/tmp/backup-2.png"));
+        assert_eq!(numbered_path(Path::new("backup"), 1), Path::new("backup-1"));
+    }
+    
+    #[test]
+    fn test_credential_path() {
+        let dir = Path::new("/run/creds");
+        assert_eq!(credential_path(dir, "db_password").unwrap(), Path::new("/run/creds/db_password"));
+        assert_eq!(credential_path(dir, "api.key").unwrap(), Path::new("/run/creds/api.key"));
+        
+        for name in ["prod/db", "../escape", "..", ".", "/etc/passwd", "trailing/", ""] {
+            assert!(credential_path(dir, name).is_err(), "{}", name);
+        }
+    }
+    
+    #[test]
+    fn test_confirm_action_assume_yes() {
+        assert!(confirm_action("Remove secret 'x'?", true).unwrap());
     }
     
     #[test]
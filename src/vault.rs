@@ -1,14 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use inquire::{Password, PasswordDisplayMode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
-use crate::crypto::{CryptoEngine, EncryptedData, SecretKey};
-use crate::identity::{authenticate, prompt_new_master_password, Identity};
+use crate::batch::BatchOp;
+use crate::crypto::{derive_key_from_password, encode_hex, generate_key, generate_salt, unwrap_key, wrap_key, CryptoEngine, EncryptedData, KdfAlgorithm, KdfParams, SecretKey, SecretString};
+use crate::error::VaultError;
+use crate::identity::{authenticate, authenticate_identity, prompt_backup_passphrase, prompt_master_password, prompt_new_master_password, prompt_new_pin, read_keyfile, register_security_key, AuthOptions, Fido2Binding, Identity, MACHINE_ID_ENV};
 use crate::lease::{parse_duration, Lease, LeaseManager};
-use crate::utils::{get_vault_path, sanitize_secret_name, validate_secret_value, format_bytes};
+use crate::migrations::{self, since_layout_3, since_layout_4, with_binary_layout, BINARY_LAYOUT, CURRENT_VERSION};
+use crate::profile;
+use crate::storage::{FileStorage, MemoryStorage, VaultStorage};
+use crate::totp;
+use crate::utils::{check_name_pattern, compile_name_pattern, glob_match, json_field, normalize_tags, password_weaknesses, sanitize_secret_name, select_env_vars, validate_secret_value, format_bytes, DEFAULT_MAX_SECRET_SIZE, MAX_SECRET_SIZE_LIMIT};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SecretEntry {
@@ -17,6 +25,15 @@ pub struct SecretEntry {
     pub updated_at: DateTime<Utc>,
     pub access_count: u64,
     pub last_accessed: Option<DateTime<Utc>>,
+    /// Reveal only after re-authenticating; enforced by the CLI
+    #[serde(default)]
+    pub protected: bool,
+    /// Labels for grouping secrets, e.g. by environment; sorted and unique
+    #[serde(default, deserialize_with = "since_layout_4")]
+    pub tags: Vec<String>,
+    /// What the secret is for, in plain text. Stored unencrypted, like the name.
+    #[serde(default, deserialize_with = "since_layout_4")]
+    pub description: Option<String>,
 }
 
 impl SecretEntry {
@@ -28,6 +45,9 @@ impl SecretEntry {
             updated_at: now,
             access_count: 0,
             last_accessed: None,
+            protected: false,
+            tags: Vec::new(),
+            description: None,
         }
     }
     
@@ -35,6 +55,11 @@ impl SecretEntry {
         self.access_count += 1;
         self.last_accessed = Some(Utc::now());
     }
+    
+    /// When the secret was last read, or created if it never has been
+    pub fn last_used(&self) -> DateTime<Utc> {
+        self.last_accessed.unwrap_or(self.created_at)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +68,38 @@ pub struct VaultData {
     pub lease_manager: LeaseManager,
     pub created_at: DateTime<Utc>,
     pub version: String,
+    /// Random data key, encrypted under the password-derived key. `None` for
+    /// vaults created before envelope encryption, which are migrated on open.
+    #[serde(default)]
+    pub wrapped_key: Option<EncryptedData>,
+    /// Largest secret value accepted by `add`, in bytes
+    #[serde(default = "default_max_secret_size")]
+    pub max_secret_size: usize,
+    /// Read-only mode: secrets can be read but not added, removed or re-leased
+    #[serde(default)]
+    pub locked: bool,
+    /// Latest time the vault was saved, to notice the clock being set back
+    #[serde(default)]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Regex that new secret names must match in full, if the team wants one
+    #[serde(default)]
+    pub name_pattern: Option<String>,
+    /// Most secrets the vault may hold, unlimited if `None`
+    #[serde(default)]
+    pub max_secrets: Option<usize>,
+    /// Other names for secrets, from each alias to the secret it reads
+    #[serde(default, deserialize_with = "since_layout_3")]
+    pub aliases: HashMap<String, String>,
+}
+
+/// Digest bytes kept by [`VaultData::fingerprint`], printed as hex
+const FINGERPRINT_BYTES: usize = 8;
+
+/// Backward clock adjustments smaller than this (e.g. NTP corrections) are ignored
+const CLOCK_SKEW_TOLERANCE_MINUTES: i64 = 5;
+
+fn default_max_secret_size() -> usize {
+    DEFAULT_MAX_SECRET_SIZE
 }
 
 impl Default for VaultData {
@@ -51,104 +108,1202 @@ impl Default for VaultData {
             secrets: HashMap::new(),
             lease_manager: LeaseManager::new(),
             created_at: Utc::now(),
-            version: "0.1.0".to_string(),
+            version: CURRENT_VERSION.to_string(),
+            wrapped_key: None,
+            max_secret_size: DEFAULT_MAX_SECRET_SIZE,
+            locked: false,
+            last_seen: None,
+            name_pattern: None,
+            max_secrets: None,
+            aliases: HashMap::new(),
         }
     }
 }
 
+impl VaultData {
+    /// How far `now` is behind the last save, if more than the tolerance.
+    /// A clock set back would otherwise keep expired leases alive.
+    pub fn clock_skew(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let skew = self.last_seen? - now;
+        (skew > Duration::minutes(CLOCK_SKEW_TOLERANCE_MINUTES)).then_some(skew)
+    }
+    
+    /// Record the current time, never moving `last_seen` backward
+    fn touch(&mut self) {
+        let now = Utc::now();
+        self.last_seen = Some(self.last_seen.map_or(now, |last| last.max(now)));
+    }
+    
+    /// Remove expired secrets and leases whose secret no longer exists.
+    /// With `dry_run`, only report what would be removed.
+    pub fn prune(&mut self, dry_run: bool) -> PruneReport {
+        let expired = self.expired_secrets();
+        let mut orphaned: Vec<String> = self
+            .lease_manager
+            .tracked_secrets()
+            .into_iter()
+            .filter(|name| !self.secrets.contains_key(name))
+            .collect();
+        orphaned.sort();
+        
+        if !dry_run {
+            for name in expired.iter().chain(&orphaned) {
+                self.remove_entry(name);
+            }
+        }
+        
+        PruneReport { expired, orphaned }
+    }
+    
+    /// Remove only the secrets whose lease has expired, returning their names.
+    /// With `dry_run`, only report them.
+    pub fn remove_expired(&mut self, dry_run: bool) -> Vec<String> {
+        let expired = self.expired_secrets();
+        if !dry_run {
+            for name in &expired {
+                self.remove_entry(name);
+            }
+        }
+        
+        expired
+    }
+    
+    /// Names of stored secrets whose lease has expired, sorted
+    fn expired_secrets(&self) -> Vec<String> {
+        let mut expired: Vec<String> = self
+            .lease_manager
+            .get_expired_secrets()
+            .into_iter()
+            .filter(|name| self.secrets.contains_key(name))
+            .collect();
+        expired.sort();
+        expired
+    }
+    
+    /// Short hex digest of every secret's name and last update, never its
+    /// value. Equal for vaults with the same content regardless of order.
+    pub fn fingerprint(&self) -> String {
+        use sha2::{Digest, Sha256};
+        
+        let mut names: Vec<&String> = self.secrets.keys().collect();
+        names.sort();
+        
+        let mut hasher = Sha256::new();
+        for name in names {
+            // Length-prefixed so no two name lists hash the same bytes
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(self.secrets[name].updated_at.to_rfc3339().as_bytes());
+        }
+        
+        encode_hex(&hasher.finalize()[..FINGERPRINT_BYTES])
+    }
+    
+    /// A copy holding only `names`, with their leases, read limits and
+    /// aliases, and the same wrapped key and settings so it can be restored
+    /// or merged
+    pub fn subset(&self, names: &[String]) -> VaultData {
+        let mut subset = self.clone();
+        subset.secrets.retain(|name, _| names.contains(name));
+        subset.lease_manager.retain(|name| names.iter().any(|kept| kept == name));
+        subset.aliases.retain(|_, target| names.contains(target));
+        subset
+    }
+    
+    /// Drop every secret, lease and alias, returning how many secrets there
+    /// were. Settings such as the name policy and size limits are kept.
+    pub fn clear(&mut self) -> usize {
+        let count = self.secrets.len();
+        self.secrets.clear();
+        self.lease_manager = LeaseManager::new();
+        self.aliases.clear();
+        count
+    }
+    
+    /// Drop a secret together with any lease or read limit attached to it,
+    /// or an alias of that name
+    pub fn remove_entry(&mut self, name: &str) -> bool {
+        self.lease_manager.remove_lease(name);
+        self.lease_manager.remove_read_limit(name);
+        let alias = self.aliases.remove(name).is_some();
+        self.secrets.remove(name).is_some() || alias
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub expired: Vec<String>,
+    pub orphaned: Vec<String>,
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.expired.is_empty() && self.orphaned.is_empty()
+    }
+}
+
 #[derive(Debug)]
 pub struct VaultStats {
     pub total_secrets: usize,
+    pub max_secrets: Option<usize>,
     pub active_leases: usize,
     pub expired_secrets: usize,
     pub vault_size: u64,
 }
 
+#[derive(Debug)]
+pub struct VaultStatsDetailed {
+    pub stats: VaultStats,
+    pub oldest_secret: Option<(String, DateTime<Utc>)>,
+    pub newest_secret: Option<(String, DateTime<Utc>)>,
+    pub most_accessed: Option<(String, u64)>,
+    pub average_lease: Option<Duration>,
+}
+
+/// Findings from [`Vault::audit_values`]. Holds secret names only, never values.
+#[derive(Debug, Default)]
+pub struct ValueAudit {
+    /// Groups of secrets that share the same value
+    pub duplicates: Vec<Vec<String>>,
+    pub weak: Vec<(String, Vec<&'static str>)>,
+}
+
+impl ValueAudit {
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.weak.is_empty()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BackupData {
     pub vault_data: VaultData,
     pub identity_hash: String,
     pub created_at: DateTime<Utc>,
     pub version: String,
+    /// Key derivation salt, needed to unwrap the data key on another machine
+    #[serde(default)]
+    pub identity_salt: Option<Vec<u8>>,
+    /// Security key credential, for vaults created with `--fido2`
+    #[serde(default)]
+    pub identity_fido2: Option<Fido2Binding>,
+    /// Scheme `identity_hash` was made with
+    #[serde(default)]
+    pub identity_kdf: KdfAlgorithm,
+    /// Key derivation cost, when tuned apart from the hash's
+    #[serde(default)]
+    pub identity_key_params: Option<KdfParams>,
+    /// Whether the key is tied to the machine, for vaults created with `--bind-machine`
+    #[serde(default)]
+    pub identity_machine_bound: bool,
+}
+
+/// How `merge` settles a secret that exists in both vaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Take whichever copy was updated last
+    Newest,
+    KeepMine,
+    KeepTheirs,
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "newest" => Ok(MergeStrategy::Newest),
+            "keep-mine" => Ok(MergeStrategy::KeepMine),
+            "keep-theirs" => Ok(MergeStrategy::KeepTheirs),
+            _ => Err(anyhow!("Unknown merge strategy: {}. Use newest, keep-mine or keep-theirs", s)),
+        }
+    }
+}
+
+/// Secret names from the other vault, by what `merge` did with them
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// Kept our copy, or theirs had already expired
+    pub skipped: Vec<String>,
+}
+
+/// A backup sealed under its own passphrase, so the file is safe to store off-site
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedBackup {
+    pub salt: Vec<u8>,
+    pub sealed: EncryptedData,
+}
+
+impl EncryptedBackup {
+    pub fn decrypt(&self, passphrase: &str) -> Result<BackupData> {
+        let key = derive_key_from_password(passphrase, &self.salt)?;
+        let backup_str = Zeroizing::new(
+            CryptoEngine::new(&key)
+                .decrypt(&self.sealed)
+                .map_err(|_| VaultError::auth("Invalid backup passphrase"))?,
+        );
+        
+        ron::from_str(&backup_str).map_err(|e| VaultError::Corrupted(e.to_string()).into())
+    }
+}
+
+impl BackupData {
+    /// Seal the backup under a key derived from `passphrase`
+    pub fn encrypt(&self, passphrase: &str) -> Result<EncryptedBackup> {
+        let salt = generate_salt().to_vec();
+        let key = derive_key_from_password(passphrase, &salt)?;
+        let backup_str = Zeroizing::new(ron::to_string(self)?);
+        
+        Ok(EncryptedBackup {
+            sealed: CryptoEngine::new(&key).encrypt(&backup_str)?,
+            salt,
+        })
+    }
+    
+    /// Recover the key the backup's secrets are encrypted under, from the
+    /// master password (and keyfile) it was made with
+    pub fn unlock(&self, password: &str, keyfile: Option<&[u8]>) -> Result<SecretKey> {
+        let salt = self.identity_salt.as_deref().ok_or_else(|| {
+            anyhow!("Backup does not include key derivation parameters. Create a new backup with this version.")
+        })?;
+        let mut identity = Identity::from_backup(&self.identity_hash, salt);
+        identity.fido2 = self.identity_fido2.clone();
+        identity.kdf = self.identity_kdf;
+        identity.key_params = self.identity_key_params;
+        identity.machine_bound = self.identity_machine_bound;
+        if !identity.verify_password(password)? {
+            return Err(VaultError::auth("Password does not match the backup").into());
+        }
+        if let Some(keyfile) = keyfile {
+            identity.require_keyfile(keyfile)?;
+        }
+        let key = identity.unlock(Some(password), keyfile)?;
+        
+        match &self.vault_data.wrapped_key {
+            Some(wrapped) => unwrap_key(&key, wrapped).map_err(|_| self.unwrap_error().into()),
+            // Secrets in pre-envelope backups are encrypted under the password key itself
+            None => Ok(key),
+        }
+    }
+    
+    /// Why the data key didn't unwrap with credentials that matched the hash
+    fn unwrap_error(&self) -> VaultError {
+        if self.identity_machine_bound {
+            return VaultError::auth(&format!(
+                "Backup is bound to the machine it was made on. Set {} to that machine's id to restore it here.",
+                MACHINE_ID_ENV
+            ));
+        }
+        VaultError::auth("Backup cannot be unlocked with these credentials (was it made with a keyfile?)")
+    }
+    
+    /// Read a backup written by `sentinel backup`, picking the format from the
+    /// extension. Encrypted backups prompt for their passphrase.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_as(path, None)
+    }
+    
+    /// Like [`BackupData::load`], but read the file as `format` (ron, json or
+    /// yaml) whatever its extension
+    pub fn load_as(path: &Path, format: Option<&str>) -> Result<Self> {
+        let format = match format {
+            Some(format @ ("ron" | "json" | "yaml")) => format.to_string(),
+            Some(format) => return Err(anyhow!("Unsupported backup format: {}. Use ron, json or yaml", format)),
+            None => path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+        };
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read backup {}: {}", path.display(), e))?;
+        
+        let backup = match format.as_str() {
+            "json" => serde_json::from_str(&contents).map_err(|e| VaultError::Corrupted(e.to_string()))?,
+            "yaml" | "yml" => serde_yaml::from_str(&contents).map_err(|e| VaultError::Corrupted(e.to_string()))?,
+            _ => match ron::from_str::<EncryptedBackup>(&contents) {
+                Ok(encrypted) => {
+                    let passphrase = Zeroizing::new(prompt_backup_passphrase()?);
+                    encrypted.decrypt(&passphrase)?
+                }
+                Err(_) => ron::from_str(&contents).map_err(|e| VaultError::Corrupted(e.to_string()))?,
+            },
+        };
+        
+        Ok(backup)
+    }
+}
+
+/// Choices made once when a vault is created
+#[derive(Debug, Default, Clone)]
+pub struct InitOptions {
+    pub require_pin: bool,
+    pub keyfile: Option<PathBuf>,
+    pub keyfile_only: bool,
+    pub enable_2fa: bool,
+    /// Override the default secret size limit, in bytes
+    pub max_secret_size: Option<usize>,
+    /// Cap the number of secrets the vault may hold
+    pub max_secrets: Option<usize>,
+    /// Tie the key to this machine's id
+    pub bind_machine: bool,
+    /// Require a FIDO2 security key at every unlock
+    pub fido2: bool,
+    /// Encoding of the vault file
+    pub format: VaultFormat,
+    /// Comma-separated globs of environment variables to store as the first secrets
+    pub import_env: Option<String>,
+    /// Argon2 cost of the master password hash, if raised above the recommended one
+    pub hash_cost: Option<KdfParams>,
+    /// Argon2 cost of deriving the vault key, if tuned apart from the hash's
+    pub key_cost: Option<KdfParams>,
+}
+
+/// Whether a secret still exists and how long its lease has left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecretStatus {
+    Missing,
+    Permanent,
+    Expires(Duration),
+    Expired,
+}
+
+/// Prefix marking a vault file written in the binary format. It's followed
+/// by one byte giving the layout, `migrations::BINARY_LAYOUT` when written
+/// by this build.
+const BINARY_MAGIC: &[u8] = b"SVBIN";
+
+/// How `VaultData` is encoded in the vault file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VaultFormat {
+    /// Human-readable, and the default
+    #[default]
+    Ron,
+    /// Compact postcard encoding behind `BINARY_MAGIC`, for large vaults
+    Binary,
+}
+
+impl VaultFormat {
+    /// Tell the formats apart by the magic header; anything else is RON
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.len() > BINARY_MAGIC.len() && bytes.starts_with(BINARY_MAGIC) {
+            VaultFormat::Binary
+        } else {
+            VaultFormat::Ron
+        }
+    }
+    
+    pub fn encode(self, data: &VaultData) -> Result<Vec<u8>> {
+        match self {
+            VaultFormat::Ron => Ok(ron::to_string(data)?.into_bytes()),
+            VaultFormat::Binary => {
+                let mut bytes = BINARY_MAGIC.to_vec();
+                bytes.push(BINARY_LAYOUT);
+                bytes.extend(postcard::to_stdvec(data)?);
+                Ok(bytes)
+            }
+        }
+    }
+    
+    pub fn decode(bytes: &[u8]) -> Result<VaultData> {
+        let corrupted = |e: &dyn std::fmt::Display| VaultError::Corrupted(e.to_string());
+        match Self::detect(bytes) {
+            VaultFormat::Ron => {
+                let vault_data_str = std::str::from_utf8(bytes).map_err(|e| corrupted(&e))?;
+                Ok(ron::from_str(vault_data_str).map_err(|e| corrupted(&e))?)
+            }
+            VaultFormat::Binary => {
+                let layout = bytes[BINARY_MAGIC.len()];
+                if layout == 0 || layout > BINARY_LAYOUT {
+                    return Err(corrupted(&format!("unknown binary layout {}; is it from a newer sentinelvault?", layout)).into());
+                }
+                let body = &bytes[BINARY_MAGIC.len() + 1..];
+                Ok(with_binary_layout(layout, || postcard::from_bytes(body)).map_err(|e| corrupted(&e))?)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for VaultFormat {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ron" => Ok(VaultFormat::Ron),
+            "binary" => Ok(VaultFormat::Binary),
+            _ => Err(anyhow!("Unsupported vault format: {}. Use ron or binary", s)),
+        }
+    }
+}
+
+/// How an existing vault is opened
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Drop secrets whose lease has expired. Disable to inspect or recover them.
+    pub cleanup: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self { cleanup: true }
+    }
+}
+
+/// What `init` needs to report back to the user
+#[derive(Debug, Default)]
+pub struct InitSummary {
+    /// `otpauth://` URI to register with an authenticator app
+    pub totp_uri: Option<String>,
+    /// Secrets stored from the environment by `import_env`
+    pub imported: Vec<String>,
+}
+
+/// Re-wrap the stored vault's data key from `old_key` to `new_key` without
+/// opening it, keeping its file format. `false` for vaults that predate the
+/// wrapped data key, which have to be opened (and migrated) first.
+pub(crate) fn rewrap_stored_key(old_key: &SecretKey, new_key: &SecretKey) -> Result<bool> {
+    let storage = FileStorage::default_location()?;
+    let bytes = storage.read()?;
+    let format = VaultFormat::detect(&bytes);
+    let mut data = VaultFormat::decode(&bytes)?;
+    
+    let Some(wrapped) = &data.wrapped_key else {
+        return Ok(false);
+    };
+    let data_key = unwrap_key(old_key, wrapped).map_err(|_| VaultError::auth("Invalid vault key"))?;
+    data.wrapped_key = Some(wrap_key(new_key, &data_key)?);
+    
+    storage.write(&format.encode(&data)?)?;
+    Ok(true)
+}
+
+/// Whether `key` opens the stored vault, without writing anything. Vaults
+/// that predate the wrapped data key are checked against one of their secrets.
+pub(crate) fn stored_key_opens(key: &SecretKey) -> Result<bool> {
+    let data = VaultFormat::decode(&FileStorage::default_location()?.read()?)?;
+    
+    Ok(match &data.wrapped_key {
+        Some(wrapped) => unwrap_key(key, wrapped).is_ok(),
+        None => data
+            .secrets
+            .values()
+            .next()
+            .is_none_or(|entry| CryptoEngine::new(key).decrypt(&entry.encrypted_value).is_ok()),
+    })
 }
 
 pub struct Vault {
     data: VaultData,
     crypto_engine: CryptoEngine,
+    data_key: SecretKey,
+    clock_skew: Option<Duration>,
+    storage: Box<dyn VaultStorage>,
+    /// Kept from the file that was read, so saves don't change it
+    format: VaultFormat,
 }
 
 impl Vault {
-    pub fn init() -> Result<()> {
+    pub fn init(options: &InitOptions) -> Result<InitSummary> {
         if Identity::exists() {
-            return Err(anyhow!("Vault already initialized. Use 'sentinel add' to add secrets."));
+            return Err(VaultError::AlreadyInitialized.into());
         }
         
-        let password = prompt_new_master_password()?;
-        let identity = Identity::new(&password)?;
-        identity.save()?;
+        if options.keyfile_only && options.keyfile.is_none() {
+            return Err(anyhow!("--keyfile-only requires --keyfile"));
+        }
         
-        let vault_data = VaultData::default();
-        let vault_data_str = ron::to_string(&vault_data)?;
+        let max_secret_size = options.max_secret_size.unwrap_or(DEFAULT_MAX_SECRET_SIZE);
+        if max_secret_size == 0 || max_secret_size > MAX_SECRET_SIZE_LIMIT {
+            return Err(anyhow!("--max-secret-size must be between 1 and {} bytes", MAX_SECRET_SIZE_LIMIT));
+        }
+        if options.max_secrets == Some(0) {
+            return Err(anyhow!("--max-secrets must be at least 1"));
+        }
+        let env_values = match &options.import_env {
+            Some(patterns) => select_env_vars(patterns, std::env::vars_os())?,
+            None => Vec::new(),
+        };
         
-        let vault_path = get_vault_path()?;
-        if let Some(parent) = vault_path.parent() {
-            fs::create_dir_all(parent)?;
+        let keyfile = options.keyfile.as_deref().map(read_keyfile).transpose()?;
+        let password = match &keyfile {
+            Some(_) if options.keyfile_only => None,
+            _ => Some(Zeroizing::new(prompt_new_master_password()?)),
+        };
+        let mut identity = match (&password, &keyfile) {
+            (Some(password), _) => Identity::with_costs(
+                password,
+                options.hash_cost.unwrap_or(KdfParams::RECOMMENDED),
+                options.key_cost,
+            )?,
+            (None, Some(keyfile)) => Identity::new_keyfile_only(keyfile)?,
+            (None, None) => unreachable!("keyfile-only init always has a keyfile"),
+        };
+        if let Some(keyfile) = &keyfile {
+            identity.require_keyfile(keyfile)?;
+        }
+        identity.machine_bound = options.bind_machine;
+        if options.fido2 {
+            identity.fido2 = Some(register_security_key()?);
         }
         
-        fs::write(vault_path, vault_data_str)?;
+        let key = identity.unlock(
+            password.as_deref().map(String::as_str),
+            keyfile.as_deref().map(Vec::as_slice),
+        )?;
         
-        Ok(())
+        let data_key = generate_key();
+        let vault_data = VaultData {
+            wrapped_key: Some(wrap_key(&key, &data_key)?),
+            max_secret_size,
+            max_secrets: options.max_secrets,
+            ..VaultData::default()
+        };
+        
+        // Stage the imported secrets first, so a bad name leaves no vault behind
+        let mut staged = Self {
+            data: vault_data,
+            crypto_engine: CryptoEngine::new(&data_key),
+            data_key,
+            clock_skew: None,
+            storage: Box::new(MemoryStorage::default()),
+            format: options.format,
+        };
+        let mut rows = Vec::new();
+        for (name, value) in env_values {
+            let name = staged.check_new_secret(&name, &value)
+                .map_err(|e| anyhow!("Environment variable for '{}': {}", name, e))?;
+            rows.push((name, value));
+        }
+        staged.insert_secrets(&rows)?;
+        
+        let mut summary = Self::finish_init(identity, &key, staged.data, options)?;
+        summary.imported = rows.into_iter().map(|(name, _)| name).collect();
+        Ok(summary)
     }
     
-    pub fn load() -> Result<Self> {
-        let key = authenticate()?;
-        let crypto_engine = CryptoEngine::new(&key);
+    /// Create a vault from a backup in one step, prompting for the master
+    /// password the backup was made with
+    pub fn init_from_backup(backup: &BackupData, options: &InitOptions) -> Result<InitSummary> {
+        if Identity::exists() {
+            return Err(VaultError::AlreadyInitialized.into());
+        }
         
-        let vault_path = get_vault_path()?;
+        let password = Zeroizing::new(prompt_master_password()?);
+        Self::init_from_backup_with_password(backup, &password, options)
+    }
+    
+    pub fn init_from_backup_with_password(backup: &BackupData, password: &str, options: &InitOptions) -> Result<InitSummary> {
+        if Identity::exists() {
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+        migrations::check_supported(&backup.version)?;
         
-        if !vault_path.exists() {
-            return Err(anyhow!("Vault file not found. Run 'sentinel init' first."));
+        if options.keyfile_only || backup.identity_hash.is_empty() {
+            return Err(anyhow!("Keyfile-only vaults cannot be initialized from a backup"));
         }
         
-        let vault_data_str = fs::read_to_string(vault_path)?;
-        let mut data: VaultData = ron::from_str(&vault_data_str)?;
+        let salt = backup.identity_salt.as_deref().ok_or_else(|| {
+            anyhow!("Backup does not include key derivation parameters. Create a new backup with this version.")
+        })?;
+        
+        let mut identity = Identity::from_backup(&backup.identity_hash, salt);
+        identity.fido2 = backup.identity_fido2.clone();
+        identity.kdf = backup.identity_kdf;
+        identity.key_params = backup.identity_key_params;
+        identity.machine_bound = backup.identity_machine_bound;
+        if !identity.verify_password(password)? {
+            return Err(VaultError::auth("Password does not match the backup").into());
+        }
+        
+        let keyfile = options.keyfile.as_deref().map(read_keyfile).transpose()?;
+        if let Some(keyfile) = &keyfile {
+            identity.require_keyfile(keyfile)?;
+        }
+        
+        let key = identity.unlock(Some(password), keyfile.as_deref().map(Vec::as_slice))?;
+        if let Some(wrapped) = &backup.vault_data.wrapped_key {
+            unwrap_key(&key, wrapped).map_err(|_| backup.unwrap_error())?;
+        }
+        
+        Self::finish_init(identity, &key, backup.vault_data.clone(), options)
+    }
+    
+    /// Apply the optional 2FA and PIN settings, then write the identity and vault files
+    fn finish_init(mut identity: Identity, key: &SecretKey, mut vault_data: VaultData, options: &InitOptions) -> Result<InitSummary> {
+        let mut summary = InitSummary::default();
+        if options.enable_2fa {
+            let secret = identity.enable_totp(key)?;
+            summary.totp_uri = Some(totp::provisioning_uri(&secret, "vault"));
+        }
+        if options.require_pin {
+            let pin = prompt_new_pin()?;
+            identity.seal_with_pin(&pin)?;
+        }
+        identity.save()?;
+        
+        vault_data.touch();
+        FileStorage::default_location()?.write(&options.format.encode(&vault_data)?)?;
+        
+        Ok(summary)
+    }
+    
+    /// Open the vault, prompting for the master password
+    pub fn load(auth: &AuthOptions) -> Result<Self> {
+        Self::load_with(auth, &LoadOptions::default())
+    }
+    
+    pub fn load_with(auth: &AuthOptions, options: &LoadOptions) -> Result<Self> {
+        let key = profile::time("authentication", || authenticate(auth))?;
+        Self::open(&key, options)
+    }
+    
+    /// Open the vault non-interactively using a master password.
+    ///
+    /// A PIN-sealed identity will still prompt for its PIN.
+    pub fn open_with_password(password: &str) -> Result<Self> {
+        let identity = Identity::load()?;
+        let key = identity.unlock(Some(password), None)?;
+        Self::open_with_key(key)
+    }
+    
+    /// Open the vault with an already derived key, without any prompting
+    pub fn open_with_key(key: SecretKey) -> Result<Self> {
+        Self::open(&key, &LoadOptions::default())
+    }
+    
+    pub fn open_with_key_using(key: SecretKey, options: &LoadOptions) -> Result<Self> {
+        Self::open(&key, options)
+    }
+    
+    /// Open a vault kept somewhere other than the default vault file. Saves go
+    /// back to the same storage.
+    pub fn open_with_storage(storage: Box<dyn VaultStorage>, key: SecretKey, options: &LoadOptions) -> Result<Self> {
+        Self::open_storage(storage, &key, options)
+    }
+    
+    /// A new, empty vault that lives only in memory and is never written to
+    /// disk. Everything except backups (which read the identity file) works
+    /// without touching the filesystem.
+    pub fn in_memory(key: SecretKey) -> Self {
+        let data_key = generate_key();
+        let data = VaultData {
+            wrapped_key: Some(wrap_key(&key, &data_key).expect("wrapping a 32-byte key cannot fail")),
+            ..VaultData::default()
+        };
+        
+        Self {
+            data,
+            crypto_engine: CryptoEngine::new(&data_key),
+            data_key,
+            clock_skew: None,
+            storage: Box::new(MemoryStorage::default()),
+            format: VaultFormat::default(),
+        }
+    }
+    
+    fn open(key: &SecretKey, options: &LoadOptions) -> Result<Self> {
+        Self::open_storage(Box::new(FileStorage::default_location()?), key, options)
+    }
+    
+    /// `key` is the password-derived key-encryption key; secrets themselves are
+    /// encrypted under the random data key it wraps.
+    fn open_storage(storage: Box<dyn VaultStorage>, key: &SecretKey, options: &LoadOptions) -> Result<Self> {
+        let bytes = profile::time("vault read", || storage.read())?;
+        let format = VaultFormat::detect(&bytes);
+        let mut data = profile::time("vault parse", || VaultFormat::decode(&bytes))?;
+        log::debug!("Vault is {:?}, version {}, {} secret(s)", format, data.version, data.secrets.len());
+        let upgraded = migrations::upgrade(&mut data)?;
+        if upgraded {
+            log::info!("Upgraded vault to version {}", data.version);
+        }
         
         // Clean up expired secrets
-        let expired_secrets = data.lease_manager.cleanup_expired();
-        for secret_name in expired_secrets {
-            data.secrets.remove(&secret_name);
+        if options.cleanup {
+            let expired_secrets = data.lease_manager.cleanup_expired();
+            log::debug!("Cleanup removed {} expired secret(s)", expired_secrets.len());
+            for secret_name in expired_secrets {
+                data.remove_entry(&secret_name);
+            }
         }
         
-        Ok(Self {
+        let (data_key, migrated) = match &data.wrapped_key {
+            Some(wrapped) => {
+                let data_key = unwrap_key(key, wrapped).map_err(|_| VaultError::auth("Invalid vault key"))?;
+                (data_key, false)
+            }
+            None => (Self::migrate_to_data_key(&mut data, key)?, true),
+        };
+        
+        let mut vault = Self {
+            clock_skew: data.clock_skew(Utc::now()),
             data,
-            crypto_engine,
+            crypto_engine: CryptoEngine::new(&data_key),
+            data_key,
+            storage,
+            format,
+        };
+        
+        if migrated || upgraded {
+            vault.save()?;
+        }
+        
+        Ok(vault)
+    }
+    
+    /// Re-encrypt a vault whose secrets were encrypted directly under the
+    /// password-derived key, returning the newly generated data key.
+    fn migrate_to_data_key(data: &mut VaultData, key: &SecretKey) -> Result<SecretKey> {
+        let legacy_engine = CryptoEngine::new(key);
+        let data_key = generate_key();
+        let engine = CryptoEngine::new(&data_key);
+        
+        for entry in data.secrets.values_mut() {
+            let plaintext = legacy_engine.decrypt_secret(&entry.encrypted_value)?;
+            entry.encrypted_value = engine.encrypt(plaintext.expose())?;
+        }
+        
+        data.wrapped_key = Some(wrap_key(key, &data_key)?);
+        Ok(data_key)
+    }
+    
+    /// Wrap the data key under a new key-encryption key. Secrets are untouched,
+    /// so this costs the same regardless of vault size.
+    pub fn rewrap_key(&mut self, new_key: &SecretKey) -> Result<()> {
+        self.data.wrapped_key = Some(wrap_key(new_key, &self.data_key)?);
+        self.save()
+    }
+    
+    /// Interactively change the master password. With `dry_run`, only check
+    /// that the data key re-wraps under the new password and that every secret
+    /// still decrypts, without writing anything.
+    pub fn change_password(auth: &AuthOptions, dry_run: bool) -> Result<()> {
+        let mut identity = Identity::load()?;
+        let old_key = authenticate_identity(&identity, auth)?;
+        let mut vault = Self::open(&old_key, &LoadOptions { cleanup: false })?;
+        
+        let new_password = Zeroizing::new(prompt_new_master_password()?);
+        let keyfile = auth.keyfile.as_deref().map(read_keyfile).transpose()?;
+        let new_key = identity.change_password(&old_key, &new_password, keyfile.as_deref().map(Vec::as_slice))?;
+        
+        if dry_run {
+            unwrap_key(&new_key, &wrap_key(&new_key, &vault.data_key)?)?;
+            for entry in vault.data.secrets.values() {
+                vault.crypto_engine.decrypt_secret(&entry.encrypted_value)?;
+            }
+            return Ok(());
+        }
+        
+        vault.rewrap_key(&new_key)?;
+        if let Err(e) = identity.save() {
+            // The identity on disk still derives the old key, so the vault must match it
+            vault.rewrap_key(&old_key)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+    
+    /// Re-read the vault file to pick up changes made by other processes. The
+    /// data key is kept, so no authentication is needed.
+    pub fn reload(&mut self) -> Result<()> {
+        let mut data = VaultFormat::decode(&self.storage.read()?)?;
+        migrations::upgrade(&mut data)?;
+        self.data = data;
+        Ok(())
+    }
+    
+    /// Where a secret's lease stands, without reading its value
+    pub fn secret_status(&self, name: &str) -> Result<SecretStatus> {
+        let name = self.resolve_alias(sanitize_secret_name(name)?);
+        if !self.data.secrets.contains_key(&name) {
+            return Ok(SecretStatus::Missing);
+        }
+        
+        Ok(match self.data.lease_manager.get_lease(&name) {
+            Some(lease) => match lease.time_remaining() {
+                Some(remaining) => SecretStatus::Expires(remaining),
+                None => SecretStatus::Expired,
+            },
+            None => SecretStatus::Permanent,
         })
     }
     
-    pub fn save(&self) -> Result<()> {
-        let vault_path = get_vault_path()?;
-        let vault_data_str = ron::to_string(&self.data)?;
-        fs::write(vault_path, vault_data_str)?;
+    /// Fail unless `name` exists and its lease hasn't run out, with a
+    /// different error for each. Expired secrets are only still there when
+    /// the vault was opened without cleanup.
+    pub fn ensure_active(&self, name: &str) -> Result<()> {
+        let name = self.resolve_alias(sanitize_secret_name(name)?);
+        if !self.data.secrets.contains_key(&name) {
+            return Err(VaultError::NotFound(name).into());
+        }
+        
+        match self.data.lease_manager.get_lease(&name) {
+            Some(lease) if lease.is_expired() => {
+                Err(VaultError::Expired { expired_at: lease.expires_at, name }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    pub fn data(&self) -> &VaultData {
+        &self.data
+    }
+    
+    pub fn format(&self) -> VaultFormat {
+        self.format
+    }
+    
+    /// Set when the system clock is earlier than the vault's last save, which
+    /// makes lease expiry untrustworthy. Callers decide whether to refuse.
+    pub fn clock_skew(&self) -> Option<Duration> {
+        self.clock_skew
+    }
+    
+    pub fn save(&mut self) -> Result<()> {
+        self.data.touch();
+        self.storage.write(&self.format.encode(&self.data)?)
+    }
+    
+    /// Put the vault in or out of read-only mode
+    pub fn set_locked(&mut self, locked: bool) -> Result<()> {
+        self.data.locked = locked;
+        self.save()
+    }
+    
+    /// Set or clear the name policy applied by `add_secret`. Returns the
+    /// existing secrets that don't match it, which are left untouched.
+    pub fn set_name_pattern(&mut self, pattern: Option<&str>) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut mismatched = Vec::new();
+        if let Some(pattern) = pattern {
+            let regex = compile_name_pattern(pattern)?;
+            mismatched = self.data.secrets.keys().filter(|name| !regex.is_match(name)).cloned().collect();
+            mismatched.sort();
+        }
+        
+        self.data.name_pattern = pattern.map(str::to_string);
+        self.save()?;
+        Ok(mismatched)
+    }
+    
+    /// Set or clear the cap on how many secrets the vault holds. A vault
+    /// already over the new cap keeps its secrets but accepts no new ones.
+    pub fn set_max_secrets(&mut self, limit: Option<usize>) -> Result<()> {
+        self.ensure_writable()?;
+        if limit == Some(0) {
+            return Err(anyhow!("The secret limit must be at least 1"));
+        }
+        
+        self.data.max_secrets = limit;
+        self.save()
+    }
+    
+    /// Check that storing `names` stays within the vault's secret limit.
+    /// Names that already exist are overwrites and don't count.
+    fn check_capacity<'a>(&self, names: impl IntoIterator<Item = &'a String>) -> Result<()> {
+        let Some(limit) = self.data.max_secrets else {
+            return Ok(());
+        };
+        
+        let new: std::collections::HashSet<&String> = names
+            .into_iter()
+            .filter(|name| !self.data.secrets.contains_key(*name))
+            .collect();
+        if !new.is_empty() && self.data.secrets.len() + new.len() > limit {
+            return Err(VaultError::SecretLimit { limit }.into());
+        }
+        Ok(())
+    }
+    
+    fn ensure_writable(&self) -> Result<()> {
+        if self.data.locked {
+            return Err(VaultError::ReadOnly.into());
+        }
         Ok(())
     }
     
-    pub fn add_secret(&mut self, name: &str, value: &str) -> Result<()> {
+    /// Sanitize a new secret's name and check it and the value against the
+    /// vault's name policy and size limit
+    fn check_new_secret(&self, name: &str, value: &str) -> Result<String> {
+        let name = self.check_new_name(name)?;
+        validate_secret_value(value, self.data.max_secret_size)?;
+        Ok(name)
+    }
+    
+    fn check_new_name(&self, name: &str) -> Result<String> {
         let name = sanitize_secret_name(name)?;
-        validate_secret_value(value)?;
+        if let Some(pattern) = &self.data.name_pattern {
+            check_name_pattern(&name, pattern)?;
+        }
+        if let Some(target) = self.data.aliases.get(&name) {
+            return Err(anyhow!("'{}' is an alias of '{}'. Remove the alias to reuse the name.", name, target));
+        }
+        Ok(name)
+    }
+    
+    /// Make `alias` another name for the secret `target`. Reading the alias
+    /// returns whatever `target` currently holds.
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let alias = self.check_new_name(alias)?;
+        if self.data.secrets.contains_key(&alias) {
+            return Err(VaultError::AlreadyExists(alias).into());
+        }
+        let target = sanitize_secret_name(target)?;
+        if let Some(real) = self.data.aliases.get(&target) {
+            return Err(anyhow!("'{}' is itself an alias; point '{}' at '{}' instead", target, alias, real));
+        }
+        if !self.data.secrets.contains_key(&target) {
+            return Err(VaultError::NotFound(target).into());
+        }
         
+        self.data.aliases.insert(alias, target);
+        self.save()
+    }
+    
+    /// Move a secret to a new name, keeping its timestamps, counters, lease
+    /// and read limit. Aliases of the old name follow it.
+    pub fn rename_secret(&mut self, old: &str, new: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let old = sanitize_secret_name(old)?;
+        let new = self.check_new_name(new)?;
+        if self.data.secrets.contains_key(&new) {
+            return Err(VaultError::AlreadyExists(new).into());
+        }
+        let entry = self.data.secrets.remove(&old).ok_or_else(|| VaultError::NotFound(old.clone()))?;
+        
+        self.data.secrets.insert(new.clone(), entry);
+        self.data.lease_manager.rename(&old, &new);
+        for target in self.data.aliases.values_mut().filter(|target| **target == old) {
+            *target = new.clone();
+        }
+        self.save()
+    }
+    
+    /// Every alias with the secret it points to, sorted by alias. The target
+    /// may be gone if it expired.
+    pub fn list_aliases(&self) -> Vec<(String, String)> {
+        let mut aliases: Vec<_> = self.data.aliases.iter().map(|(alias, target)| (alias.clone(), target.clone())).collect();
+        aliases.sort();
+        aliases
+    }
+    
+    /// The secret `name` refers to: its target if it's an alias, else itself
+    fn resolve_alias(&self, name: String) -> String {
+        match self.data.aliases.get(&name) {
+            Some(target) => target.clone(),
+            None => name,
+        }
+    }
+    
+    /// Refuse to remove secrets that an alias not also being removed points to
+    fn check_unaliased(&self, removing: &[String]) -> Result<()> {
+        let mut aliases: Vec<&str> = self
+            .data
+            .aliases
+            .iter()
+            .filter(|(alias, target)| removing.contains(target) && !removing.contains(alias))
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        if aliases.is_empty() {
+            return Ok(());
+        }
+        
+        aliases.sort();
+        Err(anyhow!("Aliases still point here: {}. Remove them first.", aliases.join(", ")))
+    }
+    
+    fn insert_secret(&mut self, name: String, value: &str) -> Result<()> {
         let encrypted_value = self.crypto_engine.encrypt(value)?;
-        let secret_entry = SecretEntry::new(encrypted_value);
+        let mut secret_entry = SecretEntry::new(encrypted_value);
+        // Overwriting a secret keeps it protected, and keeps its tags and description
+        if let Some(existing) = self.data.secrets.remove(&name) {
+            secret_entry.protected = existing.protected;
+            secret_entry.tags = existing.tags;
+            secret_entry.description = existing.description;
+        }
+        
+        self.data.secrets.insert(name, secret_entry);
+        Ok(())
+    }
+    
+    pub fn add_secret(&mut self, name: &str, value: &str) -> Result<()> {
+        self.add_secret_with_meta(name, value, &[], None)
+    }
+    
+    /// Add a secret with tags and a description. When overwriting, empty
+    /// `tags` or no `description` keep the ones already there.
+    pub fn add_secret_with_meta(&mut self, name: &str, value: &str, tags: &[String], description: Option<&str>) -> Result<()> {
+        self.ensure_writable()?;
+        let name = self.check_new_secret(name, value)?;
+        let tags = normalize_tags(tags)?;
+        self.check_capacity([&name])?;
+        self.insert_secret(name.clone(), value)?;
         
-        self.data.secrets.insert(name.clone(), secret_entry);
+        let entry = self.data.secrets.get_mut(&name).expect("just inserted");
+        if !tags.is_empty() {
+            entry.tags = tags;
+        }
+        if let Some(description) = description {
+            entry.description = Some(description.to_string());
+        }
         self.save()?;
         
         Ok(())
     }
     
-    pub fn get_secret(&self, name: &str) -> Result<Option<String>> {
-        let name = sanitize_secret_name(name)?;
+    /// Whether `name` (or the secret it's an alias of) carries `tag`
+    pub fn has_tag(&self, name: &str, tag: &str) -> bool {
+        let name = self.resolve_alias(name.to_string());
+        self.data.secrets.get(&name).is_some_and(|entry| entry.tags.iter().any(|t| t == tag))
+    }
+    
+    /// Copy secrets from another vault's data, whose values are encrypted under
+    /// `their_key`. Name collisions are settled by `strategy`, and a secret
+    /// taken from theirs brings its lease and read limit along. Secrets that
+    /// have expired in theirs are skipped. Nothing changes if any secret
+    /// fails to decrypt or validate.
+    pub fn merge(&mut self, theirs: &VaultData, their_key: &SecretKey, strategy: MergeStrategy) -> Result<MergeReport> {
+        self.ensure_writable()?;
+        let their_engine = CryptoEngine::new(their_key);
+        let mut report = MergeReport::default();
+        
+        let mut names: Vec<&String> = theirs.secrets.keys().collect();
+        names.sort();
+        
+        let mut taken = Vec::new();
+        for name in names {
+            let entry = &theirs.secrets[name];
+            let lease = theirs.lease_manager.get_lease(name);
+            let take = !lease.is_some_and(|lease| lease.is_expired())
+                && match self.data.secrets.get(name) {
+                    None => true,
+                    Some(mine) => match strategy {
+                        MergeStrategy::Newest => entry.updated_at > mine.updated_at,
+                        MergeStrategy::KeepMine => false,
+                        MergeStrategy::KeepTheirs => true,
+                    },
+                };
+            if !take {
+                report.skipped.push(name.clone());
+                continue;
+            }
+            
+            let read_limit = theirs.lease_manager.get_read_limit(name);
+            let value = their_engine.decrypt_secret(&entry.encrypted_value)
+                .map_err(|_| VaultError::auth("Backup secrets cannot be decrypted with this key"))?;
+            let name = self.check_new_secret(name, value.expose())?;
+            let merged = SecretEntry {
+                encrypted_value: self.crypto_engine.encrypt(value.expose())?,
+                created_at: entry.created_at,
+                updated_at: entry.updated_at,
+                access_count: entry.access_count,
+                last_accessed: entry.last_accessed,
+                protected: entry.protected,
+                tags: entry.tags.clone(),
+                description: entry.description.clone(),
+            };
+            taken.push((name, merged, lease.cloned(), read_limit));
+        }
+        self.check_capacity(taken.iter().map(|(name, ..)| name))?;
+        
+        for (name, entry, lease, read_limit) in taken {
+            if self.data.secrets.insert(name.clone(), entry).is_some() {
+                report.updated.push(name.clone());
+            } else {
+                report.added.push(name.clone());
+            }
+            
+            match lease {
+                Some(lease) => self.data.lease_manager.set_lease(name.clone(), lease),
+                None => {
+                    self.data.lease_manager.remove_lease(&name);
+                }
+            }
+            match read_limit {
+                Some(reads) => self.data.lease_manager.set_read_limit(name, reads),
+                None => {
+                    self.data.lease_manager.remove_read_limit(&name);
+                }
+            }
+        }
+        
+        if !report.added.is_empty() || !report.updated.is_empty() {
+            self.save()?;
+        }
+        Ok(report)
+    }
+    
+    /// Add secrets from CSV with a `name,value` header, overwriting existing
+    /// secrets of the same name. Every row is checked before anything is
+    /// stored, so a bad row leaves the vault unchanged. Returns the number of
+    /// secrets imported.
+    pub fn import_csv(&mut self, contents: &str) -> Result<usize> {
+        self.ensure_writable()?;
+        let mut reader = csv::Reader::from_reader(contents.as_bytes());
+        
+        let headers = reader.headers().map_err(|e| anyhow!("Invalid CSV: {}", e))?;
+        if headers.len() != 2 || &headers[0] != "name" || &headers[1] != "value" {
+            return Err(anyhow!("CSV must start with a 'name,value' header"));
+        }
         
-        if let Some(mut entry) = self.data.secrets.get(&name).cloned() {
+        let mut rows = Vec::new();
+        for (i, record) in reader.records().enumerate() {
+            // Row 1 is the header
+            let row = i + 2;
+            let record = record.map_err(|e| anyhow!("Invalid CSV on row {}: {}", row, e))?;
+            let value = Zeroizing::new(record[1].to_string());
+            let name = self.check_new_secret(&record[0], &value)
+                .map_err(|e| anyhow!("Row {}: {}", row, e))?;
+            rows.push((name, value));
+        }
+        
+        self.insert_secrets(&rows)?;
+        if !rows.is_empty() {
+            self.save()?;
+        }
+        
+        Ok(rows.len())
+    }
+    
+    /// Add every already checked `(name, value)` pair, or none of them if
+    /// they would go over `max_secrets`, without saving
+    fn insert_secrets(&mut self, rows: &[(String, Zeroizing<String>)]) -> Result<()> {
+        self.check_capacity(rows.iter().map(|(name, _)| name))?;
+        for (name, value) in rows {
+            self.insert_secret(name.clone(), value)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Every value `list_values` would reveal, as CSV with a `name,value`
+    /// header
+    pub fn export_csv(&self) -> Result<Zeroizing<String>> {
+        Self::write_csv(self.list_values()?)
+    }
+    
+    /// Like `export_csv`, limited to `names`
+    pub fn export_csv_of(&self, names: &[String]) -> Result<Zeroizing<String>> {
+        let mut values = self.list_values()?;
+        values.retain(|(name, _)| names.contains(name));
+        Self::write_csv(values)
+    }
+    
+    fn write_csv(values: Vec<(String, SecretString)>) -> Result<Zeroizing<String>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["name", "value"])?;
+        for (name, value) in values {
+            writer.write_record([name.as_str(), value.expose()])?;
+        }
+        
+        let mut bytes = Zeroizing::new(writer.into_inner().map_err(|e| anyhow!("Failed to write CSV: {}", e))?);
+        Ok(Zeroizing::new(String::from_utf8(std::mem::take(&mut *bytes))?))
+    }
+    
+    pub fn get_secret(&mut self, name: &str) -> Result<Option<SecretString>> {
+        let name = self.resolve_alias(sanitize_secret_name(name)?);
+        
+        if let Some(entry) = self.data.secrets.get(&name) {
             // Check if secret has expired
             if let Some(lease) = self.data.lease_manager.get_lease(&name) {
                 if lease.is_expired() {
@@ -156,17 +1311,108 @@ impl Vault {
                 }
             }
             
-            let decrypted = self.crypto_engine.decrypt(&entry.encrypted_value)?;
+            let decrypted = self.crypto_engine.decrypt_secret(&entry.encrypted_value)?;
+            
+            if let Some(entry) = self.data.secrets.get_mut(&name) {
+                entry.mark_accessed();
+            }
+            self.data.lease_manager.renew_lease(&name);
             
-            // Update access statistics (we can't modify self here, so we'll skip this for now)
-            // In a real implementation, you might want to handle this differently
+            // Burn one read from a read-limited secret, destroying it on the last one
+            if self.data.lease_manager.consume_read(&name) == Some(0) {
+                self.data.secrets.remove(&name);
+                self.data.lease_manager.remove_lease(&name);
+            }
             
+            self.save()?;
             Ok(Some(decrypted))
         } else {
             Ok(None)
         }
     }
     
+    pub fn is_protected(&self, name: &str) -> Result<bool> {
+        let name = self.resolve_alias(sanitize_secret_name(name)?);
+        Ok(self.data.secrets.get(&name).is_some_and(|entry| entry.protected))
+    }
+    
+    /// Mark a secret as requiring re-authentication before it is revealed
+    pub fn set_protected(&mut self, name: &str, protected: bool) -> Result<()> {
+        self.ensure_writable()?;
+        let name = sanitize_secret_name(name)?;
+        
+        let entry = self.data.secrets
+            .get_mut(&name)
+            .ok_or_else(|| VaultError::NotFound(name.clone()))?;
+        entry.protected = protected;
+        
+        self.save()
+    }
+    
+    /// Read a secret stored as a JSON object and return a single field of it
+    pub fn get_secret_field(&mut self, name: &str, field: &str) -> Result<Option<SecretString>> {
+        match self.get_secret(name)? {
+            Some(value) => Ok(Some(SecretString::new(json_field(value.expose(), field)?))),
+            None => Ok(None),
+        }
+    }
+    
+    /// Secrets read most recently first, with when they were last read.
+    /// Secrets never read and expired secrets are left out.
+    pub fn recent_secrets(&self, limit: usize) -> Vec<(String, DateTime<Utc>)> {
+        let mut recent: Vec<_> = self
+            .data
+            .secrets
+            .iter()
+            .filter(|(name, _)| !self.data.lease_manager.get_lease(name).is_some_and(|lease| lease.is_expired()))
+            .filter_map(|(name, entry)| Some((name.clone(), entry.last_accessed?)))
+            .collect();
+        
+        recent.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        recent.truncate(limit);
+        recent
+    }
+    
+    /// Secrets not read (or, if never read, created) within `unused_for`,
+    /// least recently used first. Expired secrets are left out.
+    pub fn stale_secrets(&self, unused_for: Duration) -> Vec<(String, DateTime<Utc>)> {
+        let cutoff = Utc::now() - unused_for;
+        let mut stale: Vec<_> = self
+            .data
+            .secrets
+            .iter()
+            .filter(|(name, _)| !self.data.lease_manager.get_lease(name).is_some_and(|lease| lease.is_expired()))
+            .map(|(name, entry)| (name.clone(), entry.last_used()))
+            .filter(|(_, last_used)| *last_used < cutoff)
+            .collect();
+        
+        stale.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        stale
+    }
+    
+    /// Stored secrets whose lease ends within `window`, sorted by name
+    pub fn expiring_within(&self, window: Duration) -> Vec<String> {
+        let mut names = self.data.lease_manager.expiring_within(window);
+        names.retain(|name| self.data.secrets.contains_key(name));
+        names
+    }
+    
+    /// Expired secrets still present because the vault was opened without cleanup
+    pub fn list_expired_secrets(&self) -> Vec<(String, DateTime<Utc>)> {
+        let mut expired: Vec<_> = self
+            .data
+            .secrets
+            .keys()
+            .filter_map(|name| {
+                let lease = self.data.lease_manager.get_lease(name)?;
+                lease.is_expired().then(|| (name.clone(), lease.expires_at))
+            })
+            .collect();
+        
+        expired.sort();
+        expired
+    }
+    
     pub fn list_secrets(&self) -> Result<Vec<(String, Option<DateTime<Utc>>)>> {
         let mut secrets = Vec::new();
         
@@ -188,11 +1434,61 @@ impl Vault {
         Ok(secrets)
     }
     
+    /// Names of unexpired secrets containing `pattern`, ignoring case, or
+    /// matching it as a glob when `glob` is set. Sorted, and no value is
+    /// decrypted.
+    pub fn search_secrets(&self, pattern: &str, glob: bool) -> Vec<String> {
+        let needle = pattern.to_lowercase();
+        let mut found: Vec<String> = self
+            .data
+            .secrets
+            .keys()
+            .filter(|name| !self.data.lease_manager.get_lease(name).is_some_and(|lease| lease.is_expired()))
+            .filter(|name| if glob { glob_match(pattern, name) } else { name.to_lowercase().contains(&needle) })
+            .cloned()
+            .collect();
+        
+        found.sort();
+        found
+    }
+    
+    /// Decrypt every unexpired secret, sorted by name. Read-limited secrets are
+    /// left out, since revealing them here would bypass the limit.
+    pub fn list_values(&self) -> Result<Vec<(String, SecretString)>> {
+        let mut values: Vec<_> = self.decrypt_all()?.into_iter().collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(values)
+    }
+    
+    /// The values `list_values` reveals, decrypted in parallel. Each one is
+    /// cheap, but vaults with thousands of secrets add up.
+    pub fn decrypt_all(&self) -> Result<HashMap<String, SecretString>> {
+        use rayon::prelude::*;
+        
+        let names: Vec<String> = self
+            .list_secrets()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| self.data.lease_manager.get_read_limit(name).is_none())
+            .collect();
+        
+        let engine = &self.crypto_engine;
+        let secrets = &self.data.secrets;
+        names
+            .into_par_iter()
+            .map(|name| {
+                let value = engine.decrypt_secret(&secrets[&name].encrypted_value)?;
+                Ok((name, value))
+            })
+            .collect()
+    }
+    
     pub fn remove_secret(&mut self, name: &str) -> Result<bool> {
+        self.ensure_writable()?;
         let name = sanitize_secret_name(name)?;
+        self.check_unaliased(std::slice::from_ref(&name))?;
         
-        let removed = self.data.secrets.remove(&name).is_some();
-        self.data.lease_manager.remove_lease(&name);
+        let removed = self.data.remove_entry(&name);
         
         if removed {
             self.save()?;
@@ -201,20 +1497,259 @@ impl Vault {
         Ok(removed)
     }
     
-    pub fn set_expiry(&mut self, name: &str, duration_str: &str) -> Result<()> {
+    /// Resolve secret names, or glob patterns when `glob` is set, to stored
+    /// secrets and aliases
+    pub fn match_secrets(&self, patterns: &[String], glob: bool) -> Result<Vec<String>> {
+        let mut matched = Vec::new();
+        
+        for pattern in patterns {
+            if glob {
+                matched.extend(
+                    self.data.secrets.keys().chain(self.data.aliases.keys()).filter(|name| glob_match(pattern, name)).cloned(),
+                );
+            } else {
+                let name = sanitize_secret_name(pattern)?;
+                if self.data.secrets.contains_key(&name) || self.data.aliases.contains_key(&name) {
+                    matched.push(name);
+                }
+            }
+        }
+        
+        matched.sort();
+        matched.dedup();
+        Ok(matched)
+    }
+    
+    /// Remove every matching secret with a single save, returning how many were removed
+    pub fn remove_secrets(&mut self, patterns: &[String], glob: bool) -> Result<usize> {
+        self.ensure_writable()?;
+        let matched = self.match_secrets(patterns, glob)?;
+        self.check_unaliased(&matched)?;
+        
+        for name in &matched {
+            self.data.remove_entry(name);
+        }
+        
+        if !matched.is_empty() {
+            self.save()?;
+        }
+        
+        Ok(matched.len())
+    }
+    
+    /// Apply a batch of changes in order with a single save. If any of them
+    /// fails, none of them are kept.
+    pub fn apply_batch(&mut self, ops: &[BatchOp]) -> Result<()> {
+        self.ensure_writable()?;
+        let snapshot = self.format.encode(&self.data)?;
+        
+        for (index, op) in ops.iter().enumerate() {
+            if let Err(e) = self.apply_batch_op(op) {
+                self.data = VaultFormat::decode(&snapshot)?;
+                return Err(e.context(format!("Operation {} ({}) failed; nothing was changed", index + 1, op.name())));
+            }
+        }
+        
+        self.save()
+    }
+    
+    fn apply_batch_op(&mut self, op: &BatchOp) -> Result<()> {
+        match op {
+            BatchOp::Add { name, value } => {
+                let name = self.check_new_secret(name, value)?;
+                self.check_capacity([&name])?;
+                self.insert_secret(name, value)
+            }
+            BatchOp::Expire { name, duration, sliding } => {
+                let name = sanitize_secret_name(name)?;
+                if !self.data.secrets.contains_key(&name) {
+                    return Err(VaultError::NotFound(name).into());
+                }
+                let duration = parse_duration(duration)?;
+                if *sliding {
+                    self.data.lease_manager.add_sliding_lease(name, duration);
+                } else {
+                    self.data.lease_manager.add_lease(name, duration);
+                }
+                Ok(())
+            }
+            BatchOp::Remove { name } => {
+                let name = sanitize_secret_name(name)?;
+                self.check_unaliased(std::slice::from_ref(&name))?;
+                if !self.data.remove_entry(&name) {
+                    return Err(VaultError::NotFound(name).into());
+                }
+                Ok(())
+            }
+        }
+    }
+    
+    /// Remove every secret and lease, keeping the vault and identity in place.
+    /// Returns how many secrets were removed.
+    pub fn remove_all(&mut self) -> Result<usize> {
+        self.ensure_writable()?;
+        let removed = self.data.clear();
+        self.save()?;
+        Ok(removed)
+    }
+    
+    /// Delete the identity and the vault file, for a full reset. Returns how
+    /// many secrets the vault held. The identity goes first, so if that fails
+    /// the vault is untouched and still opens.
+    pub fn destroy(self) -> Result<usize> {
+        self.ensure_writable()?;
+        Identity::delete()?;
+        self.storage.delete().map_err(|e| anyhow!(
+            "Deleted the identity, but not the vault file: {}. Its secrets can no longer be unlocked; `sentinel init` replaces it.", e
+        ))?;
+        Ok(self.data.secrets.len())
+    }
+    
+    /// Expire a secret after `duration_str`. A `sliding` expiry is pushed back
+    /// by the same duration every time the secret is read.
+    pub fn set_expiry(&mut self, name: &str, duration_str: &str, sliding: bool) -> Result<()> {
+        self.set_expiries(&[name.to_string()], duration_str, sliding)
+    }
+    
+    /// Apply the same expiry to several secrets with a single save. Every name
+    /// is checked first, and nothing changes if any of them is missing.
+    pub fn set_expiries(&mut self, names: &[String], duration_str: &str, sliding: bool) -> Result<()> {
+        self.ensure_writable()?;
+        let names = names
+            .iter()
+            .map(|name| sanitize_secret_name(name))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let missing: Vec<&str> = names
+            .iter()
+            .filter(|name| !self.data.secrets.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(VaultError::NotFound(missing.join(", ")).into());
+        }
+        
+        let duration = parse_duration(duration_str)?;
+        if sliding {
+            for name in names {
+                self.data.lease_manager.add_sliding_lease(name, duration);
+            }
+        } else {
+            self.data.lease_manager.add_leases(&names, duration);
+        }
+        
+        self.save()?;
+        Ok(())
+    }
+    
+    /// Remove the lease and read limit on a secret. Returns whether either existed.
+    pub fn clear_expiry(&mut self, name: &str) -> Result<bool> {
+        self.ensure_writable()?;
         let name = sanitize_secret_name(name)?;
         
         if !self.data.secrets.contains_key(&name) {
-            return Err(anyhow!("Secret '{}' not found", name));
+            return Err(VaultError::NotFound(name).into());
         }
         
-        let duration = parse_duration(duration_str)?;
-        self.data.lease_manager.add_lease(name, duration);
+        let had_lease = self.data.lease_manager.remove_lease(&name).is_some();
+        let had_limit = self.data.lease_manager.remove_read_limit(&name).is_some();
+        if !(had_lease || had_limit) {
+            return Ok(false);
+        }
+        
+        self.save()?;
+        Ok(true)
+    }
+    
+    /// Push a secret's expiry back by `duration_str`, returning the new expiry
+    pub fn extend_expiry(&mut self, name: &str, duration_str: &str) -> Result<DateTime<Utc>> {
+        self.ensure_writable()?;
+        let name = sanitize_secret_name(name)?;
+        if !self.data.secrets.contains_key(&name) {
+            return Err(VaultError::NotFound(name).into());
+        }
+        
+        let extra = parse_duration(duration_str)?;
+        self.data.lease_manager.extend_lease(&name, extra)?;
+        self.save()?;
+        Ok(self.data.lease_manager.get_lease(&name).expect("just extended").expires_at)
+    }
+    
+    pub fn set_read_limit(&mut self, name: &str, max_reads: u64) -> Result<()> {
+        self.ensure_writable()?;
+        let name = sanitize_secret_name(name)?;
+        
+        if !self.data.secrets.contains_key(&name) {
+            return Err(VaultError::NotFound(name).into());
+        }
+        
+        if max_reads == 0 {
+            return Err(anyhow!("Read limit must be at least 1"));
+        }
+        
+        self.data.lease_manager.set_read_limit(name, max_reads);
         
         self.save()?;
         Ok(())
     }
     
+    pub fn prune(&mut self, dry_run: bool) -> Result<PruneReport> {
+        if !dry_run {
+            self.ensure_writable()?;
+        }
+        let report = self.data.prune(dry_run);
+        
+        if !dry_run && !report.is_empty() {
+            self.save()?;
+        }
+        
+        Ok(report)
+    }
+    
+    /// Remove expired secrets, leaving active ones and orphaned leases alone
+    pub fn remove_expired(&mut self, dry_run: bool) -> Result<Vec<String>> {
+        if !dry_run {
+            self.ensure_writable()?;
+        }
+        let removed = self.data.remove_expired(dry_run);
+        
+        if !dry_run && !removed.is_empty() {
+            self.save()?;
+        }
+        
+        Ok(removed)
+    }
+    
+    /// Decrypt every secret and report reused or weak values by name.
+    /// Read limits are not consumed.
+    pub fn audit_values(&self) -> Result<ValueAudit> {
+        use sha2::{Digest, Sha256};
+        
+        let mut names: Vec<&String> = self.data.secrets.keys().collect();
+        names.sort();
+        
+        // Group by digest so plaintext values aren't kept around while comparing
+        let mut by_digest: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
+        let mut audit = ValueAudit::default();
+        
+        for name in names {
+            let value = self.crypto_engine.decrypt_secret(&self.data.secrets[name].encrypted_value)?;
+            
+            let weaknesses = password_weaknesses(value.expose());
+            if !weaknesses.is_empty() {
+                audit.weak.push((name.clone(), weaknesses));
+            }
+            
+            let digest = Sha256::digest(value.expose().as_bytes()).to_vec();
+            by_digest.entry(digest).or_default().push(name.clone());
+        }
+        
+        audit.duplicates = by_digest.into_values().filter(|group| group.len() > 1).collect();
+        audit.duplicates.sort();
+        
+        Ok(audit)
+    }
+    
     pub fn create_backup(&self) -> Result<BackupData> {
         let identity = Identity::load()?;
         
@@ -222,17 +1757,49 @@ impl Vault {
             vault_data: self.data.clone(),
             identity_hash: identity.password_hash.clone(),
             created_at: Utc::now(),
-            version: "0.1.0".to_string(),
+            version: CURRENT_VERSION.to_string(),
+            identity_salt: Some(identity.salt.clone()),
+            identity_fido2: identity.fido2.clone(),
+            identity_kdf: identity.kdf,
+            identity_key_params: identity.key_params,
+            identity_machine_bound: identity.machine_bound,
         })
     }
     
+    /// Replace everything in the vault with a backup of it, returning how many
+    /// secrets the backup held. The backup must come from this vault, so its
+    /// secrets decrypt under the current data key; it may predate a password
+    /// change or KDF re-hash. Use `merge` for backups of other vaults.
+    pub fn restore_from_backup(&mut self, backup: BackupData) -> Result<usize> {
+        self.ensure_writable()?;
+        migrations::check_supported(&backup.version)?;
+        
+        let mut data = backup.vault_data;
+        migrations::upgrade(&mut data)?;
+        for (name, entry) in &data.secrets {
+            self.crypto_engine.decrypt_secret(&entry.encrypted_value)
+                .map_err(|_| anyhow!("Secret '{}' in the backup is not encrypted under this vault's key. Use `sentinel merge` to copy its secrets instead.", name))?;
+        }
+        
+        // The backup's copy of the data key is wrapped under whatever password
+        // was current when it was made; the identity on disk needs this one
+        data.wrapped_key = self.data.wrapped_key.clone();
+        let count = data.secrets.len();
+        self.data = data;
+        self.save()?;
+        Ok(count)
+    }
+    
+    /// A backup of just `names`, e.g. to share a few secrets with another
+    /// vault through `merge`
+    pub fn create_backup_of(&self, names: &[String]) -> Result<BackupData> {
+        let mut backup = self.create_backup()?;
+        backup.vault_data = self.data.subset(names);
+        Ok(backup)
+    }
+    
     pub fn get_stats(&self) -> Result<VaultStats> {
-        let vault_path = get_vault_path()?;
-        let vault_size = if vault_path.exists() {
-            fs::metadata(vault_path)?.len()
-        } else {
-            0
-        };
+        let vault_size = self.storage.size()?;
         
         let total_secrets = self.data.secrets.len();
         let active_leases = self.data.lease_manager.active_leases_count();
@@ -240,11 +1807,52 @@ impl Vault {
         
         Ok(VaultStats {
             total_secrets,
+            max_secrets: self.data.max_secrets,
             active_leases,
             expired_secrets,
             vault_size,
         })
     }
+    
+    pub fn get_detailed_stats(&self) -> Result<VaultStatsDetailed> {
+        let stats = self.get_stats()?;
+        
+        // Sort by name first so ties resolve the same way on every run
+        let mut entries: Vec<(&String, &SecretEntry)> = self.data.secrets.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        
+        let oldest_secret = entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.created_at)
+            .map(|(name, entry)| ((*name).clone(), entry.created_at));
+        let newest_secret = entries
+            .iter()
+            .rev()
+            .max_by_key(|(_, entry)| entry.created_at)
+            .map(|(name, entry)| ((*name).clone(), entry.created_at));
+        let most_accessed = entries
+            .iter()
+            .rev()
+            .max_by_key(|(_, entry)| entry.access_count)
+            .filter(|(_, entry)| entry.access_count > 0)
+            .map(|(name, entry)| ((*name).clone(), entry.access_count));
+        
+        let leases = self.data.lease_manager.list_active_leases();
+        let average_lease = if leases.is_empty() {
+            None
+        } else {
+            let total: Duration = leases.iter().map(|(_, lease)| lease.duration()).sum();
+            Some(total / leases.len() as i32)
+        };
+        
+        Ok(VaultStatsDetailed {
+            stats,
+            oldest_secret,
+            newest_secret,
+            most_accessed,
+            average_lease,
+        })
+    }
 }
 
 // Update CLI to handle missing value parameter
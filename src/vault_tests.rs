@@ -2,23 +2,58 @@ use anyhow::Result;
 use chrono::Utc;
 use proptest::prelude::*;
 use sentinelvault::{
-    crypto::{CryptoEngine, SecretKey},
-    identity::Identity,
+    batch,
+    config::{config_path, VAULT_DIR_ENV},
+    crypto::{generate_key, unwrap_key, wrap_key, CryptoEngine, KdfParams, SecretKey},
+    error::VaultError,
+    identity::{export_identity, import_identity, AuthOptions, Identity, MACHINE_ID_ENV, PASSWORD_ENV},
     lease::{parse_duration, LeaseManager},
-    utils::{sanitize_secret_name, validate_secret_value},
-    vault::{SecretEntry, VaultData},
+    migrations::CURRENT_VERSION,
+    storage::{FileStorage, VaultStorage},
+    utils::{get_vault_dir, get_vault_path, sanitize_secret_name, validate_secret_value, DEFAULT_MAX_SECRET_SIZE},
+    vault::{BackupData, InitOptions, LoadOptions, MergeStrategy, SecretEntry, SecretStatus, Vault, VaultData, VaultFormat},
 };
 use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
 use tempfile::TempDir;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn setup_test_env() -> TempDir {
+    // HOME and SENTINELVAULT_DIR are process-global, so tests that touch the
+    // filesystem run one at a time
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    struct TestEnv {
+        _temp_dir: TempDir,
+        _guard: MutexGuard<'static, ()>,
+    }
+
+    fn setup_test_env() -> TestEnv {
+        let guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let temp_dir = TempDir::new().unwrap();
+        // The config file stays under HOME; the vault goes where the variable says
         std::env::set_var("HOME", temp_dir.path());
-        temp_dir
+        std::env::set_var(VAULT_DIR_ENV, "~/vault");
+        TestEnv {
+            _temp_dir: temp_dir,
+            _guard: guard,
+        }
+    }
+
+    fn init_test_vault(password: &str) {
+        Identity::new(password).unwrap().save().unwrap();
+        let vault_data = ron::to_string(&VaultData::default()).unwrap();
+        std::fs::write(get_vault_path().unwrap(), vault_data).unwrap();
+    }
+
+    fn read_secret(vault: &mut Vault, name: &str) -> Option<String> {
+        vault.get_secret(name).unwrap().map(|value| value.expose().to_string())
+    }
+
+    fn read_vault_data() -> VaultData {
+        ron::from_str(&std::fs::read_to_string(get_vault_path().unwrap()).unwrap()).unwrap()
     }
 
     #[test]
@@ -33,6 +68,1082 @@ mod tests {
         assert!(loaded_identity.verify_password(password).unwrap());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        
+        let vault_path = get_vault_path().unwrap();
+        std::fs::set_permissions(&vault_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        
+        assert_eq!(mode(&vault_path), 0o600);
+        assert_eq!(mode(vault_path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&vault_path.with_file_name("identity.ron")), 0o600);
+    }
+
+    #[test]
+    fn test_doctor_checks() {
+        let _env = setup_test_env();
+        
+        let checks = sentinelvault::doctor::run_checks();
+        let failed: Vec<_> = checks.iter().filter(|check| !check.passed).collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "Vault directory");
+        
+        init_test_vault("test_password_123");
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        assert!(sentinelvault::doctor::run_checks().iter().all(|check| check.passed));
+        
+        std::fs::write(get_vault_path().unwrap(), "not a vault").unwrap();
+        let checks = sentinelvault::doctor::run_checks();
+        let failed: Vec<_> = checks.iter().filter(|check| !check.passed).map(|check| check.name.as_str()).collect();
+        assert_eq!(failed, vec!["Vault file"]);
+    }
+
+    #[test]
+    fn test_status_without_unlocking() {
+        let _env = setup_test_env();
+        
+        let status = sentinelvault::doctor::status().unwrap();
+        assert!(status.identity.is_none() && status.vault.is_none());
+        assert!(!status.is_healthy());
+        
+        init_test_vault("test_password_123");
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        vault.add_secret("db_password", "hunter2").unwrap();
+        
+        let status = sentinelvault::doctor::status().unwrap();
+        assert!(status.is_healthy());
+        let contents = status.contents.unwrap();
+        assert_eq!(contents.secrets, 2);
+        assert_eq!(contents.version, CURRENT_VERSION);
+        assert_eq!(contents.format, VaultFormat::Ron);
+        assert_eq!(status.vault.unwrap().size, std::fs::metadata(get_vault_path().unwrap()).unwrap().len());
+        
+        std::fs::write(get_vault_path().unwrap(), "not a vault").unwrap();
+        assert!(sentinelvault::doctor::status().is_err());
+    }
+
+    #[test]
+    fn test_open_with_password() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "sk-123").unwrap();
+        
+        let mut reopened = Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(read_secret(&mut reopened, "api_key").as_deref(), Some("sk-123"));
+        
+        assert!(Vault::open_with_password("wrong_password").is_err());
+    }
+
+    #[test]
+    fn test_legacy_vault_migrates_to_data_key() {
+        let _env = setup_test_env();
+        let password = "test_password_123";
+        let identity = Identity::new(password).unwrap();
+        identity.save().unwrap();
+        
+        // Pre-envelope vaults encrypt secrets directly with the password-derived key
+        let password_key = identity.derive_key(password).unwrap();
+        let legacy_engine = CryptoEngine::new(&password_key);
+        let mut legacy = VaultData::default();
+        legacy.secrets.insert(
+            "api_key".to_string(),
+            SecretEntry::new(legacy_engine.encrypt("sk-legacy").unwrap()),
+        );
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&legacy).unwrap()).unwrap();
+        
+        let mut vault = Vault::open_with_password(password).unwrap();
+        assert_eq!(read_secret(&mut vault, "api_key").as_deref(), Some("sk-legacy"));
+        
+        let migrated = read_vault_data();
+        assert!(migrated.wrapped_key.is_some());
+        assert!(legacy_engine.decrypt(&migrated.secrets["api_key"].encrypted_value).is_err());
+    }
+
+    #[test]
+    fn test_short_identity_salt_is_rejected() {
+        let _env = setup_test_env();
+        let mut identity = Identity::new("test_password_123").unwrap();
+        assert_eq!(identity.salt.len(), 32);
+        
+        identity.salt.truncate(8);
+        identity.save().unwrap();
+        let err = Identity::load().unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_weak_kdf_is_upgraded_on_login() {
+        use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
+        
+        let _env = setup_test_env();
+        let password = "test_password_123";
+        // Identities created before the cost was raised hashed with Argon2's defaults
+        let legacy_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::encode_b64(b"legacy-salt-1234").unwrap())
+            .unwrap()
+            .to_string();
+        let identity = Identity::from_backup(&legacy_hash, &[7u8; 32]);
+        identity.save().unwrap();
+        assert!(identity.needs_kdf_upgrade());
+        
+        let legacy_key = identity.derive_key(password).unwrap();
+        let data_key = generate_key();
+        let mut vault_data = VaultData {
+            wrapped_key: Some(wrap_key(&legacy_key, &data_key).unwrap()),
+            ..VaultData::default()
+        };
+        vault_data.secrets.insert(
+            "api_key".to_string(),
+            SecretEntry::new(CryptoEngine::new(&data_key).encrypt("sk-test").unwrap()),
+        );
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        
+        std::env::set_var(PASSWORD_ENV, password);
+        let skipped = Vault::load(&AuthOptions { skip_kdf_upgrade: true, ..AuthOptions::default() });
+        assert!(skipped.is_ok());
+        assert_eq!(Identity::load().unwrap().password_hash, legacy_hash);
+        
+        let mut vault = Vault::load(&AuthOptions::default()).unwrap();
+        std::env::remove_var(PASSWORD_ENV);
+        assert_eq!(read_secret(&mut vault, "api_key").as_deref(), Some("sk-test"));
+        
+        let upgraded = Identity::load().unwrap();
+        assert!(!upgraded.needs_kdf_upgrade());
+        assert_eq!(KdfParams::from_hash(&upgraded.password_hash).unwrap(), KdfParams::RECOMMENDED);
+        
+        let mut reopened = Vault::open_with_password(password).unwrap();
+        assert_eq!(read_secret(&mut reopened, "api_key").as_deref(), Some("sk-test"));
+    }
+
+    #[test]
+    fn test_old_vault_version_is_upgraded_on_open() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault_data = read_vault_data();
+        vault_data.version = "0.1.0".to_string();
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        
+        Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(read_vault_data().version, CURRENT_VERSION);
+        
+        vault_data.version = "9.0.0".to_string();
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        let err = Vault::open_with_password("test_password_123").err().unwrap();
+        assert!(err.to_string().contains("newer than this build supports"));
+    }
+
+    #[test]
+    fn test_clock_skew_detection() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert!(vault.clock_skew().is_none());
+        vault.add_secret("api_key", "value").unwrap();
+        assert!(read_vault_data().last_seen.unwrap() <= Utc::now());
+        
+        // Pretend the vault was last saved an hour from now, as if the clock had been set back
+        let future = Utc::now() + chrono::Duration::hours(1);
+        let mut vault_data = read_vault_data();
+        vault_data.last_seen = Some(future);
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert!(vault.clock_skew().unwrap() > chrono::Duration::minutes(59));
+        
+        // Saving must not move the marker back, or one override would reset the guard
+        vault.add_secret("other", "value").unwrap();
+        assert_eq!(read_vault_data().last_seen, Some(future));
+    }
+
+    #[test]
+    fn test_rewrap_keeps_secrets_readable() {
+        let _env = setup_test_env();
+        init_test_vault("old_password_123");
+        
+        let mut vault = Vault::open_with_password("old_password_123").unwrap();
+        vault.add_secret("api_key", "sk-123").unwrap();
+        let ciphertext_before = read_vault_data().secrets["api_key"].encrypted_value.ciphertext.clone();
+        
+        let mut identity = Identity::load().unwrap();
+        let old_key = identity.derive_key("old_password_123").unwrap();
+        let new_key = identity.change_password(&old_key, "new_password_456", None).unwrap();
+        vault.rewrap_key(&new_key).unwrap();
+        identity.save().unwrap();
+        
+        // Only the data key is re-wrapped; secret ciphertext is unchanged
+        assert_eq!(read_vault_data().secrets["api_key"].encrypted_value.ciphertext, ciphertext_before);
+        
+        let mut reopened = Vault::open_with_password("new_password_456").unwrap();
+        assert_eq!(read_secret(&mut reopened, "api_key").as_deref(), Some("sk-123"));
+        assert!(Vault::open_with_password("old_password_123").is_err());
+    }
+
+    #[test]
+    fn test_restore_backup_from_before_password_change() {
+        let _env = setup_test_env();
+        init_test_vault("old_password_123");
+        
+        let mut vault = Vault::open_with_password("old_password_123").unwrap();
+        vault.add_secret("api_key", "sk-123").unwrap();
+        let backup = vault.create_backup().unwrap();
+        
+        let mut identity = Identity::load().unwrap();
+        let old_key = identity.derive_key("old_password_123").unwrap();
+        let new_key = identity.change_password(&old_key, "new_password_456", None).unwrap();
+        vault.rewrap_key(&new_key).unwrap();
+        identity.save().unwrap();
+        
+        vault.add_secret("api_key", "sk-456").unwrap();
+        assert_eq!(vault.restore_from_backup(backup).unwrap(), 1);
+        
+        let mut reopened = Vault::open_with_password("new_password_456").unwrap();
+        assert_eq!(read_secret(&mut reopened, "api_key").as_deref(), Some("sk-123"));
+    }
+
+    #[test]
+    fn test_detailed_stats() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        let empty = vault.get_detailed_stats().unwrap();
+        assert!(empty.oldest_secret.is_none());
+        assert!(empty.average_lease.is_none());
+        
+        vault.add_secret("first", "value").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        vault.add_secret("second", "value").unwrap();
+        vault.set_expiry("first", "10m", false).unwrap();
+        vault.set_expiry("second", "30m", false).unwrap();
+        
+        let details = vault.get_detailed_stats().unwrap();
+        assert_eq!(details.stats.total_secrets, 2);
+        assert_eq!(details.oldest_secret.unwrap().0, "first");
+        assert_eq!(details.newest_secret.unwrap().0, "second");
+        assert!(details.most_accessed.is_none());
+        assert_eq!(details.average_lease.unwrap().num_minutes(), 20);
+    }
+
+    #[test]
+    fn test_sliding_expiry() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("used", "value").unwrap();
+        vault.add_secret("abandoned", "value").unwrap();
+        vault.set_expiry("used", "1s", true).unwrap();
+        vault.set_expiry("abandoned", "1s", true).unwrap();
+        
+        // Keep reading past the original one-second expiry
+        for _ in 0..3 {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            assert!(read_secret(&mut vault, "used").is_some());
+        }
+        
+        assert!(read_secret(&mut vault, "abandoned").is_none());
+        
+        let lease = read_vault_data().lease_manager.get_lease("used").cloned().unwrap();
+        assert!(lease.sliding);
+        assert!(!lease.is_expired());
+    }
+
+    #[test]
+    fn test_open_with_storage() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("team.ron");
+        let storage = FileStorage::new(&path);
+        storage.write(ron::to_string(&VaultData::default()).unwrap().as_bytes()).unwrap();
+        let key = generate_key();
+        
+        let mut vault = Vault::open_with_storage(Box::new(storage.clone()), key.clone(), &LoadOptions::default()).unwrap();
+        vault.add_secret("shared", "value").unwrap();
+        assert!(vault.get_stats().unwrap().vault_size > 0);
+        
+        let mut vault = Vault::open_with_storage(Box::new(storage), key, &LoadOptions::default()).unwrap();
+        assert_eq!(read_secret(&mut vault, "shared").unwrap(), "value");
+        
+        let err = Vault::open_with_storage(Box::new(FileStorage::new(temp_dir.path().join("missing.ron"))), generate_key(), &LoadOptions::default()).err().unwrap();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotInitialized)));
+    }
+
+    #[test]
+    fn test_binary_vault_format() {
+        let mut vault = Vault::in_memory(generate_key());
+        for i in 0..50 {
+            vault.add_secret(&format!("service-{}/token", i), "sk-0123456789abcdef").unwrap();
+        }
+        vault.set_expiry("service-1/token", "1h", true).unwrap();
+        
+        let ron_bytes = VaultFormat::Ron.encode(vault.data()).unwrap();
+        let binary_bytes = VaultFormat::Binary.encode(vault.data()).unwrap();
+        assert!(binary_bytes.len() < ron_bytes.len());
+        assert_eq!(VaultFormat::detect(&ron_bytes), VaultFormat::Ron);
+        assert_eq!(VaultFormat::detect(&binary_bytes), VaultFormat::Binary);
+        
+        let decoded = VaultFormat::decode(&binary_bytes).unwrap();
+        assert_eq!(decoded.secrets.len(), 50);
+        assert!(decoded.lease_manager.get_lease("service-1/token").is_some());
+        assert!(VaultFormat::decode(&binary_bytes[..binary_bytes.len() / 2]).is_err());
+        
+        // Older layouts are still read. Aliases are the last field, and an
+        // empty map is a single zero byte.
+        let mut v2_bytes = VaultFormat::Binary.encode(&VaultData::default()).unwrap();
+        assert_eq!(v2_bytes.pop(), Some(0));
+        v2_bytes[5] = 2;
+        assert_eq!(VaultFormat::detect(&v2_bytes), VaultFormat::Binary);
+        assert!(VaultFormat::decode(&v2_bytes).unwrap().aliases.is_empty());
+        v2_bytes[5] = 1;
+        assert!(VaultFormat::decode(&v2_bytes).unwrap().secrets.is_empty());
+        v2_bytes[5] = 99;
+        assert!(VaultFormat::decode(&v2_bytes).is_err());
+        
+        // Opening a binary vault keeps saving it as binary
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault.ron"));
+        let key = generate_key();
+        storage.write(&VaultFormat::Binary.encode(&VaultData::default()).unwrap()).unwrap();
+        let mut reopened = Vault::open_with_storage(Box::new(storage.clone()), key, &LoadOptions::default()).unwrap();
+        assert_eq!(reopened.format(), VaultFormat::Binary);
+        reopened.add_secret("api_key", "value").unwrap();
+        assert_eq!(VaultFormat::detect(&storage.read().unwrap()), VaultFormat::Binary);
+        assert_eq!(read_secret(&mut reopened, "api_key").unwrap(), "value");
+    }
+
+    #[test]
+    fn test_in_memory_vault() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("api_key", "sk-123").unwrap();
+        assert_eq!(read_secret(&mut vault, "api_key").unwrap(), "sk-123");
+        
+        // Saves go to memory, so a reload sees them
+        vault.reload().unwrap();
+        assert_eq!(vault.data().secrets["api_key"].access_count, 1);
+        assert!(vault.remove_secret("api_key").unwrap());
+        assert!(read_secret(&mut vault, "api_key").is_none());
+    }
+
+    #[test]
+    fn test_expiring_within() {
+        let mut vault = Vault::in_memory(generate_key());
+        for name in ["db-pass", "api-key", "cert", "permanent"] {
+            vault.add_secret(name, "value").unwrap();
+        }
+        vault.set_expiry("db-pass", "2h", false).unwrap();
+        vault.set_expiry("api-key", "23h", false).unwrap();
+        vault.set_expiry("cert", "30d", false).unwrap();
+        
+        assert_eq!(vault.expiring_within(chrono::Duration::hours(24)), vec!["api-key", "db-pass"]);
+        vault.remove_secret("db-pass").unwrap();
+        assert_eq!(vault.expiring_within(chrono::Duration::hours(24)), vec!["api-key"]);
+    }
+
+    #[test]
+    fn test_secret_status_and_reload() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("temp", "value").unwrap();
+        assert_eq!(vault.secret_status("temp").unwrap(), SecretStatus::Permanent);
+        assert_eq!(vault.secret_status("missing").unwrap(), SecretStatus::Missing);
+        
+        // Changes made through another handle show up after a reload
+        let mut other = Vault::open_with_password("test_password_123").unwrap();
+        other.set_expiry("temp", "1h", false).unwrap();
+        assert_eq!(vault.secret_status("temp").unwrap(), SecretStatus::Permanent);
+        vault.reload().unwrap();
+        assert!(matches!(vault.secret_status("temp").unwrap(), SecretStatus::Expires(_)));
+        
+        other.set_expiry("temp", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        vault.reload().unwrap();
+        assert_eq!(vault.secret_status("temp").unwrap(), SecretStatus::Expired);
+        
+        other.remove_secret("temp").unwrap();
+        vault.reload().unwrap();
+        assert_eq!(vault.secret_status("temp").unwrap(), SecretStatus::Missing);
+    }
+
+    #[test]
+    fn test_clear_expiry() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("temp", "value").unwrap();
+        vault.set_expiry("temp", "1h", false).unwrap();
+        vault.set_read_limit("temp", 2).unwrap();
+        
+        assert!(vault.clear_expiry("temp").unwrap());
+        assert!(!vault.clear_expiry("temp").unwrap());
+        assert!(vault.clear_expiry("missing").is_err());
+        
+        let vault_data = read_vault_data();
+        assert!(vault_data.lease_manager.get_lease("temp").is_none());
+        assert!(vault_data.lease_manager.get_read_limit("temp").is_none());
+    }
+
+    #[test]
+    fn test_cleanup_drops_read_limit() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("token", "old").unwrap();
+        vault.set_read_limit("token", 1).unwrap();
+        vault.set_expiry("token", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        
+        // A secret re-added under the name doesn't inherit the old limit
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("token", "new").unwrap();
+        assert_eq!(read_secret(&mut vault, "token").unwrap(), "new");
+        assert_eq!(read_secret(&mut vault, "token").unwrap(), "new");
+    }
+
+    #[test]
+    fn test_set_expiries() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        for name in ["temp-1", "temp-2", "keep"] {
+            vault.add_secret(name, "value").unwrap();
+        }
+        
+        let names = vec!["temp-1".to_string(), "missing-a".to_string(), "missing-b".to_string()];
+        let err = vault.set_expiries(&names, "1h", false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VaultError>(),
+            Some(VaultError::NotFound(names)) if names == "missing-a, missing-b"
+        ));
+        assert!(read_vault_data().lease_manager.get_lease("temp-1").is_none());
+        
+        let matched = vault.match_secrets(&["temp-*".to_string()], true).unwrap();
+        vault.set_expiries(&matched, "1h", false).unwrap();
+        
+        let vault_data = read_vault_data();
+        assert_eq!(vault_data.lease_manager.active_leases_count(), 2);
+        assert!(vault_data.lease_manager.get_lease("keep").is_none());
+    }
+
+    #[test]
+    fn test_load_without_cleanup_keeps_expired() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("gone", "value").unwrap();
+        vault.add_secret("kept", "value").unwrap();
+        vault.set_expiry("gone", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        
+        let key = Identity::load().unwrap().unlock(Some("test_password_123"), None).unwrap();
+        let mut vault = Vault::open_with_key_using(key.clone(), &LoadOptions { cleanup: false }).unwrap();
+        let expired = vault.list_expired_secrets();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, "gone");
+        assert!(vault.list_secrets().unwrap().iter().all(|(name, _)| name != "gone"));
+        assert!(read_secret(&mut vault, "gone").is_none());
+        
+        let vault = Vault::open_with_key(key).unwrap();
+        assert!(vault.list_expired_secrets().is_empty());
+    }
+
+    #[test]
+    fn test_remove_secrets_by_name_and_glob() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        for name in ["db-user", "db-pass", "api-key", "other"] {
+            vault.add_secret(name, "value").unwrap();
+        }
+        
+        let globbed = vault.match_secrets(&["db-*".to_string()], true).unwrap();
+        assert_eq!(globbed, vec!["db-pass", "db-user"]);
+        assert_eq!(vault.remove_secrets(&["db-*".to_string()], true).unwrap(), 2);
+        
+        let names = vec!["api-key".to_string(), "missing".to_string()];
+        assert_eq!(vault.remove_secrets(&names, false).unwrap(), 1);
+        
+        let reopened = Vault::open_with_password("test_password_123").unwrap();
+        let remaining: Vec<String> = reopened.list_secrets().unwrap().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(remaining, vec!["other"]);
+    }
+
+    #[test]
+    fn test_configured_max_secret_size() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(vault.data().max_secret_size, DEFAULT_MAX_SECRET_SIZE);
+        assert!(vault.add_secret("cert", &"x".repeat(20_000)).is_err());
+        
+        let mut vault_data = read_vault_data();
+        vault_data.max_secret_size = 50_000;
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("cert", &"x".repeat(20_000)).unwrap();
+        assert_eq!(read_secret(&mut vault, "cert").unwrap().len(), 20_000);
+    }
+
+    #[test]
+    fn test_max_secrets() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("first", "value").unwrap();
+        vault.add_secret("second", "value").unwrap();
+        assert_eq!(vault.get_stats().unwrap().max_secrets, None);
+        
+        assert!(vault.set_max_secrets(Some(0)).is_err());
+        vault.set_max_secrets(Some(2)).unwrap();
+        assert_eq!(vault.get_stats().unwrap().max_secrets, Some(2));
+        
+        let err = vault.add_secret("third", "value").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::SecretLimit { limit: 2 })));
+        // Overwriting doesn't grow the vault
+        vault.add_secret("first", "updated").unwrap();
+        
+        let err = vault.import_csv("name,value\nsecond,again\nthird,value\n").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::SecretLimit { .. })));
+        assert_eq!(vault.data().secrets.len(), 2);
+        
+        vault.set_max_secrets(None).unwrap();
+        vault.add_secret("third", "value").unwrap();
+    }
+
+    #[test]
+    fn test_identity_export_and_import() {
+        let _env = setup_test_env();
+        let password = "correct_password_1";
+        init_test_vault(password);
+        // Opening wraps a data key, so the import check has something to unwrap
+        Vault::open_with_password(password).unwrap();
+        
+        let temp_dir = TempDir::new().unwrap();
+        let exported = temp_dir.path().join("id.ron");
+        export_identity(&exported, false).unwrap();
+        assert!(export_identity(&exported, false).is_err());
+        
+        let password_file = temp_dir.path().join("password");
+        std::fs::write(&password_file, password).unwrap();
+        let options = AuthOptions {
+            password_file: Some(password_file.clone()),
+            ..AuthOptions::default()
+        };
+        assert!(import_identity(&exported, &options, false).is_err());
+        
+        Identity::delete().unwrap();
+        import_identity(&exported, &options, false).unwrap();
+        Vault::open_with_password(password).unwrap();
+        
+        // A valid identity for a different password can't replace this one
+        let other = temp_dir.path().join("other.ron");
+        std::fs::write(&other, ron::to_string(&Identity::new("other_password_1").unwrap()).unwrap()).unwrap();
+        std::fs::write(&password_file, "other_password_1").unwrap();
+        let err = import_identity(&other, &options, true).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AuthFailed(_))));
+        Vault::open_with_password(password).unwrap();
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.set_max_secrets(Some(5)).unwrap();
+        vault.add_secret("first", "value").unwrap();
+        vault.add_secret("second", "value").unwrap();
+        vault.set_expiry("first", "1h", false).unwrap();
+        
+        assert_eq!(vault.remove_all().unwrap(), 2);
+        assert!(vault.data().secrets.is_empty());
+        assert!(vault.data().lease_manager.get_lease("first").is_none());
+        // Settings survive the wipe
+        assert_eq!(vault.data().max_secrets, Some(5));
+        assert_eq!(vault.remove_all().unwrap(), 0);
+        
+        vault.add_secret("third", "value").unwrap();
+        vault.set_locked(true).unwrap();
+        let err = vault.remove_all().unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::ReadOnly)));
+        assert_eq!(vault.data().secrets.len(), 1);
+    }
+
+    #[test]
+    fn test_destroy() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        
+        // A locked vault keeps both its file and its identity
+        vault.set_locked(true).unwrap();
+        assert!(vault.destroy().is_err());
+        assert!(Identity::exists());
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.set_locked(false).unwrap();
+        assert_eq!(vault.destroy().unwrap(), 1);
+        assert!(!Identity::exists());
+        assert!(!get_vault_path().unwrap().exists());
+    }
+
+    #[test]
+    fn test_get_secret_field() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("db", r#"{"user":"app","password":"s3cret"}"#).unwrap();
+        vault.add_secret("plain", "not json").unwrap();
+        
+        let password = vault.get_secret_field("db", "password").unwrap().unwrap();
+        assert_eq!(password.expose(), "s3cret");
+        assert!(vault.get_secret_field("db", "host").is_err());
+        assert!(vault.get_secret_field("plain", "user").is_err());
+        assert!(vault.get_secret_field("missing", "user").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let _env = setup_test_env();
+        
+        let err = Vault::open_with_password("test_password_123").err().unwrap();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotInitialized)));
+        
+        init_test_vault("test_password_123");
+        
+        let err = Vault::open_with_password("wrong_password").err().unwrap();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AuthFailed(_))));
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        let err = vault.set_expiry("missing", "10m", false).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotFound(name)) if name == "missing"));
+        assert_eq!(err.to_string(), "Secret 'missing' not found");
+        
+        std::fs::write(get_vault_path().unwrap(), "not a vault").unwrap();
+        let err = Vault::open_with_password("test_password_123").err().unwrap();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_name_policy() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("Legacy_Key", "value").unwrap();
+        
+        let mismatched = vault.set_name_pattern(Some("[a-z0-9_.-]+")).unwrap();
+        assert_eq!(mismatched, vec!["Legacy_Key"]);
+        assert!(vault.set_name_pattern(Some("[unclosed")).is_err());
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert!(vault.add_secret("db.password", "value").is_ok());
+        assert!(vault.add_secret("DB-Password", "value").is_err());
+        // The built-in blocklist still applies even if the policy would allow it
+        vault.set_name_pattern(Some(".+")).unwrap();
+        assert!(vault.add_secret("../escape", "value").is_err());
+        
+        vault.set_name_pattern(None).unwrap();
+        assert!(vault.add_secret("DB-Password", "value").is_ok());
+    }
+
+    #[test]
+    fn test_csv_import_export() {
+        let mut vault = Vault::in_memory(generate_key());
+        let csv = "name,value\napi_key,sk-123\nprod/db,\"user=app, pass=\"\"x\"\"\"\nmulti,\"line one\nline two\"\n";
+        assert_eq!(vault.import_csv(csv).unwrap(), 3);
+        assert_eq!(read_secret(&mut vault, "prod/db").unwrap(), "user=app, pass=\"x\"");
+        assert_eq!(read_secret(&mut vault, "multi").unwrap(), "line one\nline two");
+        
+        // A bad row anywhere rejects the whole file
+        assert!(vault.import_csv("name,value\nfine,1\n../escape,2\n").is_err());
+        assert!(!vault.data().secrets.contains_key("fine"));
+        assert!(vault.import_csv("key,secret\nfine,1\n").is_err());
+        
+        vault.add_secret("temp", "gone").unwrap();
+        vault.set_expiry("temp", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        
+        let exported = vault.export_csv().unwrap();
+        assert!(!exported.contains("temp"));
+        
+        for name in ["api_key", "prod/db", "multi"] {
+            vault.remove_secret(name).unwrap();
+        }
+        assert_eq!(vault.import_csv(&exported).unwrap(), 3);
+        assert_eq!(read_secret(&mut vault, "prod/db").unwrap(), "user=app, pass=\"x\"");
+        assert_eq!(read_secret(&mut vault, "multi").unwrap(), "line one\nline two");
+    }
+
+    #[test]
+    fn test_vault_dir_from_config_and_env() {
+        let _env = setup_test_env();
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(get_vault_dir().unwrap(), home.join("vault"));
+        std::env::remove_var(VAULT_DIR_ENV);
+        assert_eq!(get_vault_dir().unwrap(), home.join(".sentinelvault"));
+        
+        let config = config_path().unwrap();
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(&config, "vault_dir = \"vaults/work\"\n").unwrap();
+        assert_eq!(get_vault_dir().unwrap(), home.join("vaults/work"));
+        init_test_vault("test_password_123");
+        assert!(home.join("vaults/work/vault.ron").exists());
+        assert!(Vault::open_with_password("test_password_123").is_ok());
+        
+        std::env::set_var(VAULT_DIR_ENV, home.join("elsewhere"));
+        assert_eq!(get_vault_dir().unwrap(), home.join("elsewhere"));
+        assert_eq!(get_vault_path().unwrap(), home.join("elsewhere/vault.ron"));
+        // `~` is expanded, and relative paths start from the current directory
+        std::env::set_var(VAULT_DIR_ENV, "~/personal");
+        assert_eq!(get_vault_dir().unwrap(), home.join("personal"));
+        std::env::set_var(VAULT_DIR_ENV, "relative/vault");
+        assert_eq!(get_vault_dir().unwrap(), std::env::current_dir().unwrap().join("relative/vault"));
+        std::env::remove_var(VAULT_DIR_ENV);
+        
+        std::fs::write(&config, "vault_dir = 3").unwrap();
+        assert!(get_vault_dir().is_err());
+    }
+
+    #[test]
+    fn test_read_only_mode() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("kept", "value").unwrap();
+        vault.set_locked(true).unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert!(vault.data().locked);
+        for result in [
+            vault.add_secret("new", "value"),
+            vault.set_expiry("kept", "10m", false),
+            vault.set_read_limit("kept", 1),
+            vault.remove_secret("kept").map(|_| ()),
+        ] {
+            assert!(matches!(result.unwrap_err().downcast_ref::<VaultError>(), Some(VaultError::ReadOnly)));
+        }
+        
+        assert_eq!(read_secret(&mut vault, "kept").unwrap(), "value");
+        assert_eq!(vault.list_secrets().unwrap().len(), 1);
+        
+        vault.set_locked(false).unwrap();
+        assert!(vault.remove_secret("kept").unwrap());
+    }
+
+    #[test]
+    fn test_backup_formats_round_trip() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "secret_value").unwrap();
+        vault.set_expiry("api_key", "1h", true).unwrap();
+        let backup = vault.create_backup().unwrap();
+        let expected = serde_json::to_string(&backup).unwrap();
+        
+        let dir = TempDir::new().unwrap();
+        let files = [
+            ("backup.json", serde_json::to_string_pretty(&backup).unwrap()),
+            ("backup.yaml", serde_yaml::to_string(&backup).unwrap()),
+            ("backup.ron", ron::to_string(&backup).unwrap()),
+        ];
+        for (file, contents) in files {
+            let path = dir.path().join(file);
+            std::fs::write(&path, contents).unwrap();
+            let loaded = BackupData::load(&path).unwrap();
+            assert_eq!(serde_json::to_string(&loaded).unwrap(), expected, "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_init_from_backup() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "secret_value").unwrap();
+        let backup_path = std::env::temp_dir().join(format!("sentinelvault-backup-{}.json", std::process::id()));
+        std::fs::write(&backup_path, serde_json::to_string(&vault.create_backup().unwrap()).unwrap()).unwrap();
+        
+        // Fresh machine
+        drop(_env);
+        let _env = setup_test_env();
+        let backup = BackupData::load(&backup_path).unwrap();
+        std::fs::remove_file(&backup_path).unwrap();
+        
+        let err = Vault::init_from_backup_with_password(&backup, "wrong_password", &InitOptions::default()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AuthFailed(_))));
+        
+        Vault::init_from_backup_with_password(&backup, "test_password_123", &InitOptions::default()).unwrap();
+        let mut restored = Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(read_secret(&mut restored, "api_key").unwrap(), "secret_value");
+        
+        let err = Vault::init_from_backup_with_password(&backup, "test_password_123", &InitOptions::default()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AlreadyInitialized)));
+    }
+
+    #[test]
+    fn test_machine_bound_backup() {
+        let _env = setup_test_env();
+        std::env::set_var(MACHINE_ID_ENV, "machine-a");
+        let mut identity = Identity::new("test_password_123").unwrap();
+        identity.machine_bound = true;
+        let key = identity.unlock(Some("test_password_123"), None).unwrap();
+        identity.save().unwrap();
+        let vault_data = VaultData { wrapped_key: Some(wrap_key(&key, &generate_key()).unwrap()), ..VaultData::default() };
+        std::fs::write(get_vault_path().unwrap(), ron::to_string(&vault_data).unwrap()).unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "bound").unwrap();
+        let backup = vault.create_backup().unwrap();
+        assert!(backup.identity_machine_bound);
+        assert!(backup.unlock("test_password_123", None).is_ok());
+        
+        // A new host given the old machine's id
+        drop(_env);
+        let _env = setup_test_env();
+        Vault::init_from_backup_with_password(&backup, "test_password_123", &InitOptions::default()).unwrap();
+        assert!(Identity::load().unwrap().machine_bound);
+        let mut restored = Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(read_secret(&mut restored, "api_key").unwrap(), "bound");
+        
+        std::env::set_var(MACHINE_ID_ENV, "machine-b");
+        let err = backup.unlock("test_password_123", None).unwrap_err();
+        std::env::remove_var(MACHINE_ID_ENV);
+        assert!(err.to_string().contains("bound to the machine"));
+    }
+
+    #[test]
+    fn test_merge_from_backup() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut mine = Vault::in_memory(generate_key());
+        mine.add_secret("shared", "mine").unwrap();
+        mine.add_secret("mine-only", "mine").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        
+        let mut theirs = Vault::open_with_password("test_password_123").unwrap();
+        theirs.add_secret("shared", "theirs").unwrap();
+        theirs.add_secret("newer-here", "theirs").unwrap();
+        theirs.add_secret("theirs-only", "theirs").unwrap();
+        theirs.set_expiry("theirs-only", "1h", true).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        mine.add_secret("newer-here", "mine").unwrap();
+        
+        let backup = theirs.create_backup().unwrap();
+        assert!(backup.unlock("wrong_password", None).is_err());
+        let key = backup.unlock("test_password_123", None).unwrap();
+        
+        let report = mine.merge(&backup.vault_data, &key, MergeStrategy::Newest).unwrap();
+        assert_eq!(report.added, vec!["theirs-only"]);
+        assert_eq!(report.updated, vec!["shared"]);
+        assert_eq!(report.skipped, vec!["newer-here"]);
+        assert_eq!(read_secret(&mut mine, "shared").unwrap(), "theirs");
+        assert_eq!(read_secret(&mut mine, "newer-here").unwrap(), "mine");
+        assert!(mine.data().lease_manager.get_lease("theirs-only").unwrap().sliding);
+        
+        let report = mine.merge(&backup.vault_data, &key, MergeStrategy::KeepMine).unwrap();
+        assert!(report.added.is_empty() && report.updated.is_empty());
+        
+        let report = mine.merge(&backup.vault_data, &key, MergeStrategy::KeepTheirs).unwrap();
+        assert_eq!(report.updated.len(), 3);
+        assert_eq!(read_secret(&mut mine, "newer-here").unwrap(), "theirs");
+        assert_eq!(read_secret(&mut mine, "mine-only").unwrap(), "mine");
+        
+        assert!(mine.merge(&backup.vault_data, &generate_key(), MergeStrategy::KeepTheirs).is_err());
+    }
+
+    #[test]
+    fn test_partial_backup_and_export() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("project/api", "sk-1").unwrap();
+        vault.add_secret("project/db", "pg-2").unwrap();
+        vault.add_secret("personal", "mine").unwrap();
+        vault.set_expiry("project/db", "1h", false).unwrap();
+        vault.set_expiry("personal", "1h", false).unwrap();
+        
+        let names = vault.match_secrets(&["project/*".to_string()], true).unwrap();
+        let backup = vault.create_backup_of(&names).unwrap();
+        let mut kept: Vec<&String> = backup.vault_data.secrets.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["project/api", "project/db"]);
+        assert!(backup.vault_data.lease_manager.get_lease("project/db").is_some());
+        assert!(backup.vault_data.lease_manager.get_lease("personal").is_none());
+        
+        // Still unlocks and merges like a full backup
+        let key = backup.unlock("test_password_123", None).unwrap();
+        let mut other = Vault::in_memory(generate_key());
+        let report = other.merge(&backup.vault_data, &key, MergeStrategy::Newest).unwrap();
+        assert_eq!(report.added, vec!["project/api", "project/db"]);
+        
+        let csv = vault.export_csv_of(&["personal".to_string()]).unwrap();
+        assert_eq!(csv.as_str(), "name,value\npersonal,mine\n");
+    }
+
+    #[test]
+    fn test_encrypted_backup() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "sk-123").unwrap();
+        let backup = vault.create_backup().unwrap();
+        
+        let encrypted = backup.encrypt("backup passphrase").unwrap();
+        let sealed = ron::to_string(&encrypted).unwrap();
+        assert!(!sealed.contains(&backup.identity_hash));
+        
+        let decrypted = encrypted.decrypt("backup passphrase").unwrap();
+        assert_eq!(decrypted.identity_hash, backup.identity_hash);
+        assert!(decrypted.vault_data.secrets.contains_key("api_key"));
+        
+        let err = encrypted.decrypt("wrong passphrase").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AuthFailed(_))));
+    }
+
+    #[test]
+    fn test_protected_secrets() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("root_key", "value").unwrap();
+        assert!(!vault.is_protected("root_key").unwrap());
+        
+        vault.set_protected("root_key", true).unwrap();
+        vault.add_secret("root_key", "rotated").unwrap();
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        assert!(vault.is_protected("root_key").unwrap());
+        assert!(!vault.is_protected("missing").unwrap());
+        assert!(vault.set_protected("missing", true).is_err());
+        
+        vault.set_protected("root_key", false).unwrap();
+        assert!(!vault.is_protected("root_key").unwrap());
+        assert_eq!(read_secret(&mut vault, "root_key").unwrap(), "rotated");
+    }
+
+    #[test]
+    fn test_list_values() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("b_key", "two").unwrap();
+        vault.add_secret("a_key", "one").unwrap();
+        vault.add_secret("once", "burn").unwrap();
+        vault.set_read_limit("once", 1).unwrap();
+        vault.add_secret("old", "gone").unwrap();
+        vault.set_expiry("old", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        
+        let values: Vec<(String, String)> = vault
+            .list_values()
+            .unwrap()
+            .into_iter()
+            .map(|(name, value)| (name, value.expose().to_string()))
+            .collect();
+        assert_eq!(values, vec![
+            ("a_key".to_string(), "one".to_string()),
+            ("b_key".to_string(), "two".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_decrypt_all_large_vault() {
+        use std::time::Instant;
+        
+        let key = generate_key();
+        let mut vault = Vault::in_memory(key.clone());
+        let mut csv = String::from("name,value\n");
+        for i in 0..4000 {
+            csv.push_str(&format!("secret_{},value-{}-{}\n", i, i, "x".repeat(64)));
+        }
+        assert_eq!(vault.import_csv(&csv).unwrap(), 4000);
+        
+        // Sequential baseline: decrypt each entry in turn with the data key
+        let data_key = unwrap_key(&key, vault.data().wrapped_key.as_ref().unwrap()).unwrap();
+        let engine = CryptoEngine::new(&data_key);
+        let start = Instant::now();
+        let sequential: HashMap<&String, String> = vault
+            .data()
+            .secrets
+            .iter()
+            .map(|(name, entry)| (name, engine.decrypt(&entry.encrypted_value).unwrap()))
+            .collect();
+        let sequential_time = start.elapsed();
+        
+        let start = Instant::now();
+        let parallel = vault.decrypt_all().unwrap();
+        let parallel_time = start.elapsed();
+        
+        assert_eq!(parallel.len(), 4000);
+        assert_eq!(parallel["secret_17"].expose(), format!("value-17-{}", "x".repeat(64)));
+        for (name, value) in &parallel {
+            assert_eq!(value.expose(), sequential[name]);
+        }
+        // A loose bound, so a slow or busy machine doesn't fail it: the slack
+        // covers starting the thread pool
+        assert!(
+            parallel_time < sequential_time * 4 + std::time::Duration::from_millis(500),
+            "decrypt_all took {:?}, decrypting in turn took {:?}",
+            parallel_time,
+            sequential_time
+        );
+    }
+
+    #[test]
+    fn test_password_from_environment() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        std::env::set_var(PASSWORD_ENV, "test_password_123");
+        let opened = Vault::load(&AuthOptions::default());
+        std::env::set_var(PASSWORD_ENV, "wrong_password");
+        let rejected = Vault::load(&AuthOptions::default());
+        std::env::remove_var(PASSWORD_ENV);
+        
+        assert!(opened.is_ok());
+        assert!(rejected.is_err());
+    }
+
+    #[test]
+    fn test_audit_values() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("github", "Sh4red-Token-9x!").unwrap();
+        vault.add_secret("gitlab", "Sh4red-Token-9x!").unwrap();
+        vault.add_secret("wifi", "password").unwrap();
+        vault.add_secret("api-key", "Zq8-unique-Value-31").unwrap();
+        vault.set_read_limit("api-key", 1).unwrap();
+        
+        let audit = vault.audit_values().unwrap();
+        assert_eq!(audit.duplicates, vec![vec!["github".to_string(), "gitlab".to_string()]]);
+        assert_eq!(audit.weak.len(), 1);
+        assert_eq!(audit.weak[0].0, "wifi");
+        
+        // Auditing must not burn reads
+        assert!(read_secret(&mut vault, "api-key").is_some());
+    }
+
     #[test]
     fn test_secret_encryption_roundtrip() {
         let key = SecretKey::new([42u8; 32]);
@@ -127,7 +1238,7 @@ mod tests {
         #[test]
         fn test_secret_value_validation(value in "\\PC{1,1000}") {
             // Property test: non-empty values without null bytes should be valid
-            let result = validate_secret_value(&value);
+            let result = validate_secret_value(&value, DEFAULT_MAX_SECRET_SIZE);
             if !value.contains('\0') && !value.is_empty() && value.len() <= 10_000 {
                 assert!(result.is_ok());
             }
@@ -180,10 +1291,400 @@ mod tests {
         assert!(entry.last_accessed.unwrap() > first_access);
     }
 
+    #[test]
+    fn test_interrupted_save_leaves_vault_intact() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        
+        // A crash mid-save only ever truncates the temporary file
+        let vault_path = get_vault_path().unwrap();
+        let tmp_path = vault_path.with_file_name("vault.ron.tmp");
+        let saved = std::fs::read(&vault_path).unwrap();
+        std::fs::write(&tmp_path, &saved[..saved.len() / 2]).unwrap();
+        
+        let mut reopened = Vault::open_with_password("test_password_123").unwrap();
+        assert_eq!(read_secret(&mut reopened, "api_key").unwrap(), "value");
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn test_restore_from_backup() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "original").unwrap();
+        vault.set_expiry("api_key", "1h", false).unwrap();
+        
+        let dir = get_vault_dir().unwrap();
+        let ron_path = dir.join("backup.ron");
+        let json_path = dir.join("backup.bak");
+        std::fs::write(&ron_path, ron::to_string(&vault.create_backup().unwrap()).unwrap()).unwrap();
+        std::fs::write(&json_path, serde_json::to_string(&vault.create_backup().unwrap()).unwrap()).unwrap();
+        
+        for (path, format) in [(&ron_path, None), (&json_path, Some("json"))] {
+            vault.add_secret("api_key", "changed").unwrap();
+            vault.add_secret("extra", "value").unwrap();
+            
+            let backup = BackupData::load_as(path, format).unwrap();
+            assert_eq!(vault.restore_from_backup(backup).unwrap(), 1);
+            let mut reopened = Vault::open_with_password("test_password_123").unwrap();
+            assert_eq!(read_secret(&mut reopened, "api_key").unwrap(), "original");
+            assert!(matches!(reopened.secret_status("api_key").unwrap(), SecretStatus::Expires(_)));
+            assert!(read_secret(&mut reopened, "extra").is_none());
+        }
+        assert!(BackupData::load_as(&json_path, Some("toml")).is_err());
+        
+        let mut foreign = vault.create_backup().unwrap();
+        foreign.vault_data.secrets.get_mut("api_key").unwrap().encrypted_value =
+            CryptoEngine::new(&generate_key()).encrypt("other").unwrap();
+        assert!(vault.restore_from_backup(foreign).is_err());
+        
+        let mut newer = vault.create_backup().unwrap();
+        newer.version = "99.0.0".to_string();
+        let err = vault.restore_from_backup(newer).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NewerVersion { .. })));
+        
+        // Restoring onto a machine without a vault
+        let mut newer = vault.create_backup().unwrap();
+        newer.version = "99.0.0".to_string();
+        drop(_env);
+        let _env = setup_test_env();
+        let err = Vault::init_from_backup_with_password(&newer, "test_password_123", &InitOptions::default()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NewerVersion { .. })));
+        assert!(!Identity::exists());
+    }
+
+    #[test]
+    fn test_reads_are_persisted() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        vault.add_secret("api_key", "value").unwrap();
+        read_secret(&mut vault, "api_key");
+        read_secret(&mut vault, "api_key");
+        
+        let reopened = Vault::open_with_password("test_password_123").unwrap();
+        let entry = &reopened.data().secrets["api_key"];
+        assert_eq!(entry.access_count, 2);
+        assert_eq!(entry.last_accessed, vault.data().secrets["api_key"].last_accessed);
+    }
+
+    #[test]
+    fn test_recent_secrets() {
+        let _env = setup_test_env();
+        init_test_vault("test_password_123");
+        
+        let mut vault = Vault::open_with_password("test_password_123").unwrap();
+        for name in ["first", "second", "unread"] {
+            vault.add_secret(name, "value").unwrap();
+        }
+        read_secret(&mut vault, "first");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        read_secret(&mut vault, "second");
+        
+        // Access times survive a reopen
+        let vault = Vault::open_with_password("test_password_123").unwrap();
+        let names: Vec<_> = vault.recent_secrets(5).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["second", "first"]);
+        assert_eq!(vault.recent_secrets(1).len(), 1);
+        assert_eq!(vault.data().secrets["first"].access_count, 1);
+    }
+
+    #[test]
+    fn test_stale_secrets() {
+        let mut vault = Vault::in_memory(generate_key());
+        for name in ["old_b", "old_a", "read"] {
+            vault.add_secret(name, "value").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        vault.add_secret("gone", "value").unwrap();
+        vault.set_expiry("gone", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        read_secret(&mut vault, "read");
+        vault.add_secret("new", "value").unwrap();
+        
+        let stale: Vec<_> = vault.stale_secrets(chrono::Duration::milliseconds(500)).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(stale, vec!["old_b", "old_a"]);
+        assert!(vault.stale_secrets(chrono::Duration::days(1)).is_empty());
+        
+        let entry = &vault.data().secrets["read"];
+        assert_eq!(entry.last_used(), entry.last_accessed.unwrap());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("github_token", "ghp_old").unwrap();
+        vault.add_alias("ci/token", "github_token").unwrap();
+        assert_eq!(read_secret(&mut vault, "ci/token").unwrap(), "ghp_old");
+        
+        // Updating the target updates what the alias reads
+        vault.add_secret("github_token", "ghp_new").unwrap();
+        assert_eq!(read_secret(&mut vault, "ci/token").unwrap(), "ghp_new");
+        assert_eq!(vault.secret_status("ci/token").unwrap(), SecretStatus::Permanent);
+        assert_eq!(vault.list_aliases(), vec![("ci/token".to_string(), "github_token".to_string())]);
+        assert!(vault.list_secrets().unwrap().iter().all(|(name, _)| name != "ci/token"));
+        
+        assert!(vault.add_secret("ci/token", "value").is_err());
+        assert!(vault.add_alias("other", "ci/token").is_err());
+        let err = vault.add_alias("other", "missing").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotFound(_))));
+        let err = vault.add_alias("github_token", "github_token").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AlreadyExists(_))));
+        
+        // The target can't be removed out from under its alias
+        assert!(vault.remove_secret("github_token").is_err());
+        assert_eq!(vault.remove_secrets(&["github_token".to_string(), "ci/token".to_string()], false).unwrap(), 2);
+        assert!(vault.list_aliases().is_empty());
+        
+        // Aliases survive the binary format
+        vault.add_secret("db", "value").unwrap();
+        vault.add_alias("database", "db").unwrap();
+        let decoded = VaultFormat::decode(&VaultFormat::Binary.encode(vault.data()).unwrap()).unwrap();
+        assert_eq!(decoded.aliases["database"], "db");
+        assert!(vault.remove_secret("database").unwrap());
+        assert!(vault.remove_secret("db").unwrap());
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        let _env = setup_test_env();
+        init_test_vault("batch-password");
+        let mut vault = Vault::open_with_password("batch-password").unwrap();
+        vault.add_secret("old_token", "value").unwrap();
+        vault.add_secret("db", "old").unwrap();
+        
+        let ops = batch::parse("add db new\nexpire db 1h\nremove old_token\nadd fresh value\n").unwrap();
+        vault.apply_batch(&ops).unwrap();
+        let mut reopened = Vault::open_with_password("batch-password").unwrap();
+        assert_eq!(read_secret(&mut reopened, "db").unwrap(), "new");
+        assert!(matches!(reopened.secret_status("db").unwrap(), SecretStatus::Expires(_)));
+        assert!(read_secret(&mut reopened, "old_token").is_none());
+        
+        // A failing operation rolls back the ones before it, in memory and on disk
+        let ops = batch::parse("remove fresh\nadd db changed\nexpire missing 1h\n").unwrap();
+        let err = vault.apply_batch(&ops).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotFound(_))));
+        assert!(format!("{:#}", err).starts_with("Operation 3 (missing) failed"));
+        for vault in [&mut vault, &mut Vault::open_with_password("batch-password").unwrap()] {
+            assert_eq!(read_secret(vault, "fresh").unwrap(), "value");
+            assert_eq!(read_secret(vault, "db").unwrap(), "new");
+        }
+    }
+
+    #[test]
+    fn test_secret_tags() {
+        let mut vault = Vault::in_memory(generate_key());
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        vault.add_secret_with_meta("db_url", "postgres://", &tags(&["prod", "db"]), Some("Primary Postgres")).unwrap();
+        vault.add_secret_with_meta("staging_db", "postgres://", &tags(&["staging", "db"]), None).unwrap();
+        vault.add_secret("plain", "value").unwrap();
+        vault.add_alias("database", "db_url").unwrap();
+        assert!(vault.add_secret_with_meta("bad", "value", &tags(&["two words"]), None).is_err());
+        
+        let tagged = |vault: &Vault, tag: &str| -> Vec<String> {
+            let mut names: Vec<String> = vault.data().secrets.keys().chain(vault.data().aliases.keys())
+                .filter(|name| vault.has_tag(name, tag))
+                .cloned()
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(tagged(&vault, "prod"), vec!["database", "db_url"]);
+        assert_eq!(tagged(&vault, "db"), vec!["database", "db_url", "staging_db"]);
+        assert!(tagged(&vault, "dev").is_empty());
+        
+        // Replacing the value keeps the metadata unless new metadata is given
+        vault.add_secret("db_url", "postgres://new").unwrap();
+        let entry = &vault.data().secrets["db_url"];
+        assert_eq!(entry.tags, tags(&["db", "prod"]));
+        assert_eq!(entry.description.as_deref(), Some("Primary Postgres"));
+        vault.add_secret_with_meta("db_url", "postgres://new", &tags(&["dev"]), None).unwrap();
+        assert_eq!(tagged(&vault, "dev"), vec!["database", "db_url"]);
+        
+        // Vaults written before tags load without them, in RON and binary
+        let mut old = Vault::in_memory(generate_key());
+        old.add_secret("api_key", "value").unwrap();
+        let ron = ron::to_string(old.data()).unwrap();
+        let legacy = ron.replace(",tags:[],description:None", "");
+        assert_ne!(legacy, ron);
+        let decoded = VaultFormat::decode(legacy.as_bytes()).unwrap();
+        assert!(decoded.secrets["api_key"].tags.is_empty());
+        
+        let binary = VaultFormat::Binary.encode(old.data()).unwrap();
+        let described = VaultFormat::Binary.encode(&{
+            let mut data = VaultFormat::decode(&binary).unwrap();
+            data.secrets.get_mut("api_key").unwrap().description = Some("x".to_string());
+            data
+        }).unwrap();
+        // The empty tag list and missing description are the two bytes
+        // ending where the encodings first differ
+        let at = binary.iter().zip(&described).position(|(a, b)| a != b).unwrap();
+        let mut layout_3 = binary.clone();
+        layout_3.drain(at - 1..=at);
+        layout_3[5] = 3;
+        let decoded = VaultFormat::decode(&layout_3).unwrap();
+        assert!(decoded.secrets["api_key"].tags.is_empty());
+        assert!(decoded.secrets["api_key"].description.is_none());
+    }
+
+    #[test]
+    fn test_search_secrets() {
+        let mut vault = Vault::in_memory(generate_key());
+        for name in ["AWS_SECRET_KEY", "aws_access_key", "github_token", "old_aws"] {
+            vault.add_secret(name, "value").unwrap();
+        }
+        vault.set_expiry("old_aws", "1s", false).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        
+        assert_eq!(vault.search_secrets("aws", false), vec!["AWS_SECRET_KEY", "aws_access_key"]);
+        assert_eq!(vault.search_secrets("KEY", false), vec!["AWS_SECRET_KEY", "aws_access_key"]);
+        assert_eq!(vault.search_secrets("aws_*", true), vec!["aws_access_key"]);
+        assert_eq!(vault.search_secrets("*_t?ken", true), vec!["github_token"]);
+        assert!(vault.search_secrets("aws", true).is_empty());
+        assert!(vault.search_secrets("gitlab", false).is_empty());
+    }
+
+    #[test]
+    fn test_rename_secret() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("api_kye", "value").unwrap();
+        vault.set_expiry("api_kye", "1h", true).unwrap();
+        vault.add_alias("ci/key", "api_kye").unwrap();
+        read_secret(&mut vault, "api_kye");
+        vault.add_secret("other", "value").unwrap();
+        let before = ron::to_string(&vault.data().secrets["api_kye"]).unwrap();
+        let lease = vault.data().lease_manager.get_lease("api_kye").cloned();
+        
+        vault.rename_secret("api_kye", "api_key").unwrap();
+        let data = vault.data();
+        assert!(!data.secrets.contains_key("api_kye"));
+        // The entry moves untouched: same ciphertext, timestamps and read count
+        assert_eq!(ron::to_string(&data.secrets["api_key"]).unwrap(), before);
+        assert_eq!(data.secrets["api_key"].access_count, 1);
+        assert_eq!(data.lease_manager.get_lease("api_key").cloned(), lease);
+        assert!(data.lease_manager.get_lease("api_kye").is_none());
+        assert_eq!(data.aliases["ci/key"], "api_key");
+        assert_eq!(read_secret(&mut vault, "api_key").unwrap(), "value");
+        
+        let err = vault.rename_secret("api_kye", "new").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::NotFound(_))));
+        let err = vault.rename_secret("api_key", "other").unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultError>(), Some(VaultError::AlreadyExists(_))));
+        assert!(vault.rename_secret("api_key", "ci/key").is_err());
+        assert!(vault.data().secrets.contains_key("api_key"));
+    }
+
+    #[test]
+    fn test_ensure_active() {
+        let mut vault = Vault::in_memory(generate_key());
+        vault.add_secret("api_key", "value").unwrap();
+        vault.add_secret("temp", "value").unwrap();
+        vault.set_expiry("temp", "1s", false).unwrap();
+        assert!(vault.ensure_active("api_key").is_ok());
+        assert!(vault.ensure_active("temp").is_ok());
+        
+        let err = vault.ensure_active("api_kye").unwrap_err();
+        let err = err.downcast_ref::<VaultError>().unwrap();
+        assert!(matches!(err, VaultError::NotFound(_)));
+        assert_eq!(err.exit_code(), 2);
+        
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        let err = vault.ensure_active("temp").unwrap_err();
+        let err = err.downcast_ref::<VaultError>().unwrap();
+        assert!(matches!(err, VaultError::Expired { name, .. } if name == "temp"));
+        assert!(err.to_string().starts_with("Secret 'temp' expired on "));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_vault_data_prune() {
+        let key = SecretKey::new([1u8; 32]);
+        let engine = CryptoEngine::new(&key);
+        let mut vault_data = VaultData::default();
+        
+        for name in ["expired", "active"] {
+            let entry = SecretEntry::new(engine.encrypt("value").unwrap());
+            vault_data.secrets.insert(name.to_string(), entry);
+        }
+        vault_data.lease_manager.add_lease("expired".to_string(), chrono::Duration::milliseconds(-1));
+        vault_data.lease_manager.add_lease("active".to_string(), chrono::Duration::minutes(10));
+        vault_data.lease_manager.add_lease("ghost".to_string(), chrono::Duration::minutes(10));
+        
+        let report = vault_data.prune(true);
+        assert_eq!(report.expired, vec!["expired"]);
+        assert_eq!(report.orphaned, vec!["ghost"]);
+        assert_eq!(vault_data.secrets.len(), 2);
+        assert!(vault_data.lease_manager.get_lease("ghost").is_some());
+        
+        let report = vault_data.prune(false);
+        assert!(!report.is_empty());
+        assert!(!vault_data.secrets.contains_key("expired"));
+        assert!(vault_data.secrets.contains_key("active"));
+        assert!(vault_data.lease_manager.get_lease("ghost").is_none());
+        assert!(vault_data.prune(false).is_empty());
+    }
+
+    #[test]
+    fn test_vault_data_fingerprint() {
+        let key = SecretKey::new([1u8; 32]);
+        let engine = CryptoEngine::new(&key);
+        let entry = SecretEntry::new(engine.encrypt("value").unwrap());
+        
+        let mut first = VaultData::default();
+        let mut second = VaultData::default();
+        for name in ["a", "b", "c"] {
+            first.secrets.insert(name.to_string(), entry.clone());
+        }
+        for name in ["c", "a", "b"] {
+            second.secrets.insert(name.to_string(), entry.clone());
+        }
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        assert_eq!(first.fingerprint().len(), 16);
+        
+        // Values don't count, names and update times do
+        second.secrets.get_mut("a").unwrap().encrypted_value = engine.encrypt("other").unwrap();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        second.secrets.get_mut("a").unwrap().updated_at += chrono::Duration::seconds(1);
+        assert_ne!(first.fingerprint(), second.fingerprint());
+        second.secrets.clear();
+        assert_ne!(first.fingerprint(), second.fingerprint());
+    }
+
+    #[test]
+    fn test_vault_data_remove_expired() {
+        let key = SecretKey::new([1u8; 32]);
+        let engine = CryptoEngine::new(&key);
+        let mut vault_data = VaultData::default();
+        
+        for name in ["b_expired", "a_expired", "active", "permanent"] {
+            let entry = SecretEntry::new(engine.encrypt("value").unwrap());
+            vault_data.secrets.insert(name.to_string(), entry);
+        }
+        vault_data.lease_manager.add_lease("a_expired".to_string(), chrono::Duration::milliseconds(-1));
+        vault_data.lease_manager.add_lease("b_expired".to_string(), chrono::Duration::milliseconds(-1));
+        vault_data.lease_manager.add_lease("active".to_string(), chrono::Duration::minutes(10));
+        vault_data.lease_manager.add_lease("ghost".to_string(), chrono::Duration::milliseconds(-1));
+        
+        assert_eq!(vault_data.remove_expired(true), vec!["a_expired", "b_expired"]);
+        assert_eq!(vault_data.secrets.len(), 4);
+        
+        assert_eq!(vault_data.remove_expired(false), vec!["a_expired", "b_expired"]);
+        assert!(vault_data.secrets.contains_key("active"));
+        assert!(vault_data.secrets.contains_key("permanent"));
+        assert!(vault_data.lease_manager.get_lease("a_expired").is_none());
+        // Orphaned leases are left for prune
+        assert!(vault_data.lease_manager.get_lease("ghost").is_some());
+        assert!(vault_data.remove_expired(false).is_empty());
+    }
+
     #[test]
     fn test_vault_data_versioning() {
         let vault_data = VaultData::default();
-        assert_eq!(vault_data.version, "0.1.0");
+        assert_eq!(vault_data.version, CURRENT_VERSION);
         assert!(vault_data.created_at <= Utc::now());
     }
 
@@ -194,7 +1695,7 @@ mod tests {
         
         // Test with a large but valid secret (9KB)
         let large_secret = "x".repeat(9000);
-        assert!(validate_secret_value(&large_secret).is_ok());
+        assert!(validate_secret_value(&large_secret, DEFAULT_MAX_SECRET_SIZE).is_ok());
         
         let encrypted = engine.encrypt(&large_secret).unwrap();
         let decrypted = engine.decrypt(&encrypted).unwrap();
@@ -203,7 +1704,7 @@ mod tests {
         
         // Test with too large secret
         let too_large_secret = "x".repeat(10_001);
-        assert!(validate_secret_value(&too_large_secret).is_err());
+        assert!(validate_secret_value(&too_large_secret, DEFAULT_MAX_SECRET_SIZE).is_err());
     }
 
     #[test]